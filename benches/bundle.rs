@@ -0,0 +1,114 @@
+// benchmarks run against a cache file supplied by the user, they're skipped without one:
+// REDSCRIPT_CACHE=path/to/final.redscripts cargo bench
+use std::hint::black_box;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use redscript_io::{
+    BundleAssembler, BundleReader, ClassSpec, DecodeTimings, FieldSpec, FunctionSpec, ModuleSpec,
+    ScriptBundle,
+};
+
+// the number of classes merged into the cache by the merge benchmark
+const MERGED_CLASSES: usize = 100;
+
+fn cache_bytes() -> Option<Vec<u8>> {
+    let path = PathBuf::from(std::env::var_os("REDSCRIPT_CACHE")?);
+    Some(std::fs::read(path).expect("failed to read the cache file"))
+}
+
+// a mod with the given number of classes, each with a field and a method
+fn module<'i>(classes: usize) -> ModuleSpec<'i> {
+    (0..classes).fold(ModuleSpec::new("Bench"), |module, i| {
+        module.with_class(
+            ClassSpec::new(format!("Class{i}"))
+                .with_field(FieldSpec::new("value", "Int32"))
+                .with_method(
+                    FunctionSpec::new("Get")
+                        .with_return_type("Int32")
+                        .with_body(|_| redscript_io::instr![Return, I32Const(1)]),
+                ),
+        )
+    })
+}
+
+fn decode_table(
+    c: &mut Criterion,
+    reader: &BundleReader<'_>,
+    name: &str,
+    table: fn(&DecodeTimings) -> Duration,
+) {
+    c.bench_function(name, |b| {
+        b.iter_custom(|iters| {
+            (0..iters)
+                .map(|_| table(&reader.decode_all_timed().unwrap()))
+                .sum()
+        });
+    });
+}
+
+fn bundle(c: &mut Criterion) {
+    let Some(bytes) = cache_bytes() else {
+        return;
+    };
+
+    // each table is timed separately through the decoding hook
+    let reader = BundleReader::new(&bytes).expect("failed to read the cache");
+    decode_table(c, &reader, "decode_names", |t| {
+        t.cnames + t.tweakdb_ids + t.resources
+    });
+    decode_table(c, &reader, "decode_strings", |t| t.strings);
+    decode_table(c, &reader, "decode_definitions", |t| t.definitions);
+
+    c.bench_function("parse", |b| {
+        b.iter(|| ScriptBundle::from_bytes(black_box(&bytes)).unwrap());
+    });
+
+    c.bench_function("write", |b| {
+        b.iter_batched(
+            || ScriptBundle::from_bytes(&bytes).unwrap(),
+            |bundle| bundle.into_writeable().to_bytes().unwrap(),
+            BatchSize::LargeInput,
+        );
+    });
+
+    c.bench_function("merge", |b| {
+        b.iter_batched(
+            || {
+                (
+                    ScriptBundle::from_bytes(&bytes).unwrap(),
+                    module(MERGED_CLASSES),
+                )
+            },
+            |(bundle, module)| {
+                BundleAssembler::with_bundle(bundle)
+                    .with_primitive("Int32")
+                    .with_module(module)
+                    .assemble()
+                    .unwrap()
+            },
+            BatchSize::LargeInput,
+        );
+    });
+
+    let bundle = ScriptBundle::from_bytes(&bytes).unwrap();
+    let names = bundle
+        .functions()
+        .map(|(_, fun)| fun.name())
+        .collect::<Vec<_>>();
+    c.bench_function("resolve_each", |b| {
+        b.iter(|| {
+            black_box(&names)
+                .iter()
+                .map(|&name| bundle.get_item(name))
+                .collect::<Option<Vec<_>>>()
+        });
+    });
+    c.bench_function("resolve_all", |b| {
+        b.iter(|| bundle.resolve_all(black_box(&names)));
+    });
+}
+
+criterion_group!(benches, bundle);
+criterion_main!(benches);
@@ -0,0 +1,171 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+use crate::index::FunctionIndex;
+use crate::instr::InstrIndex;
+
+// user metadata attached to a definition or an instruction, it's kept in memory only
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Annotation {
+    pub comments: Vec<String>,
+    pub tags: BTreeSet<String>,
+    // where the item came from, e.g. the mod that inserted it
+    pub provenance: Option<String>,
+}
+
+impl Annotation {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[inline]
+    pub fn with_comment(mut self, comment: impl Into<String>) -> Self {
+        self.comments.push(comment.into());
+        self
+    }
+
+    #[inline]
+    pub fn with_tag(mut self, tag: impl Into<String>) -> Self {
+        self.tags.insert(tag.into());
+        self
+    }
+
+    #[inline]
+    pub fn with_provenance(mut self, provenance: impl Into<String>) -> Self {
+        self.provenance = Some(provenance.into());
+        self
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.comments.is_empty() && self.tags.is_empty() && self.provenance.is_none()
+    }
+}
+
+// formats the annotation as a single line, e.g. `[tag] (from mod) comment; other comment`
+impl fmt::Display for Annotation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut sep = "";
+        for tag in &self.tags {
+            write!(f, "{sep}[{tag}]")?;
+            sep = " ";
+        }
+        if let Some(provenance) = &self.provenance {
+            write!(f, "{sep}(from {provenance})")?;
+            sep = " ";
+        }
+        for comment in &self.comments {
+            write!(f, "{sep}{comment}")?;
+            sep = "; ";
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum AnnotationTarget {
+    Definition(u32),
+    Instr(FunctionIndex, InstrIndex),
+}
+
+// instructions are identified by their position in the instruction list, so the annotations
+// survive rewrites of operands, rewrites that insert or remove instructions have to move them
+// with `remap_instrs`
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Annotations {
+    entries: BTreeMap<AnnotationTarget, Annotation>,
+}
+
+impl Annotations {
+    #[inline]
+    pub fn get(&self, target: AnnotationTarget) -> Option<&Annotation> {
+        self.entries.get(&target)
+    }
+
+    // returns the annotation of the target, creating an empty one if there's none
+    #[inline]
+    pub fn annotate(&mut self, target: AnnotationTarget) -> &mut Annotation {
+        self.entries.entry(target).or_default()
+    }
+
+    #[inline]
+    pub fn remove(&mut self, target: AnnotationTarget) -> Option<Annotation> {
+        self.entries.remove(&target)
+    }
+
+    #[inline]
+    pub fn iter(&self) -> impl Iterator<Item = (AnnotationTarget, &Annotation)> {
+        self.entries
+            .iter()
+            .map(|(target, annotation)| (*target, annotation))
+    }
+
+    // annotations of the instructions of a function, in the order of the instructions
+    pub fn instrs(
+        &self,
+        function: FunctionIndex,
+    ) -> impl Iterator<Item = (InstrIndex, &Annotation)> {
+        let range = AnnotationTarget::Instr(function, InstrIndex::new(0))
+            ..=AnnotationTarget::Instr(function, InstrIndex::new(u32::MAX));
+        self.entries
+            .range(range)
+            .filter_map(|(target, annotation)| match target {
+                AnnotationTarget::Instr(_, index) => Some((*index, annotation)),
+                AnnotationTarget::Definition(_) => None,
+            })
+    }
+
+    // moves the annotations of the instructions of a function, the ones mapped to None are dropped
+    pub fn remap_instrs(
+        &mut self,
+        function: FunctionIndex,
+        mut f: impl FnMut(InstrIndex) -> Option<InstrIndex>,
+    ) {
+        let moved = self
+            .instrs(function)
+            .map(|(index, _)| index)
+            .collect::<Vec<_>>();
+        let moved = moved
+            .into_iter()
+            .filter_map(|index| {
+                let annotation = self.remove(AnnotationTarget::Instr(function, index))?;
+                Some((f(index)?, annotation))
+            })
+            .collect::<Vec<_>>();
+        for (index, annotation) in moved {
+            self.entries
+                .insert(AnnotationTarget::Instr(function, index), annotation);
+        }
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    // follows definitions moved to new positions, `positions` holds the new index of every
+    // definition by its old one
+    pub(crate) fn remap_definitions(&mut self, positions: &[u32]) {
+        let remap = |index: u32| positions.get(index as usize).copied().unwrap_or(index);
+        self.entries = std::mem::take(&mut self.entries)
+            .into_iter()
+            .map(|(target, annotation)| {
+                let target = match target {
+                    AnnotationTarget::Definition(index) => {
+                        AnnotationTarget::Definition(remap(index))
+                    }
+                    AnnotationTarget::Instr(function, instr) => {
+                        AnnotationTarget::Instr(function.map(remap), instr)
+                    }
+                };
+                (target, annotation)
+            })
+            .collect();
+    }
+}
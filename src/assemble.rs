@@ -0,0 +1,586 @@
+use std::fmt;
+
+use crate::bundle::{PoolError, ScriptBundle};
+use crate::definition::{
+    Class, ClassFlags, CodeError, Field, FieldFlags, Function, FunctionFlags, Local, LocalFlags,
+    Parameter, ParameterFlags, Type, TypeKind, Visibility,
+};
+use crate::index::{
+    ClassIndex, FieldIndex, FunctionIndex, LocalIndex, NzPoolIndex, ParameterIndex, TypeIndex,
+};
+use crate::instr::Instr;
+use crate::lookup::SymbolLookup;
+use crate::monitor::{Monitor, Phase};
+use crate::order::SortError;
+use crate::symbols;
+use crate::syntax::TypeParseError;
+use crate::validate::Issue;
+use crate::PoolItemIndex;
+
+type BodyFn<'i> = Box<dyn FnOnce(&BodyScope<'_, 'i>) -> Vec<Instr> + 'i>;
+
+// builds a complete bundle out of a description of modules, classes and functions, the names of
+// classes and free functions are qualified with their module, e.g. `MyMod.Foo`, and types are
+// written in redscript syntax
+pub struct BundleAssembler<'i> {
+    bundle: ScriptBundle<'i>,
+    primitives: Vec<String>,
+    modules: Vec<ModuleSpec<'i>>,
+}
+
+impl<'i> BundleAssembler<'i> {
+    #[inline]
+    pub fn new() -> Self {
+        Self::with_bundle(ScriptBundle::default())
+    }
+
+    // adds the definitions on top of an existing bundle, its classes can be used as bases
+    // and types and its definitions can be referenced from bodies
+    #[inline]
+    pub fn with_bundle(bundle: ScriptBundle<'i>) -> Self {
+        Self {
+            bundle,
+            primitives: vec![],
+            modules: vec![],
+        }
+    }
+
+    // declares a primitive type, e.g. `Int32`, it's only defined if it's not present already
+    #[inline]
+    pub fn with_primitive(mut self, name: impl Into<String>) -> Self {
+        self.primitives.push(name.into());
+        self
+    }
+
+    #[inline]
+    pub fn with_module(mut self, module: ModuleSpec<'i>) -> Self {
+        self.modules.push(module);
+        self
+    }
+
+    #[inline]
+    pub fn assemble(self) -> Result<ScriptBundle<'i>, AssembleError> {
+        self.assemble_monitored(Monitor::new())
+    }
+
+    // classes are defined first so that they can refer to each other regardless of the order
+    // they're declared in, bodies are built last when every definition is in place, the new
+    // definitions are sorted topologically after the base bundle and have to pass validation,
+    // progress is reported for every class and function added and then for the validation
+    pub fn assemble_monitored(
+        self,
+        mut monitor: Monitor<'_>,
+    ) -> Result<ScriptBundle<'i>, AssembleError> {
+        let Self {
+            mut bundle,
+            primitives,
+            modules,
+        } = self;
+        // the definitions of the base bundle stay where they are
+        let start = bundle.definition_count();
+
+        for name in &primitives {
+            if bundle.parse_type(name).is_err() {
+                let name = bundle
+                    .cnames_mut()
+                    .get_or_add(name.clone())
+                    .map_err(AssembleError::Pool)?;
+                bundle.define(Type::new(name, TypeKind::Primitive));
+            }
+        }
+
+        let mut class_specs = vec![];
+        let mut function_specs = vec![];
+        for module in modules {
+            for class in module.classes {
+                class_specs.push((qualify(&module.name, &class.name), class));
+            }
+            for fun in module.functions {
+                function_specs.push((qualify(&module.name, &fun.name), fun));
+            }
+        }
+
+        let total = class_specs
+            .iter()
+            .map(|(_, spec)| 1 + spec.methods.len())
+            .sum::<usize>()
+            + function_specs.len();
+        let total = total as u32;
+        let mut done = 0;
+        let mut step = |monitor: &mut Monitor<'_>| {
+            done += 1;
+            monitor
+                .step(Phase::Merge, done, total)
+                .map_err(|_| AssembleError::Cancelled)
+        };
+
+        let mut classes = Vec::with_capacity(class_specs.len());
+        for (name, spec) in &class_specs {
+            let cname = bundle
+                .cnames_mut()
+                .get_or_add(name.clone())
+                .map_err(AssembleError::Pool)?;
+            classes.push(bundle.define(Class::new(cname, spec.visibility, spec.flags)));
+            bundle.define(Type::new(cname, TypeKind::Class));
+        }
+
+        // classes share their names with their types, so they're looked up by kind
+        let bases = class_specs
+            .iter()
+            .map(|(name, spec)| {
+                let Some(base) = &spec.base else {
+                    return Ok(None);
+                };
+                let index = bundle
+                    .classes()
+                    .find(|(_, class)| bundle.get_item(class.name()) == Some(base.as_str()))
+                    .map(|(index, _)| index)
+                    .ok_or_else(|| AssembleError::UnknownBase {
+                        class: name.clone(),
+                        base: base.clone(),
+                    })?;
+                Ok(Some(index))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut bodies = vec![];
+        for ((name, spec), (index, base)) in
+            class_specs.into_iter().zip(classes.into_iter().zip(bases))
+        {
+            let mut fields = Vec::with_capacity(spec.fields.len());
+            for field in &spec.fields {
+                fields.push(define_field(&mut bundle, index, &name, field)?);
+            }
+            let mut methods = Vec::with_capacity(spec.methods.len());
+            for method in spec.methods {
+                let qualified = format!("{name}.{}", method.name);
+                let (fun, body) = define_function(&mut bundle, Some(index), &qualified, method)?;
+                methods.push(fun);
+                bodies.extend(body);
+                step(&mut monitor)?;
+            }
+            bundle.modify(index, |class| {
+                *class = class
+                    .clone()
+                    .with_base(base)
+                    .with_fields(fields)
+                    .with_methods(methods);
+            });
+            step(&mut monitor)?;
+        }
+        for (name, spec) in function_specs {
+            let (_, body) = define_function(&mut bundle, None, &name, spec)?;
+            bodies.extend(body);
+            step(&mut monitor)?;
+        }
+
+        let code = {
+            let lookup = bundle.symbol_lookup();
+            bodies
+                .into_iter()
+                .map(|(function, body)| {
+                    let fun = bundle.get_item(function);
+                    let scope = BodyScope {
+                        bundle: &bundle,
+                        lookup: &lookup,
+                        function,
+                        parameters: fun.map_or(&[], Function::parameters),
+                        locals: fun.map_or(&[], Function::locals),
+                    };
+                    (function, body(&scope))
+                })
+                .collect::<Vec<_>>()
+        };
+        for (function, code) in code {
+            let Some(fun) = bundle.get_item(function) else {
+                continue;
+            };
+            let fun = fun
+                .clone()
+                .try_with_code(code)
+                .map_err(|error| AssembleError::Code {
+                    function: symbols::qualified_name(&bundle, function.into()).unwrap_or_default(),
+                    error,
+                })?;
+            bundle.modify(function, |slot| *slot = fun);
+        }
+
+        bundle
+            .sort_definitions_from(start)
+            .map_err(AssembleError::Sort)?;
+        let issues = bundle
+            .validate_from(start, &mut monitor)
+            .map_err(|_| AssembleError::Cancelled)?
+            .into_iter()
+            .filter(|issue| !issue.is_warning())
+            .collect::<Vec<_>>();
+        if !issues.is_empty() {
+            return Err(AssembleError::Invalid(issues));
+        }
+        Ok(bundle)
+    }
+}
+
+impl Default for BundleAssembler<'_> {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn define_field(
+    bundle: &mut ScriptBundle<'_>,
+    class: ClassIndex,
+    class_name: &str,
+    spec: &FieldSpec,
+) -> Result<FieldIndex, AssembleError> {
+    let typ = parse_type(bundle, &format!("{class_name}.{}", spec.name), &spec.typ)?;
+    let name = bundle
+        .cnames_mut()
+        .get_or_add(spec.name.clone())
+        .map_err(AssembleError::Pool)?;
+    Ok(bundle.define(Field::new(name, class, spec.visibility, typ, spec.flags)))
+}
+
+// methods are named by their unqualified names, free functions by their qualified ones
+fn define_function<'i>(
+    bundle: &mut ScriptBundle<'i>,
+    class: Option<ClassIndex>,
+    qualified: &str,
+    spec: FunctionSpec<'i>,
+) -> Result<(FunctionIndex, Option<(FunctionIndex, BodyFn<'i>)>), AssembleError> {
+    let name = if class.is_some() {
+        spec.name.clone()
+    } else {
+        qualified.to_owned()
+    };
+    let name = bundle
+        .cnames_mut()
+        .get_or_add(name)
+        .map_err(AssembleError::Pool)?;
+    let index = bundle.define(Function::new(name, spec.visibility, spec.flags));
+    let return_type = spec
+        .return_type
+        .map(|typ| parse_type(bundle, qualified, &typ))
+        .transpose()?;
+
+    let mut parameters: Vec<ParameterIndex> = Vec::with_capacity(spec.parameters.len());
+    for (name, typ, flags) in &spec.parameters {
+        let typ = parse_type(bundle, qualified, typ)?;
+        let name = bundle
+            .cnames_mut()
+            .get_or_add(name.clone())
+            .map_err(AssembleError::Pool)?;
+        parameters.push(bundle.define(Parameter::new(name, index, typ, *flags)));
+    }
+    let mut locals: Vec<LocalIndex> = Vec::with_capacity(spec.locals.len());
+    for (name, typ) in &spec.locals {
+        let typ = parse_type(bundle, qualified, typ)?;
+        let name = bundle
+            .cnames_mut()
+            .get_or_add(name.clone())
+            .map_err(AssembleError::Pool)?;
+        locals.push(bundle.define(Local::new(name, index, typ, LocalFlags::new())));
+    }
+
+    bundle.modify(index, |fun| {
+        *fun = fun
+            .clone()
+            .with_class(class)
+            .with_return_type(return_type)
+            .with_parameters(parameters)
+            .with_locals(locals);
+    });
+    Ok((index, spec.body.map(|body| (index, body))))
+}
+
+fn parse_type(
+    bundle: &mut ScriptBundle<'_>,
+    definition: &str,
+    typ: &str,
+) -> Result<TypeIndex, AssembleError> {
+    bundle.parse_type(typ).map_err(|error| AssembleError::Type {
+        definition: definition.to_owned(),
+        error,
+    })
+}
+
+#[inline]
+fn qualify(module: &str, name: &str) -> String {
+    if module.is_empty() {
+        name.to_owned()
+    } else {
+        format!("{module}.{name}")
+    }
+}
+
+// what a body builder can see, every definition has been defined when bodies are built
+pub struct BodyScope<'a, 'i> {
+    bundle: &'a ScriptBundle<'i>,
+    lookup: &'a SymbolLookup<'a, 'i>,
+    function: FunctionIndex,
+    parameters: &'a [ParameterIndex],
+    locals: &'a [LocalIndex],
+}
+
+impl<'a, 'i> BodyScope<'a, 'i> {
+    #[inline]
+    pub fn bundle(&self) -> &'a ScriptBundle<'i> {
+        self.bundle
+    }
+
+    #[inline]
+    pub fn function(&self) -> FunctionIndex {
+        self.function
+    }
+
+    // parameters and locals in the order they were declared
+    #[inline]
+    pub fn parameters(&self) -> &'a [ParameterIndex] {
+        self.parameters
+    }
+
+    #[inline]
+    pub fn locals(&self) -> &'a [LocalIndex] {
+        self.locals
+    }
+
+    // finds a definition by its fully-qualified name, see SymbolLookup::find
+    #[inline]
+    pub fn find<A>(&self, name: &str) -> Option<NzPoolIndex<A>>
+    where
+        NzPoolIndex<A>: PoolItemIndex<'i>,
+    {
+        self.lookup.find(name)
+    }
+}
+
+pub struct ModuleSpec<'i> {
+    name: String,
+    classes: Vec<ClassSpec<'i>>,
+    functions: Vec<FunctionSpec<'i>>,
+}
+
+impl<'i> ModuleSpec<'i> {
+    // an empty name leaves the names of the definitions unqualified
+    #[inline]
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            classes: vec![],
+            functions: vec![],
+        }
+    }
+
+    #[inline]
+    pub fn with_class(mut self, class: ClassSpec<'i>) -> Self {
+        self.classes.push(class);
+        self
+    }
+
+    #[inline]
+    pub fn with_function(mut self, function: FunctionSpec<'i>) -> Self {
+        self.functions.push(function);
+        self
+    }
+}
+
+pub struct ClassSpec<'i> {
+    name: String,
+    visibility: Visibility,
+    flags: ClassFlags,
+    // a fully-qualified name of a class
+    base: Option<String>,
+    fields: Vec<FieldSpec>,
+    methods: Vec<FunctionSpec<'i>>,
+}
+
+impl<'i> ClassSpec<'i> {
+    #[inline]
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            visibility: Visibility::Public,
+            flags: ClassFlags::new(),
+            base: None,
+            fields: vec![],
+            methods: vec![],
+        }
+    }
+
+    #[inline]
+    pub fn with_visibility(mut self, visibility: Visibility) -> Self {
+        self.visibility = visibility;
+        self
+    }
+
+    #[inline]
+    pub fn with_flags(mut self, flags: ClassFlags) -> Self {
+        self.flags = flags;
+        self
+    }
+
+    #[inline]
+    pub fn with_base(mut self, base: impl Into<String>) -> Self {
+        self.base = Some(base.into());
+        self
+    }
+
+    #[inline]
+    pub fn with_field(mut self, field: FieldSpec) -> Self {
+        self.fields.push(field);
+        self
+    }
+
+    #[inline]
+    pub fn with_method(mut self, method: FunctionSpec<'i>) -> Self {
+        self.methods.push(method);
+        self
+    }
+}
+
+pub struct FieldSpec {
+    name: String,
+    typ: String,
+    visibility: Visibility,
+    flags: FieldFlags,
+}
+
+impl FieldSpec {
+    #[inline]
+    pub fn new(name: impl Into<String>, typ: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            typ: typ.into(),
+            visibility: Visibility::Public,
+            flags: FieldFlags::new(),
+        }
+    }
+
+    #[inline]
+    pub fn with_visibility(mut self, visibility: Visibility) -> Self {
+        self.visibility = visibility;
+        self
+    }
+
+    #[inline]
+    pub fn with_flags(mut self, flags: FieldFlags) -> Self {
+        self.flags = flags;
+        self
+    }
+}
+
+pub struct FunctionSpec<'i> {
+    name: String,
+    visibility: Visibility,
+    flags: FunctionFlags,
+    return_type: Option<String>,
+    parameters: Vec<(String, String, ParameterFlags)>,
+    locals: Vec<(String, String)>,
+    body: Option<BodyFn<'i>>,
+}
+
+impl<'i> FunctionSpec<'i> {
+    #[inline]
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            visibility: Visibility::Public,
+            flags: FunctionFlags::new(),
+            return_type: None,
+            parameters: vec![],
+            locals: vec![],
+            body: None,
+        }
+    }
+
+    #[inline]
+    pub fn with_visibility(mut self, visibility: Visibility) -> Self {
+        self.visibility = visibility;
+        self
+    }
+
+    #[inline]
+    pub fn with_flags(mut self, flags: FunctionFlags) -> Self {
+        self.flags = flags;
+        self
+    }
+
+    #[inline]
+    pub fn with_return_type(mut self, typ: impl Into<String>) -> Self {
+        self.return_type = Some(typ.into());
+        self
+    }
+
+    #[inline]
+    pub fn with_parameter(
+        mut self,
+        name: impl Into<String>,
+        typ: impl Into<String>,
+        flags: ParameterFlags,
+    ) -> Self {
+        self.parameters.push((name.into(), typ.into(), flags));
+        self
+    }
+
+    #[inline]
+    pub fn with_local(mut self, name: impl Into<String>, typ: impl Into<String>) -> Self {
+        self.locals.push((name.into(), typ.into()));
+        self
+    }
+
+    // the body is built once every definition of the bundle is in place
+    #[inline]
+    pub fn with_body(mut self, body: impl FnOnce(&BodyScope<'_, 'i>) -> Vec<Instr> + 'i) -> Self {
+        self.body = Some(Box::new(body));
+        self
+    }
+}
+
+#[derive(Debug)]
+pub enum AssembleError {
+    Pool(PoolError),
+    Type {
+        definition: String,
+        error: TypeParseError,
+    },
+    UnknownBase {
+        class: String,
+        base: String,
+    },
+    Sort(SortError),
+    Code {
+        function: String,
+        error: CodeError,
+    },
+    Invalid(Vec<Issue>),
+    Cancelled,
+}
+
+impl fmt::Display for AssembleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AssembleError::Pool(err) => write!(f, "{err}"),
+            AssembleError::Type { definition, error } => {
+                write!(f, "invalid type in {definition}: {error}")
+            }
+            AssembleError::UnknownBase { class, base } => {
+                write!(f, "base class {base} of {class} not found")
+            }
+            AssembleError::Sort(err) => write!(f, "{err}"),
+            AssembleError::Code { function, error } => {
+                write!(f, "invalid code in {function}: {error}")
+            }
+            AssembleError::Invalid(issues) => {
+                write!(f, "assembled bundle is invalid")?;
+                for issue in issues {
+                    write!(f, "\n  {issue}")?;
+                }
+                Ok(())
+            }
+            AssembleError::Cancelled => write!(f, "assembly cancelled"),
+        }
+    }
+}
+
+impl std::error::Error for AssembleError {}
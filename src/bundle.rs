@@ -1,31 +1,56 @@
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::hash::BuildHasher;
 use std::marker::PhantomData;
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
 use std::{fmt, iter, mem, ops};
 
 use bitfield_struct::bitfield;
 use byte::ctx::{Delimiter, Endianess, LittleEndian};
 use byte::{BytesExt, Measure, ToBytesExt, TryRead, TryWrite};
+use hashbrown::HashTable;
+#[cfg(feature = "indexmap")]
 use indexmap::map::RawEntryApiV1;
-use indexmap::{IndexMap, IndexSet};
+#[cfg(feature = "indexmap")]
+use indexmap::IndexMap;
 
+use crate::annotate::Annotations;
+#[cfg(any(feature = "lz4", feature = "zstd"))]
+use crate::compression;
 use crate::definition::{
-    Class, Definition, DefinitionHeader, DefinitionIndex, Enum, EnumMember, Field, Function, Local,
-    Parameter, SourceFile, Type,
+    Class, Definition, DefinitionHeader, DefinitionIndex, DefinitionKind, Enum, EnumMember, Field,
+    Function, Local, Parameter, SourceFile, Type,
 };
 use crate::index::{
-    self, CNameIndex, ClassIndex, EnumIndex, EnumValueIndex, FieldIndex, FunctionIndex, LocalIndex,
-    NzPoolIndex, ParameterIndex, PoolIndex, ResourceIndex, SourceFileIndex, StringIndex,
-    TweakDbIndex, TypeIndex,
+    self, CNameIndex, ClassIndex, EnumIndex, EnumValueIndex, FieldIndex, FunctionIndex,
+    IndexOverflow, IndexRange, LocalIndex, NzPoolIndex, ParameterIndex, PoolIndex, RawIndex,
+    ResourceIndex, SourceFileIndex, StringIndex, TweakDbIndex, TypeIndex,
 };
+use crate::instr::{ByteOffset, Instr};
+use crate::journal::{BundleObserver, Edit, EditMismatch, Journal, ObserverId, Observers};
+use crate::monitor::{Monitor, Phase};
+use crate::order::SortError;
+use crate::provenance::{ChangeKind, Provenance};
+use crate::util::{Owned, RandomState, Relocate};
 use crate::{util, Str, ENDIANESS};
 
 #[derive(Debug)]
 pub struct BundleReader<'i> {
     header: Header,
     contents: &'i [u8],
+    options: ReadOptions,
+    hash_mismatches: Vec<HashMismatch>,
 }
 
 impl<'i> BundleReader<'i> {
+    #[inline]
     pub fn new(bytes: &'i [u8]) -> byte::Result<Self> {
+        Self::with_options(bytes, ReadOptions::default())
+    }
+
+    pub fn with_options(bytes: &'i [u8], options: ReadOptions) -> byte::Result<Self> {
         let header: Header = bytes.read_at(0, ENDIANESS)?;
         if header.magic != Header::MAGIC {
             return Err(byte::Error::BadInput {
@@ -37,12 +62,66 @@ impl<'i> BundleReader<'i> {
                 err: "unsupported version",
             });
         };
+        if header.definitions.count > options.limits.max_definitions {
+            return Err(byte::Error::BadInput {
+                err: "definition count exceeds the limit",
+            });
+        }
+        let hash_mismatches = match options.hash_policy {
+            HashPolicy::Ignore => vec![],
+            policy => {
+                let mismatches = header.verify_hashes(bytes);
+                #[cfg(feature = "tracing")]
+                for mismatch in &mismatches {
+                    tracing::warn!(
+                        table = mismatch.table,
+                        expected = mismatch.expected,
+                        actual = ?mismatch.actual,
+                        "hash mismatch"
+                    );
+                }
+                if policy == HashPolicy::Enforce && !mismatches.is_empty() {
+                    return Err(byte::Error::BadInput {
+                        err: "hash mismatch",
+                    });
+                }
+                mismatches
+            }
+        };
+        #[cfg(feature = "tracing")]
+        tracing::debug!(
+            size = bytes.len(),
+            cnames = header.cnames.count,
+            tweakdb_ids = header.tweakdb_ids.count,
+            resources = header.resources.count,
+            strings = header.strings.count,
+            definitions = header.definitions.count,
+            "read bundle header"
+        );
         Ok(BundleReader {
             header,
             contents: bytes,
+            options,
+            hash_mismatches,
         })
     }
 
+    #[inline]
+    pub(crate) fn contents(&self) -> &'i [u8] {
+        self.contents
+    }
+
+    #[inline]
+    pub fn options(&self) -> ReadOptions {
+        self.options
+    }
+
+    // hashes that did not match the contents, only checked with HashPolicy::Warn
+    #[inline]
+    pub fn hash_mismatches(&self) -> &[HashMismatch] {
+        &self.hash_mismatches
+    }
+
     #[inline]
     pub fn cnames(&self) -> ItemReader<'_, 'i, &'i str> {
         ItemReader::new(self, &self.header.cnames)
@@ -67,9 +146,218 @@ impl<'i> BundleReader<'i> {
     pub fn definitions(&self) -> ItemReader<'_, 'i, Definition<'i>> {
         ItemReader::new(self, &self.header.definitions)
     }
+
+    // finds the index of the definition whose data contains the given file offset,
+    // relies on definitions being laid out in index order
+    pub fn definition_at_offset(&self, pos: u32) -> byte::Result<Option<u32>> {
+        let definitions = self.definitions();
+        let (mut lo, mut hi) = (1, definitions.count);
+        while lo < hi {
+            let mid = lo + (hi - lo) / 2;
+            if definitions.header(mid)?.offset() <= pos {
+                lo = mid + 1;
+            } else {
+                hi = mid;
+            }
+        }
+        if lo == 1 {
+            return Ok(None);
+        }
+        let index = lo - 1;
+        let header = definitions.header(index)?;
+        let end = header
+            .offset()
+            .checked_add(header.size())
+            .ok_or(byte::Error::BadInput {
+                err: "definition end overflows",
+            })?;
+        Ok((pos < end).then_some(index))
+    }
+
+    // decodes every table and reports how long each one took, for benchmarking
+    pub fn decode_all_timed(&self) -> byte::Result<DecodeTimings> {
+        fn timed<A>(f: impl FnOnce() -> byte::Result<A>) -> byte::Result<Duration> {
+            let start = Instant::now();
+            f()?;
+            Ok(start.elapsed())
+        }
+
+        let strings = |items: ItemReader<'_, 'i, &'i str>| {
+            timed(|| items.into_iter().try_for_each(|res| res.map(drop)))
+        };
+        Ok(DecodeTimings {
+            cnames: strings(self.cnames())?,
+            tweakdb_ids: strings(self.tweakdb_ids())?,
+            resources: strings(self.resources())?,
+            strings: strings(self.strings())?,
+            definitions: timed(|| {
+                self.definitions()
+                    .into_iter()
+                    .skip(1)
+                    .try_for_each(|res| res.map(drop))
+            })?,
+        })
+    }
+
+    // byte ranges of every region of the file as it was read, the same report that is produced
+    // when writing, useful for annotating the file in a hex editor
+    pub fn read_report(&self) -> byte::Result<WriteReport> {
+        let table = |header: &TableHeader, item_size: u32| {
+            let size = header
+                .count
+                .checked_mul(item_size)
+                .ok_or(byte::Error::BadInput {
+                    err: "table size overflows",
+                })?;
+            Ok(Section::new(header.offset as _, size as _))
+        };
+        let header = &self.header;
+        let definitions = self.definitions();
+        // the count comes from the input, so it's only trusted as far as the headers fit in it
+        let capacity = (definitions.count as usize)
+            .min(self.contents.len() / Definition::HEADER_SIZE as usize + 1);
+        let mut sections = Vec::with_capacity(capacity);
+        sections.push(Section::default());
+        for index in 1..definitions.count {
+            let def = definitions.header(index)?;
+            sections.push(Section::new(def.offset() as _, def.size() as _));
+        }
+        Ok(WriteReport {
+            header: Section::new(0, Header::SIZE as _),
+            string_data: Section::new(
+                header.string_data.offset as _,
+                header.string_data.count as _,
+            ),
+            cnames: table(&header.cnames, 4)?,
+            tweakdb_ids: table(&header.tweakdb_ids, 4)?,
+            resources: table(&header.resources, 4)?,
+            definition_headers: table(&header.definitions, Definition::HEADER_SIZE)?,
+            strings: table(&header.strings, 4)?,
+            definitions: sections,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct DecodeTimings {
+    pub cnames: Duration,
+    pub tweakdb_ids: Duration,
+    pub resources: Duration,
+    pub strings: Duration,
+    pub definitions: Duration,
+}
+
+impl DecodeTimings {
+    #[inline]
+    pub fn total(&self) -> Duration {
+        self.cnames + self.tweakdb_ids + self.resources + self.strings + self.definitions
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReadOptions {
+    // fail on non-zero reserved bits and padding bytes
+    pub strict: bool,
+    // keep reserved bits and padding as they were read instead of clearing them, off by default
+    // so that they're normalized, meant for inspecting files
+    pub preserve_unknown: bool,
+    // retain the original file layout so that an unmodified bundle is written back byte-for-byte
+    pub round_trip: bool,
+    // keep repeated entries of the string pools, otherwise only the first one is kept and
+    // the indices past a repeated entry shift, implied by `round_trip`
+    pub preserve_duplicate_strings: bool,
+    // what to do about header and table hashes that don't match the contents
+    pub hash_policy: HashPolicy,
+    pub limits: ParseLimits,
+    // validate headers and positions of every item before decoding it, see ItemReader::get_checked
+    pub checked: bool,
 }
 
-#[derive(Debug, TryRead, TryWrite, Measure)]
+impl ReadOptions {
+    // every check that does not reject valid game files, used by ScriptBundle::from_bytes_untrusted
+    pub fn untrusted() -> Self {
+        Self {
+            hash_policy: HashPolicy::Enforce,
+            limits: ParseLimits::UNTRUSTED,
+            checked: true,
+            ..Default::default()
+        }
+    }
+}
+
+impl Default for ReadOptions {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            strict: false,
+            preserve_unknown: false,
+            round_trip: false,
+            preserve_duplicate_strings: false,
+            hash_policy: HashPolicy::Ignore,
+            limits: ParseLimits::default(),
+            checked: false,
+        }
+    }
+}
+
+// upper bounds for untrusted input, reading fails when any of them is exceeded
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParseLimits {
+    pub max_definitions: u32,
+    // in bytes of encoded instructions
+    pub max_code_size: u32,
+    // in bytes, applies to every string of the name and string pools
+    pub max_string_length: u32,
+}
+
+impl ParseLimits {
+    pub const UNLIMITED: Self = Self {
+        max_definitions: u32::MAX,
+        max_code_size: u32::MAX,
+        max_string_length: u32::MAX,
+    };
+    // comfortably above what the game ships with
+    pub const UNTRUSTED: Self = Self {
+        max_definitions: 1 << 22,
+        max_code_size: 1 << 20,
+        max_string_length: 1 << 16,
+    };
+}
+
+impl Default for ParseLimits {
+    #[inline]
+    fn default() -> Self {
+        Self::UNLIMITED
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum HashPolicy {
+    // hashes are not checked, some community tools write bogus ones
+    #[default]
+    Ignore,
+    // mismatches are collected and exposed through BundleReader::hash_mismatches
+    Warn,
+    // mismatches fail the read
+    Enforce,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HashMismatch {
+    pub table: &'static str,
+    pub expected: u32,
+    // none when the table lies outside of the file and can't be hashed
+    pub actual: Option<u32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BundleKind {
+    Supported,
+    Older(u32),
+    Newer(u32),
+}
+
+#[derive(Debug, Clone, Copy, TryRead, TryWrite, Measure)]
 pub struct Header {
     magic: [u8; 4],
     version: u32,
@@ -87,9 +375,53 @@ pub struct Header {
 }
 
 impl Header {
-    const MAGIC: [u8; 4] = *b"REDS";
-    const SIZE: u32 = 104;
-    const SUPPORTED_VERSION: u32 = 14;
+    pub(crate) const MAGIC: [u8; 4] = *b"REDS";
+    pub(crate) const SIZE: u32 = 104;
+    pub(crate) const SUPPORTED_VERSION: u32 = 14;
+    const HASH_PLACEHOLDER: u32 = 0xDEAD_BEEF;
+
+    fn verify_hashes(&self, bytes: &[u8]) -> Vec<HashMismatch> {
+        let mut mismatches = vec![];
+        let mut check = |table, expected, actual| {
+            if Some(expected) != actual {
+                mismatches.push(HashMismatch {
+                    table,
+                    expected,
+                    actual,
+                });
+            }
+        };
+
+        let for_hash = Header {
+            crc: Self::HASH_PLACEHOLDER,
+            ..*self
+        };
+        check(
+            "header",
+            self.crc,
+            for_hash
+                .to_bytes(ENDIANESS)
+                .ok()
+                .map(|header| crc32fast::hash(&header)),
+        );
+
+        let tables = [
+            ("string_data", &self.string_data, 1),
+            ("cnames", &self.cnames, 4),
+            ("tweakdb_ids", &self.tweakdb_ids, 4),
+            ("resources", &self.resources, 4),
+            ("definitions", &self.definitions, Definition::HEADER_SIZE),
+            ("strings", &self.strings, 4),
+        ];
+        for (name, table, item_size) in tables {
+            let contents = table
+                .count
+                .checked_mul(item_size)
+                .and_then(|size| bytes.get(table.offset as usize..)?.get(..size as usize));
+            check(name, table.hash, contents.map(crc32fast::hash));
+        }
+        mismatches
+    }
 }
 
 #[derive(Debug, Clone, Copy, TryRead, TryWrite, Measure)]
@@ -117,29 +449,101 @@ pub struct ScriptBundle<'i> {
     resources: StringPool<'i, index::types::Resource>,
     strings: StringPool<'i, index::types::String>,
     definitions: Vec<Definition<'i>>,
+    // headers of the definitions as they were read, empty for bundles built from scratch
+    headers: Vec<DefinitionHeader>,
+    journal: Option<Journal<'i>>,
+    observers: Observers,
+    layout: Option<Box<Layout<'i>>>,
+    annotations: Annotations,
+    provenance: Option<Provenance>,
 }
 
 impl<'i> ScriptBundle<'i> {
     pub fn from_bytes(bytes: &'i [u8]) -> byte::Result<Self> {
+        #[cfg(any(feature = "lz4", feature = "zstd"))]
+        if let Some(bytes) = compression::decompress_input(bytes)? {
+            return Ok(ScriptBundle::from_bytes(&bytes)?.into_owned());
+        }
         let reader = BundleReader::new(bytes)?;
         Self::from_reader(&reader)
     }
 
+    // reads a bundle from a source that can't be trusted, hashes are enforced, the resource
+    // limits of ReadOptions::untrusted are applied and every item is validated before decoding
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all, err(Debug)))]
+    pub fn from_bytes_untrusted(bytes: &'i [u8]) -> byte::Result<Self> {
+        #[cfg(any(feature = "lz4", feature = "zstd"))]
+        if let Some(bytes) = compression::decompress_input(bytes)? {
+            return Ok(ScriptBundle::from_bytes_untrusted(&bytes)?.into_owned());
+        }
+        let reader = BundleReader::with_options(bytes, ReadOptions::untrusted())?;
+        Self::from_reader(&reader)
+    }
+
+    #[inline]
     pub fn from_reader(reader: &BundleReader<'i>) -> byte::Result<Self> {
+        Self::from_reader_monitored(reader, Monitor::new())
+    }
+
+    pub fn from_reader_monitored(
+        reader: &BundleReader<'i>,
+        mut monitor: Monitor<'_>,
+    ) -> byte::Result<Self> {
         Ok(Self {
-            cnames: reader.cnames().into_iter().collect::<byte::Result<_>>()?,
-            tdb_ids: reader
-                .tweakdb_ids()
-                .into_iter()
-                .collect::<byte::Result<_>>()?,
-            resources: reader
-                .resources()
-                .into_iter()
-                .collect::<byte::Result<_>>()?,
-            strings: reader.strings().into_iter().collect::<byte::Result<_>>()?,
-            definitions: iter::once(Ok(Definition::UNDEFINED))
-                .chain(reader.definitions().into_iter().skip(1))
-                .collect::<byte::Result<_>>()?,
+            cnames: read_table("cnames", reader.cnames(), reader.options)?,
+            tdb_ids: read_table("tweakdb_ids", reader.tweakdb_ids(), reader.options)?,
+            resources: read_table("resources", reader.resources(), reader.options)?,
+            strings: read_table("strings", reader.strings(), reader.options)?,
+            definitions: {
+                #[cfg(feature = "tracing")]
+                let _span = tracing::debug_span!("read_table", table = "definitions").entered();
+                let total = reader.definitions().count;
+                iter::once(Ok(Definition::UNDEFINED))
+                    .chain(reader.definitions().into_iter().skip(1))
+                    .zip(1..)
+                    .map(|(def, done)| {
+                        monitor.step(Phase::Parse, done, total)?;
+                        def
+                    })
+                    .collect::<byte::Result<_>>()?
+            },
+            headers: {
+                let definitions = reader.definitions();
+                let preserve = reader.options.preserve_unknown;
+                (0..definitions.count)
+                    .map(|i| {
+                        let header = definitions.header(i)?;
+                        Ok(if preserve {
+                            header
+                        } else {
+                            header.with_padding([0; 2])
+                        })
+                    })
+                    .collect::<byte::Result<_>>()?
+            },
+            journal: None,
+            observers: Observers::default(),
+            layout: if reader.options.round_trip {
+                Some(Box::new(Layout::from_reader(reader)?))
+            } else {
+                None
+            },
+            annotations: Annotations::default(),
+            provenance: None,
+        })
+    }
+
+    // cheaply classifies a file by its magic and version, returns None if it's not a script cache
+    pub fn sniff(bytes: &[u8]) -> Option<BundleKind> {
+        let magic: [u8; 4] = bytes.read_at(0, ENDIANESS).ok()?;
+        if magic != Header::MAGIC {
+            return None;
+        }
+        let version: u32 = bytes.read_at(4, ENDIANESS).ok()?;
+        Some(match version.cmp(&Header::SUPPORTED_VERSION) {
+            Ordering::Equal => BundleKind::Supported,
+            Ordering::Less => BundleKind::Older(version),
+            Ordering::Greater => BundleKind::Newer(version),
         })
     }
 
@@ -147,36 +551,153 @@ impl<'i> ScriptBundle<'i> {
         let mut string_data = StringData::with_capacity(
             self.cnames.len() + self.tdb_ids.len() + self.resources.len() + self.strings.len(),
         );
-        let it = self
-            .cnames
-            .strings
-            .iter()
-            .chain(&self.tdb_ids.strings)
-            .chain(&self.resources.strings)
-            .chain(&self.strings.strings)
-            .cloned();
-        string_data.extend(it);
+        let layout = self.layout.as_deref();
+        if let Some(layout) = layout {
+            string_data.base = layout.string_data.clone();
+            string_data.length = layout.string_data.len();
+        }
+        let offsets = [
+            string_data.resolve(&self.cnames, layout.map(|l| &l.cnames[..])),
+            string_data.resolve(&self.tdb_ids, layout.map(|l| &l.tdb_ids[..])),
+            string_data.resolve(&self.resources, layout.map(|l| &l.resources[..])),
+            string_data.resolve(&self.strings, layout.map(|l| &l.strings[..])),
+        ];
 
         WriteableBundle {
             bundle: self,
             string_data,
+            offsets,
         }
     }
 
+    // loads the bundle at the given path, applies the edit and atomically replaces the file
+    #[cfg(feature = "mmap")]
+    pub fn edit_file<R>(
+        path: impl AsRef<std::path::Path>,
+        edit: impl FnOnce(&mut ScriptBundle<'_>) -> R,
+    ) -> Result<R, SaveError> {
+        let path = &*util::extended_path(path.as_ref());
+        let (map, _) = vmap::Map::with_options()
+            .open(path)
+            .map_err(SaveError::Mmap)?;
+        let options = ReadOptions {
+            round_trip: true,
+            ..Default::default()
+        };
+        let reader = BundleReader::with_options(&map, options).map_err(SaveError::Encoding)?;
+        let mut bundle = ScriptBundle::from_reader(&reader).map_err(SaveError::Encoding)?;
+        let result = edit(&mut bundle);
+        let bytes = bundle
+            .into_writeable()
+            .to_bytes()
+            .map_err(SaveError::Encoding)?;
+        // make sure the output can be read back before replacing anything
+        ScriptBundle::from_bytes(&bytes).map_err(SaveError::Encoding)?;
+        drop(map);
+
+        write_atomic(path, &bytes).map_err(SaveError::Io)?;
+        Ok(result)
+    }
+
+    // the header the definition was originally read with, definitions added or removed after
+    // reading have a header describing them without a position in the input
+    #[inline]
+    pub fn header_of<A>(&self, index: NzPoolIndex<A>) -> Option<&DefinitionHeader> {
+        self.headers.get(u32::from(index) as usize)
+    }
+
+    #[inline]
+    pub fn preserves_layout(&self) -> bool {
+        self.layout.is_some()
+    }
+
+    // drops the original layout so that the bundle is written from scratch
+    #[inline]
+    pub fn discard_layout(&mut self) {
+        self.layout = None;
+    }
+
+    // the build number from the header of the original file, it's only kept with the layout
+    #[inline]
+    pub(crate) fn original_build(&self) -> Option<u32> {
+        self.layout.as_ref().map(|layout| layout.build)
+    }
+
+    // the raw timestamp from the header of the original file, it's only kept with the layout
+    #[inline]
+    pub(crate) fn original_timestamp(&self) -> Option<u64> {
+        self.layout
+            .as_ref()
+            .map(|layout| layout.timestamp.into_bits())
+    }
+
+    #[inline]
     pub fn into_owned(self) -> ScriptBundle<'static> {
+        self.relocate(&Owned)
+    }
+
+    // releases the memory reserved for growth, e.g. once a bundle is done being edited and is
+    // kept around for lookups, child lists short enough are moved back inline
+    pub fn shrink_to_fit(&mut self) {
+        self.cnames.shrink_to_fit();
+        self.tdb_ids.shrink_to_fit();
+        self.resources.shrink_to_fit();
+        self.strings.shrink_to_fit();
+        for def in &mut self.definitions {
+            def.shrink_to_fit();
+        }
+        self.definitions.shrink_to_fit();
+        self.headers.shrink_to_fit();
+    }
+
+    // copies all strings and code borrowed from the input into the arena, the definitions
+    // themselves are still allocated on the heap, only their borrowed contents move
+    #[cfg(feature = "bumpalo")]
+    #[inline]
+    pub fn into_arena<'b>(self, bump: &'b bumpalo::Bump) -> ScriptBundle<'b> {
+        self.relocate(&bump)
+    }
+
+    fn relocate<'b>(self, to: &impl Relocate<'b>) -> ScriptBundle<'b> {
         ScriptBundle {
-            cnames: self.cnames.into_owned(),
-            tdb_ids: self.tdb_ids.into_owned(),
-            resources: self.resources.into_owned(),
-            strings: self.strings.into_owned(),
+            cnames: self.cnames.relocate(to),
+            tdb_ids: self.tdb_ids.relocate(to),
+            resources: self.resources.relocate(to),
+            strings: self.strings.relocate(to),
             definitions: self
                 .definitions
                 .into_iter()
-                .map(Definition::into_owned)
+                .map(|def| def.relocate(to))
                 .collect(),
+            headers: self.headers,
+            journal: self.journal.map(|journal| journal.relocate(to)),
+            observers: self.observers,
+            layout: self.layout.map(|layout| Box::new(layout.relocate(to))),
+            annotations: self.annotations,
+            provenance: self.provenance,
         }
     }
 
+    #[inline]
+    pub fn cnames(&self) -> &StringPool<'i, index::types::CName> {
+        &self.cnames
+    }
+
+    #[inline]
+    pub fn tdb_ids(&self) -> &StringPool<'i, index::types::TweakDbId> {
+        &self.tdb_ids
+    }
+
+    #[inline]
+    pub fn resources(&self) -> &StringPool<'i, index::types::Resource> {
+        &self.resources
+    }
+
+    #[inline]
+    pub fn strings(&self) -> &StringPool<'i, index::types::String> {
+        &self.strings
+    }
+
     #[inline]
     pub fn cnames_mut(&mut self) -> &mut StringPool<'i, index::types::CName> {
         &mut self.cnames
@@ -205,12 +726,25 @@ impl<'i> ScriptBundle<'i> {
         index.get(self)
     }
 
+    // resolves the indices in order, e.g. every name referenced by a function in one go
+    #[inline]
+    pub fn resolve_many<'a, I>(
+        &'a self,
+        indices: &'a [I],
+    ) -> impl ExactSizeIterator<Item = Option<&'a I::Output>> + 'a
+    where
+        I: PoolItemIndex<'i> + Copy,
+    {
+        indices.iter().map(|&index| index.get(self))
+    }
+
+    // resolves the indices in order, returns None if any of them doesn't resolve
     #[inline]
-    pub fn get_item_mut<I>(&mut self, index: I) -> Option<&mut I::Output>
+    pub fn resolve_all<'a, I>(&'a self, indices: &[I]) -> Option<Vec<&'a I::Output>>
     where
-        I: PoolItemIndexMut<'i>,
+        I: PoolItemIndex<'i> + Copy,
     {
-        index.get_mut(self)
+        I::get_all(indices, self)
     }
 
     #[inline]
@@ -218,16 +752,539 @@ impl<'i> ScriptBundle<'i> {
         self.definitions.iter()
     }
 
+    #[inline]
+    pub fn functions(&self) -> impl Iterator<Item = (FunctionIndex, &Function<'i>)> {
+        self.definitions_of()
+    }
+
+    #[inline]
+    pub fn classes(&self) -> impl Iterator<Item = (ClassIndex, &Class)> {
+        self.definitions_of()
+    }
+
+    #[inline]
+    pub fn enums(&self) -> impl Iterator<Item = (EnumIndex, &Enum)> {
+        self.definitions_of()
+    }
+
+    // every definition of the kind the index refers to, along with its index
+    fn definitions_of<'a, A: 'a>(
+        &'a self,
+    ) -> impl Iterator<
+        Item = (
+            NzPoolIndex<A>,
+            &'a <NzPoolIndex<A> as PoolItemIndex<'i>>::Output,
+        ),
+    >
+    where
+        NzPoolIndex<A>: PoolItemIndex<'i> + Copy,
+    {
+        (1..self.definitions.len()).filter_map(|i| {
+            let index = NzPoolIndex::from_usize(i)?;
+            Some((index, index.get(self)?))
+        })
+    }
+
+    pub(crate) fn enum_by_name(&self, name: CNameIndex) -> Option<&Enum> {
+        self.enums().find(|(_, e)| e.name() == name).map(|(_, e)| e)
+    }
+
+    // all enums by name for passes that look up many of them, the first one with a name wins
+    // like in `enum_by_name`
+    pub fn enums_by_name(&self) -> EnumsByName<'_> {
+        let mut enums = HashMap::default();
+        for (_, enum_) in self.enums() {
+            enums.entry(enum_.name()).or_insert(enum_);
+        }
+        EnumsByName(enums)
+    }
+
+    // finds classes and functions whose name hashes to the given FNV-1a 64-bit value,
+    // useful for crash and RTTI dumps which only contain hashes
+    pub fn find_by_name_hash(&self, hash: u64) -> impl Iterator<Item = u32> + '_ {
+        self.definitions
+            .iter()
+            .enumerate()
+            .filter(|(_, def)| matches!(def, Definition::Class(_) | Definition::Function(_)))
+            .filter(move |(_, def)| {
+                self.get_item(def.name())
+                    .is_some_and(|name| util::fnv1a64(name) == hash)
+            })
+            .map(|(i, _)| i as u32)
+    }
+
+    // a range of indices clamped to the definitions present in the bundle
+    pub fn index_range<A>(&self, range: impl ops::RangeBounds<NzPoolIndex<A>>) -> IndexRange<A> {
+        let start = match range.start_bound() {
+            ops::Bound::Included(&start) => u32::from(start),
+            ops::Bound::Excluded(&start) => u32::from(start).saturating_add(1),
+            ops::Bound::Unbounded => 1,
+        };
+        let end = match range.end_bound() {
+            ops::Bound::Included(&end) => u32::from(end).saturating_add(1),
+            ops::Bound::Excluded(&end) => u32::from(end),
+            ops::Bound::Unbounded => self.definition_count(),
+        };
+        IndexRange::new(start, end.min(self.definition_count()))
+    }
+
+    #[inline]
+    pub fn definition(&self, index: u32) -> Option<&Definition<'i>> {
+        self.definitions.get(index as usize)
+    }
+
+    #[inline]
+    pub(crate) fn definition_count(&self) -> u32 {
+        self.definitions.len() as u32
+    }
+
+    // panics if the number of definitions would exceed the range of indices, see `try_define`
+    #[inline]
     pub fn define<A>(&mut self, def: A) -> NzPoolIndex<A::Index>
     where
         A: DefinitionIndex<'i>,
     {
-        let index = self.definitions.len() as u32;
-        self.definitions.push(def.into());
-        NzPoolIndex::new(index).expect("definition index set to zero")
+        self.try_define(def)
+            .expect("number of definitions should fit the range of indices")
+    }
+
+    pub fn try_define<A>(&mut self, def: A) -> Result<NzPoolIndex<A::Index>, IndexOverflow>
+    where
+        A: DefinitionIndex<'i>,
+    {
+        let index = u32::from_usize(self.definitions.len()).ok_or(IndexOverflow)?;
+        let definition = def.into();
+        if let Some(journal) = &mut self.journal {
+            journal.record(Edit::Define {
+                index,
+                definition: definition.clone(),
+            });
+        }
+        self.definitions.push(definition);
+        sync_header(&mut self.headers, &self.definitions, index);
+        self.observers
+            .on_define(index, &self.definitions[index as usize]);
+        self.record_change(index, ChangeKind::Defined);
+        Ok(NzPoolIndex::new(index).expect("definition index set to zero"))
+    }
+
+    pub fn modify<I>(&mut self, index: I, f: impl FnOnce(&mut I::Output)) -> bool
+    where
+        I: PoolItemIndexMut<'i> + Into<u32> + Copy,
+    {
+        let pos = index.into();
+        let tracked = self.journal.is_some() || !self.observers.is_empty();
+        let old = tracked
+            .then(|| self.definitions.get(pos as usize).cloned())
+            .flatten();
+        let Some(item) = self
+            .definitions
+            .get_mut(pos as usize)
+            .and_then(|def| index.get_mut(def))
+        else {
+            return false;
+        };
+        f(item);
+        self.record_change(pos, ChangeKind::Modified);
+        if let Some(old) = old {
+            let new = &self.definitions[pos as usize];
+            self.observers.on_modify(pos, &old, new);
+            if let Some(journal) = &mut self.journal {
+                journal.record(Edit::Modify {
+                    index: pos,
+                    old,
+                    new: new.clone(),
+                });
+            }
+        }
+        true
+    }
+
+    // like `modify`, but for a definition of any kind
+    pub(crate) fn modify_definition(&mut self, pos: u32, f: impl FnOnce(&mut Definition<'i>)) {
+        let Some(def) = self.definitions.get_mut(pos as usize) else {
+            return;
+        };
+        let old = def.clone();
+        f(def);
+        self.record_change(pos, ChangeKind::Modified);
+        let new = &self.definitions[pos as usize];
+        self.observers.on_modify(pos, &old, new);
+        if let Some(journal) = &mut self.journal {
+            journal.record(Edit::Modify {
+                index: pos,
+                old,
+                new: new.clone(),
+            });
+        }
+    }
+
+    // the slot is replaced with a placeholder so that the remaining indices stay valid
+    pub fn remove<A>(&mut self, index: NzPoolIndex<A>) -> Option<Definition<'i>> {
+        let pos = u32::from(index);
+        let slot = self.definitions.get_mut(pos as usize)?;
+        let definition = mem::replace(slot, Definition::UNDEFINED);
+        sync_header(&mut self.headers, &self.definitions, pos);
+        self.observers.on_remove(pos, &definition);
+        self.record_change(pos, ChangeKind::Removed);
+        if let Some(journal) = &mut self.journal {
+            journal.record(Edit::Remove {
+                index: pos,
+                definition: definition.clone(),
+            });
+        }
+        Some(definition)
+    }
+
+    // rebuilds the parent references of fields, methods, parameters, locals and enum members
+    // from the child lists of their containers, the first container listing a child wins
+    pub fn repair_links(&mut self) -> Vec<LinkRepair> {
+        let repairs = self.broken_links();
+        for repair in &repairs {
+            self.modify_definition(repair.index, |def| {
+                def.set_parent(repair.new_parent);
+            });
+        }
+        repairs
+    }
+
+    // the changes `repair_links` would make
+    pub(crate) fn broken_links(&self) -> Vec<LinkRepair> {
+        let mut parents: Vec<Option<(u32, DefinitionKind)>> = vec![None; self.definitions.len()];
+        for (i, def) in self.definitions.iter().enumerate() {
+            let parent = i as u32;
+            match def {
+                Definition::Class(c) => {
+                    claim(&mut parents, parent, c.methods(), DefinitionKind::Function);
+                    claim(&mut parents, parent, c.fields(), DefinitionKind::Field);
+                }
+                Definition::Enum(e) => {
+                    claim(&mut parents, parent, e.values(), DefinitionKind::EnumMember);
+                }
+                Definition::Function(f) => {
+                    claim(
+                        &mut parents,
+                        parent,
+                        f.parameters(),
+                        DefinitionKind::Parameter,
+                    );
+                    claim(&mut parents, parent, f.locals(), DefinitionKind::Local);
+                }
+                _ => {}
+            }
+        }
+
+        let mut repairs = vec![];
+        for (i, entry) in parents.into_iter().enumerate() {
+            let Some((parent, kind)) = entry else {
+                continue;
+            };
+            let def = &self.definitions[i];
+            let old_parent = def.parent();
+            if def.kind() != kind || old_parent == parent {
+                continue;
+            }
+            repairs.push(LinkRepair {
+                index: i as u32,
+                old_parent,
+                new_parent: parent,
+            });
+        }
+        repairs
+    }
+
+    // moves every definition to a new position, `order` lists the current index of each definition
+    // in its new place and all indices are rewritten to match, annotations move along,
+    // the journal is reset since its edits refer to the old positions and the original layout
+    // is discarded
+    // the definitions before `start` have to stay in place and can't refer to the ones that move,
+    // so they're left as they are, the bundle is not modified when a body can't be decoded or
+    // a definition refers to an index out of range
+    pub(crate) fn permute_definitions(
+        &mut self,
+        order: &[u32],
+        start: usize,
+    ) -> Result<Vec<u32>, SortError> {
+        debug_assert_eq!(order.len(), self.definitions.len());
+        let mut positions = vec![0; order.len()];
+        for (new, &old) in order.iter().enumerate() {
+            positions[old as usize] = new as u32;
+        }
+
+        let mut moved = Vec::with_capacity(order.len().saturating_sub(start));
+        for (i, def) in self.definitions.iter().enumerate().skip(start) {
+            let mut def = def.clone();
+            def.decode_body().map_err(|error| SortError::Decoding {
+                definition: i as u32,
+                error,
+            })?;
+            let mut dangling = None;
+            def.map_definitions(|index| match positions.get(index as usize) {
+                Some(&new) => new,
+                None => *dangling.get_or_insert(index),
+            });
+            if let Some(index) = dangling {
+                return Err(SortError::Dangling {
+                    definition: i as u32,
+                    index,
+                });
+            }
+            moved.push(def);
+        }
+
+        let mut definitions = mem::take(&mut self.definitions)
+            .into_iter()
+            .take(start)
+            .chain(moved)
+            .map(Some)
+            .collect::<Vec<_>>();
+        self.definitions = order
+            .iter()
+            .map(|&old| definitions[old as usize].take().expect("duplicate index"))
+            .collect();
+        if !self.headers.is_empty() {
+            self.headers = order
+                .iter()
+                .map(|&old| self.headers.get(old as usize).copied().unwrap_or_default())
+                .collect();
+        }
+        if self.journal.is_some() {
+            self.journal = Some(Journal::new());
+        }
+        self.layout = None;
+        self.annotations.remap_definitions(&positions);
+        if let Some(provenance) = &mut self.provenance {
+            provenance.remap_definitions(&positions);
+        }
+        Ok(positions)
+    }
+
+    // recalculates the size operand of `EnumToI32` and `I32ToEnum` instructions from the declared
+    // size of the enum, for bundles from tools that always write 4, bodies that can't be decoded
+    // are left as they are
+    pub fn repair_enum_sizes(&mut self) -> Vec<EnumSizeRepair> {
+        let enums = self.enums_by_name();
+        let changes = (0..self.definition_count())
+            .filter_map(|i| Some((i, self.enum_size_repairs(i, &enums)?)))
+            .collect::<Vec<_>>();
+
+        let mut repairs = vec![];
+        for (i, (code, fixed)) in changes {
+            self.modify_definition(i, |def| {
+                if let Definition::Function(fun) = def {
+                    fun.set_code(code);
+                }
+            });
+            repairs.extend(fixed);
+        }
+        repairs
+    }
+
+    // the code of a function with corrected enum sizes and the changes made, if there are any
+    pub(crate) fn enum_size_repairs(
+        &self,
+        index: u32,
+        enums: &EnumsByName<'_>,
+    ) -> Option<(Vec<Instr>, Vec<EnumSizeRepair>)> {
+        let Some(Definition::Function(fun)) = self.definition(index) else {
+            return None;
+        };
+        let mut code = fun.body().code_owned().ok()?;
+        let mut repairs = vec![];
+        let mut offset = 0;
+        for instr in &mut code {
+            if let Instr::EnumToI32 { enum_type, size } | Instr::I32ToEnum { enum_type, size } =
+                instr
+            {
+                if let Some(declared) = enums.size_mismatch(self, *enum_type, *size) {
+                    repairs.push(EnumSizeRepair {
+                        function: index,
+                        offset: ByteOffset::new(offset),
+                        old_size: *size,
+                        new_size: declared,
+                    });
+                    *size = declared;
+                }
+            }
+            offset += u32::from(instr.size());
+        }
+        (!repairs.is_empty()).then_some((code, repairs))
+    }
+
+    // attributes the definitions defined, modified or removed from now on to the source,
+    // provenance tracking starts with the first call
+    pub fn set_source(&mut self, source: impl Into<String>) {
+        self.provenance
+            .get_or_insert_with(Provenance::new)
+            .set_source(Some(source.into()));
+    }
+
+    // changes made from now on are not attributed to any source
+    pub fn clear_source(&mut self) {
+        if let Some(provenance) = &mut self.provenance {
+            provenance.set_source(None);
+        }
+    }
+
+    #[inline]
+    pub fn provenance(&self) -> Option<&Provenance> {
+        self.provenance.as_ref()
+    }
+
+    // replaces the provenance, e.g. with one stored next to the cache, and returns the old one
+    #[inline]
+    pub fn set_provenance(&mut self, provenance: Option<Provenance>) -> Option<Provenance> {
+        mem::replace(&mut self.provenance, provenance)
+    }
+
+    #[inline]
+    fn record_change(&mut self, index: u32, change: ChangeKind) {
+        if let Some(provenance) = &mut self.provenance {
+            provenance.record(index, change);
+        }
+    }
+
+    #[inline]
+    pub fn annotations(&self) -> &Annotations {
+        &self.annotations
+    }
+
+    #[inline]
+    pub fn annotations_mut(&mut self) -> &mut Annotations {
+        &mut self.annotations
+    }
+
+    #[inline]
+    pub fn enable_journal(&mut self) {
+        self.journal.get_or_insert_with(Journal::new);
+    }
+
+    #[inline]
+    pub fn disable_journal(&mut self) -> Option<Journal<'i>> {
+        self.journal.take()
+    }
+
+    #[inline]
+    pub fn journal(&self) -> Option<&Journal<'i>> {
+        self.journal.as_ref()
+    }
+
+    pub fn add_observer<O>(&mut self, observer: O) -> ObserverId
+    where
+        O: BundleObserver + Send + Sync + 'static,
+    {
+        self.observers.add(Box::new(observer))
+    }
+
+    pub fn remove_observer(&mut self, id: ObserverId) -> bool {
+        self.observers.remove(id).is_some()
+    }
+
+    // returns whether there was an edit to undo, one that doesn't fit the definitions is an error
+    // and leaves the bundle unchanged
+    pub fn undo(&mut self) -> Result<bool, EditMismatch> {
+        let Some(journal) = &mut self.journal else {
+            return Ok(false);
+        };
+        let (index, change) = match journal.undo(&mut self.definitions) {
+            Some(edit) => {
+                let edit = edit?;
+                sync_header(&mut self.headers, &self.definitions, edit.index());
+                self.observers.on_revert(edit);
+                (edit.index(), edit.reverted_change())
+            }
+            None => return Ok(false),
+        };
+        self.record_change(index, change);
+        Ok(true)
+    }
+
+    pub fn redo(&mut self) -> Result<bool, EditMismatch> {
+        let Some(journal) = &mut self.journal else {
+            return Ok(false);
+        };
+        let (index, change) = match journal.redo(&mut self.definitions) {
+            Some(edit) => {
+                let edit = edit?;
+                sync_header(&mut self.headers, &self.definitions, edit.index());
+                self.observers.on_apply(edit);
+                (edit.index(), edit.change())
+            }
+            None => return Ok(false),
+        };
+        self.record_change(index, change);
+        Ok(true)
+    }
+}
+
+// keeps the headers of a read bundle aligned with its definitions after the one at `pos` has
+// been added, removed or restored, bundles built from scratch have no headers to keep
+fn sync_header(headers: &mut Vec<DefinitionHeader>, definitions: &[Definition<'_>], pos: u32) {
+    if headers.is_empty() {
+        return;
+    }
+    headers.resize(definitions.len(), DefinitionHeader::default());
+    if let Some(def) = definitions.get(pos as usize) {
+        headers[pos as usize] = DefinitionHeader::from_defintion(def, 0, 0);
+    }
+}
+
+fn claim<A>(
+    parents: &mut [Option<(u32, DefinitionKind)>],
+    parent: u32,
+    children: &[NzPoolIndex<A>],
+    kind: DefinitionKind,
+) {
+    for &child in children {
+        if let Some(slot @ None) = parents.get_mut(u32::from(child) as usize) {
+            *slot = Some((parent, kind));
+        }
+    }
+}
+
+// a parent reference that was changed by ScriptBundle::repair_links
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LinkRepair {
+    pub index: u32,
+    pub old_parent: u32,
+    pub new_parent: u32,
+}
+
+// enums by name, built once by ScriptBundle::enums_by_name for passes that look up many of them
+#[derive(Debug, Default)]
+pub struct EnumsByName<'a>(HashMap<CNameIndex, &'a Enum, RandomState>);
+
+impl EnumsByName<'_> {
+    #[inline]
+    pub(crate) fn get(&self, name: CNameIndex) -> Option<&Enum> {
+        self.0.get(&name).copied()
+    }
+
+    // the declared size of the enum if the size operand of a conversion disagrees with it,
+    // conversions of unknown enums are left alone
+    pub(crate) fn size_mismatch(
+        &self,
+        bundle: &ScriptBundle<'_>,
+        enum_type: TypeIndex,
+        size: u8,
+    ) -> Option<u8> {
+        bundle
+            .get_item(enum_type)
+            .and_then(|typ| self.get(typ.name()))
+            .map(Enum::size)
+            .filter(|&declared| declared != size)
     }
 }
 
+// a size operand that was changed by ScriptBundle::repair_enum_sizes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EnumSizeRepair {
+    pub function: u32,
+    pub offset: ByteOffset,
+    pub old_size: u8,
+    pub new_size: u8,
+}
+
 impl Default for ScriptBundle<'_> {
     fn default() -> Self {
         Self {
@@ -236,18 +1293,48 @@ impl Default for ScriptBundle<'_> {
             resources: StringPool::new(),
             strings: StringPool::new(),
             definitions: vec![Definition::UNDEFINED],
+            headers: vec![],
+            journal: None,
+            observers: Observers::default(),
+            layout: None,
+            annotations: Annotations::default(),
+            provenance: None,
         }
     }
 }
 
+// can be implemented outside of this crate to add custom typed views of definitions, e.g.
+// an index that resolves to any function, implementations are expected to go through
+// `ScriptBundle::definition`, indexing the bundle with `bundle[index]` additionally requires
+// `Copy` and `Display` and `ScriptBundle::modify` requires `Into<u32>`
 pub trait PoolItemIndex<'i> {
     type Output: ?Sized;
 
     fn get<'a>(self, bundle: &'a ScriptBundle<'i>) -> Option<&'a Self::Output>;
+
+    // resolves the indices in order, returns None if any of them doesn't resolve, the built-in
+    // indices check the whole slice against the pool once instead of looking up each of them
+    fn get_all<'a>(indices: &[Self], bundle: &'a ScriptBundle<'i>) -> Option<Vec<&'a Self::Output>>
+    where
+        Self: Sized + Copy,
+    {
+        indices.iter().map(|&index| index.get(bundle)).collect()
+    }
 }
 
+// whether all of the indices fall within a pool of the given length
+fn all_in_bounds<I: Copy + Into<u32>>(indices: &[I], len: usize) -> bool {
+    indices
+        .iter()
+        .map(|&index| index.into())
+        .max()
+        .is_none_or(|max| (max as usize) < len)
+}
+
+// only the definition at the position of the index is handed out, so that the bundle can
+// record the change once it's made
 pub trait PoolItemIndexMut<'i>: PoolItemIndex<'i> {
-    fn get_mut<'a>(self, bundle: &'a mut ScriptBundle<'i>) -> Option<&'a mut Self::Output>;
+    fn get_mut<'a>(self, definition: &'a mut Definition<'i>) -> Option<&'a mut Self::Output>;
 }
 
 macro_rules! impl_string_item {
@@ -259,9 +1346,19 @@ macro_rules! impl_string_item {
                 bundle
                     .$name
                     .strings
-                    .get_index(u32::from(self) as _)
+                    .get(u32::from(self) as usize)
                     .map(Str::as_str)
             }
+
+            fn get_all<'a>(indices: &[Self], bundle: &'a ScriptBundle<'_>) -> Option<Vec<&'a str>> {
+                let strings = &bundle.$name.strings;
+                all_in_bounds(indices, strings.len()).then(|| {
+                    indices
+                        .iter()
+                        .map(|&index| strings[u32::from(index) as usize].as_str())
+                        .collect()
+                })
+            }
         }
     };
 }
@@ -283,11 +1380,28 @@ macro_rules! impl_def_item {
                     None
                 }
             }
+
+            fn get_all<'a>(indices: &[Self], bundle: &'a ScriptBundle<'i>) -> Option<Vec<&'a Self::Output>> {
+                let definitions = &bundle.definitions;
+                if !all_in_bounds(indices, definitions.len()) {
+                    return None;
+                }
+                indices
+                    .iter()
+                    .map(|&index| match &definitions[u32::from(index) as usize] {
+                        Definition::$ty(val) => {
+                            let val: &Self::Output = val;
+                            Some(val)
+                        }
+                        _ => None,
+                    })
+                    .collect()
+            }
         }
 
         impl<'i> PoolItemIndexMut<'i> for $idx {
-            fn get_mut<'a>(self, bundle: &'a mut ScriptBundle<'i>) -> Option<&'a mut Self::Output> {
-                if let Some(Definition::$ty(val)) = bundle.definitions.get_mut(u32::from(self) as usize) {
+            fn get_mut<'a>(self, definition: &'a mut Definition<'i>) -> Option<&'a mut Self::Output> {
+                if let Definition::$ty(val) = definition {
                     Some(val)
                 } else {
                     None
@@ -324,47 +1438,172 @@ where
     }
 }
 
-impl<'i, I> ops::IndexMut<I> for ScriptBundle<'i>
-where
-    I: PoolItemIndexMut<'i> + fmt::Display + Copy,
-{
-    fn index_mut(&mut self, index: I) -> &mut Self::Output {
-        match I::get_mut(index, self) {
-            Some(val) => val,
-            None => panic!(
-                "unresolved {} index: {index}",
-                std::any::type_name::<I::Output>()
-            ),
+// how strings are matched by StringPool::find
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LookupMode {
+    #[default]
+    Exact,
+    // ascii case is ignored
+    CaseInsensitive,
+    // strings with the same name hash match, e.g. to resolve data that was hashed by the engine
+    Hash,
+}
+
+#[derive(Debug, Default)]
+pub struct StringPool<'i, A> {
+    // entries are kept in their original order, including duplicates pushed explicitly or
+    // preserved when reading
+    strings: Vec<Str<'i>>,
+    // the index of the first occurrence of each string, hashed by the string it points at
+    lookup: HashTable<u32>,
+    hasher: RandomState,
+    // built on the first case-insensitive or hash lookup and kept up to date by pushes after that
+    by_lowercase: OnceLock<HashMap<Box<str>, u32, RandomState>>,
+    by_hash: OnceLock<HashMap<u64, u32, RandomState>>,
+    // set once indices have been embedded in code and must not shift anymore
+    frozen: bool,
+    phantom: PhantomData<PoolIndex<A>>,
+}
+
+impl<'i, A> StringPool<'i, A> {
+    #[inline]
+    fn new() -> Self {
+        StringPool {
+            strings: Vec::new(),
+            lookup: HashTable::new(),
+            hasher: RandomState::default(),
+            by_lowercase: OnceLock::new(),
+            by_hash: OnceLock::new(),
+            frozen: false,
+            phantom: PhantomData,
+        }
+    }
+
+    // returns the index of an existing equal string if there is one, panics if the pool is frozen
+    #[deprecated(note = "use `get_or_add` or `add_new` to make the intent explicit")]
+    pub fn add(&mut self, string: impl Into<Str<'i>>) -> PoolIndex<A> {
+        self.get_or_add(string)
+            .expect("string pool should not be frozen")
+    }
+
+    pub fn get_or_add(&mut self, string: impl Into<Str<'i>>) -> Result<PoolIndex<A>, PoolError> {
+        let string = string.into();
+        match self.get_index(&string) {
+            Some(index) => Ok(index),
+            None => self.push(string),
+        }
+    }
+
+    // fails if an equal string is already present
+    pub fn add_new(&mut self, string: impl Into<Str<'i>>) -> Result<PoolIndex<A>, PoolError> {
+        let string = string.into();
+        if self.get_index(&string).is_some() {
+            return Err(PoolError::AlreadyExists);
+        }
+        self.push(string)
+    }
+
+    // always appends, even if an equal string is already present
+    pub fn push(&mut self, string: impl Into<Str<'i>>) -> Result<PoolIndex<A>, PoolError> {
+        if self.frozen {
+            return Err(PoolError::Frozen);
         }
+        self.push_unchecked(string.into())
+    }
+
+    fn push_unchecked(&mut self, string: Str<'i>) -> Result<PoolIndex<A>, PoolError> {
+        let index = u32::from_usize(self.strings.len()).ok_or(PoolError::Overflow)?;
+        if self.get_index(&string).is_none() {
+            let (strings, hasher) = (&self.strings, &self.hasher);
+            let hash = hash_str(hasher, &string);
+            self.lookup
+                .insert_unique(hash, index, |&i| hash_str(hasher, &strings[i as usize]));
+        }
+        if let Some(map) = self.by_lowercase.get_mut() {
+            map.entry(string.as_str().to_ascii_lowercase().into())
+                .or_insert(index);
+        }
+        if let Some(map) = self.by_hash.get_mut() {
+            map.entry(util::fnv1a64(&string)).or_insert(index);
+        }
+        self.strings.push(string);
+        Ok(PoolIndex::new(index))
+    }
+
+    #[inline]
+    pub fn freeze(&mut self) {
+        self.frozen = true;
+    }
+
+    #[inline]
+    pub fn unfreeze(&mut self) {
+        self.frozen = false;
+    }
+
+    #[inline]
+    pub fn is_frozen(&self) -> bool {
+        self.frozen
+    }
+
+    #[inline]
+    pub fn get_index(&self, str: &str) -> Option<PoolIndex<A>> {
+        let hash = hash_str(&self.hasher, str);
+        self.lookup
+            .find(hash, |&i| self.strings[i as usize].as_str() == str)
+            .map(|&index| PoolIndex::new(index))
+    }
+
+    // the first string equal to the given one ignoring ascii case, the way the engine compares
+    // names in places, the case-folded index is built on first use
+    pub fn get_index_ci(&self, str: &str) -> Option<PoolIndex<A>> {
+        let map = self.by_lowercase.get_or_init(|| {
+            let mut map = HashMap::default();
+            for (i, s) in self.strings.iter().enumerate() {
+                map.entry(s.as_str().to_ascii_lowercase().into())
+                    .or_insert(i as u32);
+            }
+            map
+        });
+        let index = map.get(str.to_ascii_lowercase().as_str())?;
+        Some(PoolIndex::new(*index))
+    }
+
+    // the first string with the given FNV-1a 64-bit hash, the one the engine uses for names,
+    // the hash index is built on first use
+    pub fn get_index_by_hash(&self, hash: u64) -> Option<PoolIndex<A>> {
+        let map = self.by_hash.get_or_init(|| {
+            let mut map = HashMap::default();
+            for (i, s) in self.strings.iter().enumerate() {
+                map.entry(util::fnv1a64(s)).or_insert(i as u32);
+            }
+            map
+        });
+        Some(PoolIndex::new(*map.get(&hash)?))
     }
-}
 
-#[derive(Debug, Default)]
-pub struct StringPool<'i, A> {
-    strings: IndexSet<Str<'i>, ahash::RandomState>,
-    phantom: PhantomData<PoolIndex<A>>,
-}
+    pub fn find(&self, str: &str, mode: LookupMode) -> Option<PoolIndex<A>> {
+        match mode {
+            LookupMode::Exact => self.get_index(str),
+            LookupMode::CaseInsensitive => self.get_index_ci(str),
+            LookupMode::Hash => self.get_index_by_hash(util::fnv1a64(str)),
+        }
+    }
 
-impl<'i, A> StringPool<'i, A> {
     #[inline]
-    fn new() -> Self {
-        StringPool {
-            strings: IndexSet::default(),
-            phantom: PhantomData,
-        }
+    pub fn iter(&self) -> impl Iterator<Item = &str> {
+        self.strings.iter().map(Str::as_str)
     }
 
+    // a handle to the pooled string that doesn't borrow the pool, cloning it does not copy the
+    // contents, with the `shared` feature the handle is `Send` and `Sync`
     #[inline]
-    pub fn add(&mut self, string: impl Into<Str<'i>>) -> PoolIndex<A> {
-        let (index, _) = self.strings.insert_full(string.into());
-        PoolIndex::new(index as _)
+    pub fn get_shared(&self, index: PoolIndex<A>) -> Option<Str<'i>> {
+        self.strings.get(u32::from(index) as usize).cloned()
     }
 
     #[inline]
-    pub fn get_index(&self, str: &str) -> Option<PoolIndex<A>> {
-        self.strings
-            .get_full(str)
-            .map(|(index, _)| PoolIndex::new(index as _))
+    pub fn has_duplicates(&self) -> bool {
+        self.lookup.len() != self.strings.len()
     }
 
     #[inline]
@@ -372,27 +1611,47 @@ impl<'i, A> StringPool<'i, A> {
         self.strings.len()
     }
 
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.strings.is_empty()
+    }
+
+    #[inline]
+    fn shrink_to_fit(&mut self) {
+        self.strings.shrink_to_fit();
+        let (strings, hasher) = (&self.strings, &self.hasher);
+        self.lookup
+            .shrink_to_fit(|&i| hash_str(hasher, &strings[i as usize]));
+    }
+
+    #[inline]
     pub fn into_owned(self) -> StringPool<'static, A> {
-        StringPool {
-            strings: self.strings.into_iter().map(Str::into_owned).collect(),
-            phantom: PhantomData,
-        }
+        self.relocate(&Owned)
+    }
+
+    fn relocate<'b>(self, to: &impl Relocate<'b>) -> StringPool<'b, A> {
+        let mut pool: StringPool<'b, A> = self
+            .strings
+            .into_iter()
+            .map(|str| to.relocate_str(str))
+            .collect();
+        pool.frozen = self.frozen;
+        pool
     }
 
     fn write<Ctx>(
         &self,
         offset: &mut usize,
         bytes: &mut [u8],
-        index: &StringData<'i>,
+        string_offsets: &[u32],
         ctx: Ctx,
     ) -> byte::Result<TableHeader>
     where
         Ctx: Endianess,
     {
         let pos = *offset;
-        for string in &self.strings {
-            let pos = index.dedup.get(string).expect("should contain all strings");
-            bytes.write(offset, pos, ctx)?;
+        for string_offset in string_offsets {
+            bytes.write(offset, string_offset, ctx)?;
         }
         Ok(TableHeader::new(
             pos as _,
@@ -402,23 +1661,111 @@ impl<'i, A> StringPool<'i, A> {
     }
 }
 
-impl<'i, Index> FromIterator<&'i str> for StringPool<'i, Index> {
-    fn from_iter<T: IntoIterator<Item = &'i str>>(iter: T) -> Self {
+#[inline]
+fn hash_str(hasher: &RandomState, str: &str) -> u64 {
+    BuildHasher::hash_one(hasher, str)
+}
+
+impl<A> StringPool<'_, A> {
+    // the pool encoded on its own the way a new bundle would lay it out, the offsets in the
+    // table are relative to the start of the string data
+    pub fn to_bytes(&self) -> byte::Result<PoolBytes> {
+        let mut data = StringData::with_capacity(self.strings.len());
+        let offsets = data.resolve(self, None);
+
+        let mut string_data = vec![0; data.length];
+        let offset = &mut 0;
+        for str in data.added() {
+            string_data.write(offset, str.as_str(), Delimiter(0))?;
+        }
+        let mut table = vec![0; offsets.len() * mem::size_of::<u32>()];
+        let offset = &mut 0;
+        for pos in &offsets {
+            table.write(offset, pos, ENDIANESS)?;
+        }
+        Ok(PoolBytes { string_data, table })
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct PoolBytes {
+    pub string_data: Vec<u8>,
+    pub table: Vec<u8>,
+}
+
+// the exact encoding of a definition as it appears in the definition section of a bundle
+#[inline]
+pub fn encode_definition(def: &Definition<'_>) -> byte::Result<Vec<u8>> {
+    def.to_bytes(ENDIANESS)
+}
+
+impl<'i, Index> FromIterator<Str<'i>> for StringPool<'i, Index> {
+    fn from_iter<T: IntoIterator<Item = Str<'i>>>(iter: T) -> Self {
         let mut pool = StringPool::new();
-        pool.strings.extend(iter.into_iter().map(Str::borrowed));
+        for string in iter {
+            pool.push_unchecked(string)
+                .expect("number of strings should fit the range of indices");
+        }
         pool
     }
 }
 
+impl<'i, Index> FromIterator<&'i str> for StringPool<'i, Index> {
+    #[inline]
+    fn from_iter<T: IntoIterator<Item = &'i str>>(iter: T) -> Self {
+        iter.into_iter().map(Str::borrowed).collect()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PoolError {
+    AlreadyExists,
+    Frozen,
+    // the pool already holds as many strings as there are indices
+    Overflow,
+}
+
+impl fmt::Display for PoolError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PoolError::AlreadyExists => f.write_str("string is already present in the pool"),
+            PoolError::Frozen => f.write_str("string pool is frozen"),
+            PoolError::Overflow => f.write_str("string pool exceeds the range of indices"),
+        }
+    }
+}
+
+impl std::error::Error for PoolError {}
+
 #[derive(Debug)]
 pub struct WriteableBundle<'i> {
     bundle: ScriptBundle<'i>,
     string_data: StringData<'i>,
+    offsets: [Vec<u32>; 4],
 }
 
 impl<'i> WriteableBundle<'i> {
     #[cfg(feature = "mmap")]
+    #[inline]
     pub fn save(&self, path: impl AsRef<std::path::Path>) -> Result<(), SaveError> {
+        self.save_with(path, SaveOptions::default())
+    }
+
+    #[cfg(feature = "mmap")]
+    pub fn save_with(
+        &self,
+        path: impl AsRef<std::path::Path>,
+        options: SaveOptions,
+    ) -> Result<(), SaveError> {
+        let path = &*util::extended_path(path.as_ref());
+        if options.backup && path.exists() {
+            std::fs::copy(path, with_suffix(path, ".bak")).map_err(SaveError::Io)?;
+        }
+        if options.atomic {
+            let bytes = self.to_bytes().map_err(SaveError::Encoding)?;
+            return write_atomic(path, &bytes).map_err(SaveError::Io);
+        }
+
         let (mut out, _) = vmap::MapMut::with_options()
             .create(true)
             .truncate(true)
@@ -438,13 +1785,108 @@ impl<'i> WriteableBundle<'i> {
 }
 
 impl<'i, Ctx: Endianess> TryWrite<Ctx> for WriteableBundle<'i> {
+    #[inline]
     fn try_write(&self, bytes: &mut [u8], ctx: Ctx) -> byte::Result<usize> {
+        self.write_sections(bytes, ctx, None, &mut Monitor::new())
+    }
+}
+
+impl WriteableBundle<'_> {
+    pub fn to_bytes_with_report(&self) -> byte::Result<(Vec<u8>, WriteReport)> {
+        let mut bytes = vec![0; self.measure(())];
+        let mut report = WriteReport::default();
+        self.write_sections(
+            &mut bytes,
+            ENDIANESS,
+            Some(&mut report),
+            &mut Monitor::new(),
+        )?;
+        Ok((bytes, report))
+    }
+
+    pub fn to_bytes_monitored(&self, mut monitor: Monitor<'_>) -> byte::Result<Vec<u8>> {
+        let mut bytes = vec![0; self.measure(())];
+        self.write_sections(&mut bytes, ENDIANESS, None, &mut monitor)?;
+        Ok(bytes)
+    }
+
+    // encodes the parts of the bundle on their own and compares their sizes with the ones
+    // `measure` expects, a disagreement would otherwise surface as an overflow deep inside
+    // try_write without saying what caused it
+    pub fn preflight(&self) -> Result<(), PreflightError> {
+        let actual = self.string_data.base.len()
+            + self
+                .string_data
+                .added()
+                .map(|str| str.len() + 1)
+                .sum::<usize>();
+        if actual != self.string_data.length {
+            return Err(PreflightError::StringDataSize {
+                expected: self.string_data.length,
+                actual,
+            });
+        }
+
+        let mut scratch = vec![];
+        for (i, def) in self.bundle.definitions.iter().enumerate().skip(1) {
+            let index = u32::from_usize(i).ok_or(PreflightError::Write(byte::Error::BadInput {
+                err: "bundle exceeds the maximum number of definitions",
+            }))?;
+            let expected = def.measure(ENDIANESS);
+            // the buffer grows until the definition fits, so the actual size can be reported
+            let mut capacity = expected.max(16) * 2;
+            let actual = loop {
+                scratch.clear();
+                scratch.resize(capacity, 0);
+                let offset = &mut 0;
+                match scratch.write(offset, def, ENDIANESS) {
+                    Ok(()) => break *offset,
+                    Err(byte::Error::Incomplete) if capacity < u32::MAX as usize => {
+                        capacity *= 2;
+                    }
+                    Err(error) => return Err(PreflightError::Encoding { index, error }),
+                }
+            };
+            if actual != expected {
+                return Err(PreflightError::DefinitionSize {
+                    index,
+                    expected,
+                    actual,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    // runs the preflight before encoding the bundle
+    pub fn to_bytes_checked(&self) -> Result<Vec<u8>, PreflightError> {
+        self.preflight()?;
+        self.to_bytes().map_err(PreflightError::Write)
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(skip_all, fields(size = bytes.len()), err(Debug))
+    )]
+    fn write_sections<Ctx: Endianess>(
+        &self,
+        bytes: &mut [u8],
+        ctx: Ctx,
+        mut report: Option<&mut WriteReport>,
+        monitor: &mut Monitor<'_>,
+    ) -> byte::Result<usize> {
         let offset = &mut 0;
         // skip the header
         *offset += Header::SIZE as usize;
 
         let string_data_start = *offset;
-        for str in self.string_data.dedup.keys() {
+        let base = &self.string_data.base;
+        bytes
+            .get_mut(*offset..*offset + base.len())
+            .ok_or(byte::Error::Incomplete)?
+            .copy_from_slice(base);
+        *offset += base.len();
+        for str in self.string_data.added() {
             bytes.write(offset, str.as_str(), Delimiter(0))?;
         }
 
@@ -454,18 +1896,22 @@ impl<'i, Ctx: Endianess> TryWrite<Ctx> for WriteableBundle<'i> {
             &bytes[string_data_start..*offset],
         );
 
+        let [cname_offsets, tdb_id_offsets, resource_offsets, string_offsets] = &self.offsets;
         let cnames = self
             .bundle
             .cnames
-            .write(offset, bytes, &self.string_data, ctx)?;
+            .write(offset, bytes, cname_offsets, ctx)?;
         let tweakdb_ids = self
             .bundle
             .tdb_ids
-            .write(offset, bytes, &self.string_data, ctx)?;
+            .write(offset, bytes, tdb_id_offsets, ctx)?;
         let resources = self
             .bundle
             .resources
-            .write(offset, bytes, &self.string_data, ctx)?;
+            .write(offset, bytes, resource_offsets, ctx)?;
+
+        #[cfg(feature = "tracing")]
+        tracing::debug!(offset = *offset, "wrote string data and pools");
 
         let headers_start = *offset;
         // skip definition headers
@@ -474,19 +1920,43 @@ impl<'i, Ctx: Endianess> TryWrite<Ctx> for WriteableBundle<'i> {
         let strings = self
             .bundle
             .strings
-            .write(offset, bytes, &self.string_data, ctx)?;
+            .write(offset, bytes, string_offsets, ctx)?;
 
+        let layout = self.bundle.layout.as_deref();
         let mut headers_offset = headers_start;
         bytes.write(&mut headers_offset, &DefinitionHeader::default(), ctx)?;
-        for def in self.bundle.definitions.iter().skip(1) {
+        if let Some(report) = report.as_deref_mut() {
+            report.definitions.reserve(self.bundle.definitions.len());
+            report.definitions.push(Section::default());
+        }
+        let total = self.bundle.definitions.len() as u32;
+        for (i, def) in self.bundle.definitions.iter().enumerate().skip(1) {
+            monitor.step(Phase::Write, i as u32 + 1, total)?;
             let pos = *offset;
             bytes.write(offset, def, ctx)?;
             let size = *offset - pos;
+            if let Some(report) = report.as_deref_mut() {
+                report.definitions.push(Section::new(pos, size));
+            }
 
-            let header = DefinitionHeader::from_defintion(def, size as _, pos as _);
+            let mut header = DefinitionHeader::from_defintion(def, size as _, pos as _);
+            if let Some(original) = layout.and(self.bundle.headers.get(i)) {
+                header = header.with_padding(original.padding());
+            }
             bytes.write(&mut headers_offset, &header, ctx)?;
         }
 
+        #[cfg(feature = "tracing")]
+        tracing::debug!(offset = *offset, "wrote definitions");
+
+        // every offset, size and count in the file is bounded by its size, so checking it once is
+        // enough to tell that none of them has been truncated
+        if u32::from_usize(*offset).is_none() {
+            return Err(byte::Error::BadInput {
+                err: "bundle exceeds the maximum size of the format",
+            });
+        }
+
         let definitions = TableHeader::new(
             headers_start as _,
             self.bundle.definitions.len() as _,
@@ -496,11 +1966,11 @@ impl<'i, Ctx: Endianess> TryWrite<Ctx> for WriteableBundle<'i> {
         let header_for_hash = Header {
             magic: Header::MAGIC,
             version: Header::SUPPORTED_VERSION,
-            flags: 0,
-            timestamp: Timestamp::new(),
-            build: 0,
-            crc: 0xDEAD_BEEF,
-            segments: 7,
+            flags: layout.map_or(0, |l| l.flags),
+            timestamp: layout.map_or_else(Timestamp::new, |l| l.timestamp),
+            build: layout.map_or(0, |l| l.build),
+            crc: Header::HASH_PLACEHOLDER,
+            segments: layout.map_or(7, |l| l.segments),
             string_data,
             cnames,
             tweakdb_ids,
@@ -514,10 +1984,105 @@ impl<'i, Ctx: Endianess> TryWrite<Ctx> for WriteableBundle<'i> {
         };
         bytes.write_at(0, &header, ctx)?;
 
+        if let Some(report) = report {
+            let table = |header: &TableHeader, item_size: u32| {
+                Section::new(header.offset as _, (header.count * item_size) as _)
+            };
+            report.header = Section::new(0, Header::SIZE as _);
+            report.string_data = Section::new(string_data.offset as _, string_data.count as _);
+            report.cnames = table(&cnames, 4);
+            report.tweakdb_ids = table(&tweakdb_ids, 4);
+            report.resources = table(&resources, 4);
+            report.definition_headers = table(&definitions, Definition::HEADER_SIZE);
+            report.strings = table(&strings, 4);
+        }
+
         Ok(*offset)
     }
 }
 
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PreflightError {
+    StringDataSize {
+        expected: usize,
+        actual: usize,
+    },
+    DefinitionSize {
+        index: u32,
+        expected: usize,
+        actual: usize,
+    },
+    Encoding {
+        index: u32,
+        error: byte::Error,
+    },
+    // the parts agree with their sizes but writing the whole bundle failed
+    Write(byte::Error),
+}
+
+impl fmt::Display for PreflightError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PreflightError::StringDataSize { expected, actual } => write!(
+                f,
+                "string data is measured as {expected} bytes but encodes to {actual}"
+            ),
+            PreflightError::DefinitionSize {
+                index,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "definition {index} is measured as {expected} bytes but encodes to {actual}"
+            ),
+            PreflightError::Encoding { index, error } => {
+                write!(f, "failed to encode definition {index}: {error}")
+            }
+            PreflightError::Write(error) => write!(f, "failed to write the bundle: {error}"),
+        }
+    }
+}
+
+impl std::error::Error for PreflightError {}
+
+// byte ranges of everything written to a bundle
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct WriteReport {
+    pub header: Section,
+    pub string_data: Section,
+    pub cnames: Section,
+    pub tweakdb_ids: Section,
+    pub resources: Section,
+    pub definition_headers: Section,
+    pub strings: Section,
+    // indexed by definition index, the placeholder at index 0 has an empty section
+    pub definitions: Vec<Section>,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Section {
+    pub offset: u32,
+    pub size: u32,
+}
+
+impl Section {
+    #[inline]
+    fn new(offset: usize, size: usize) -> Self {
+        Self {
+            offset: offset as _,
+            size: size as _,
+        }
+    }
+
+    // none when the section comes from a file and runs past the end of the address range
+    #[inline]
+    pub fn end(&self) -> Option<u32> {
+        self.offset.checked_add(self.size)
+    }
+}
+
 impl<Ctx: Copy> Measure<Ctx> for WriteableBundle<'_> {
     fn measure(&self, ctx: Ctx) -> usize {
         Header::SIZE as usize
@@ -538,7 +2103,15 @@ impl<Ctx: Copy> Measure<Ctx> for WriteableBundle<'_> {
 
 #[derive(Debug, Default)]
 struct StringData<'i> {
-    dedup: IndexMap<Str<'i>, u32, ahash::RandomState>,
+    // verbatim string data of the original bundle, written before any new strings
+    base: Cow<'i, [u8]>,
+    #[cfg(feature = "indexmap")]
+    dedup: IndexMap<Str<'i>, u32, RandomState>,
+    // without indexmap the insertion order is kept separately
+    #[cfg(not(feature = "indexmap"))]
+    dedup: HashMap<Str<'i>, u32, RandomState>,
+    #[cfg(not(feature = "indexmap"))]
+    order: Vec<Str<'i>>,
     length: usize,
 }
 
@@ -546,24 +2119,123 @@ impl<'i> StringData<'i> {
     #[inline]
     fn with_capacity(capacity: usize) -> Self {
         StringData {
+            base: Cow::Borrowed(&[]),
+            #[cfg(feature = "indexmap")]
             dedup: IndexMap::with_capacity_and_hasher(capacity, Default::default()),
+            #[cfg(not(feature = "indexmap"))]
+            dedup: HashMap::with_capacity_and_hasher(capacity, Default::default()),
+            #[cfg(not(feature = "indexmap"))]
+            order: Vec::with_capacity(capacity),
             length: 0,
         }
     }
-}
 
-impl<'i> Extend<Str<'i>> for StringData<'i> {
-    fn extend<T: IntoIterator<Item = Str<'i>>>(&mut self, iter: T) {
-        for string in iter {
-            if let indexmap::map::raw_entry_v1::RawEntryMut::Vacant(entry) =
-                self.dedup.raw_entry_mut_v1().from_key(&string)
-            {
-                let len = string.len();
-                entry.insert(string, self.length as _);
-                self.length += len + 1;
+    #[cfg(feature = "indexmap")]
+    fn insert(&mut self, string: &Str<'i>) -> u32 {
+        match self.dedup.raw_entry_mut_v1().from_key(string) {
+            indexmap::map::raw_entry_v1::RawEntryMut::Occupied(entry) => *entry.get(),
+            indexmap::map::raw_entry_v1::RawEntryMut::Vacant(entry) => {
+                let pos = self.length as u32;
+                entry.insert(string.clone(), pos);
+                self.length += string.len() + 1;
+                pos
             }
         }
     }
+
+    #[cfg(not(feature = "indexmap"))]
+    fn insert(&mut self, string: &Str<'i>) -> u32 {
+        if let Some(&pos) = self.dedup.get(string) {
+            return pos;
+        }
+        let pos = self.length as u32;
+        self.dedup.insert(string.clone(), pos);
+        self.order.push(string.clone());
+        self.length += string.len() + 1;
+        pos
+    }
+
+    // strings added on top of the base in the order they were inserted
+    #[cfg(feature = "indexmap")]
+    #[inline]
+    fn added(&self) -> impl Iterator<Item = &Str<'i>> {
+        self.dedup.keys()
+    }
+
+    #[cfg(not(feature = "indexmap"))]
+    #[inline]
+    fn added(&self) -> impl Iterator<Item = &Str<'i>> {
+        self.order.iter()
+    }
+
+    fn resolve<A>(&mut self, pool: &StringPool<'i, A>, original: Option<&[u32]>) -> Vec<u32> {
+        pool.strings
+            .iter()
+            .enumerate()
+            .map(
+                |(i, string)| match original.and_then(|offsets| offsets.get(i)) {
+                    Some(&pos) if self.base_str(pos) == Some(string.as_bytes()) => pos,
+                    _ => self.insert(string),
+                },
+            )
+            .collect()
+    }
+
+    fn base_str(&self, pos: u32) -> Option<&[u8]> {
+        let rest = self.base.get(pos as usize..)?;
+        let len = rest.iter().position(|&b| b == 0)?;
+        Some(&rest[..len])
+    }
+}
+
+// the parts of the original file that are not otherwise represented in a bundle
+#[derive(Debug)]
+struct Layout<'i> {
+    flags: u32,
+    timestamp: Timestamp,
+    build: u32,
+    segments: u32,
+    string_data: Cow<'i, [u8]>,
+    cnames: Vec<u32>,
+    tdb_ids: Vec<u32>,
+    resources: Vec<u32>,
+    strings: Vec<u32>,
+}
+
+impl<'i> Layout<'i> {
+    fn from_reader(reader: &BundleReader<'i>) -> byte::Result<Self> {
+        let header = &reader.header;
+        let start = header.string_data.offset as usize;
+        let string_data = reader
+            .contents
+            .get(start..start + header.string_data.count as usize)
+            .ok_or(byte::Error::BadInput {
+                err: "string data out of bounds",
+            })?;
+        let offsets = |items: ItemReader<'_, 'i, &'i str>| {
+            (0..items.count)
+                .map(|i| items.header(i))
+                .collect::<byte::Result<Vec<_>>>()
+        };
+        Ok(Self {
+            flags: header.flags,
+            timestamp: header.timestamp,
+            build: header.build,
+            segments: header.segments,
+            string_data: Cow::Borrowed(string_data),
+            cnames: offsets(reader.cnames())?,
+            tdb_ids: offsets(reader.tweakdb_ids())?,
+            resources: offsets(reader.resources())?,
+            strings: offsets(reader.strings())?,
+        })
+    }
+
+    fn relocate<'b>(self, to: &impl Relocate<'b>) -> Layout<'b> {
+        Layout {
+            string_data: to.relocate_bytes(self.string_data),
+            ..self
+        }
+    }
 }
 
 #[bitfield(u64)]
@@ -609,14 +2281,114 @@ impl<'r, 'i, Item> ItemReader<'r, 'i, Item> {
         }
     }
 
+    // the number of items in the table, including the reserved first one for definitions
+    #[inline]
+    pub fn count(&self) -> u32 {
+        self.count
+    }
+
     pub fn get(&self, index: impl Into<u32>) -> byte::Result<Item>
     where
         Item: BundleItem<'i>,
     {
-        let header_pos = self.offset + index.into() * Item::HEADER_SIZE;
-        let header: Item::Header = self.parent.contents.read_at(header_pos as _, ENDIANESS)?;
-        let pos = Item::pos(&self.parent.header, &header);
-        self.parent.contents.read_at(pos as _, Item::ctx(&header))
+        let index = index.into();
+        let read = || {
+            let header = self.header(index)?;
+            let pos = Item::pos(&self.parent.header, &header);
+            let data = self
+                .parent
+                .contents
+                .get(pos as usize..)
+                .ok_or(byte::Error::Incomplete)?;
+            Item::check_limits(data, &header, &self.parent.options)?;
+            let mut item = data.read_at(0, Item::ctx(&header))?;
+            Item::apply_options(&mut item, &header, &self.parent.options)?;
+            Ok(item)
+        };
+        let result = if self.parent.options.checked {
+            self.get_checked(index)
+        } else {
+            read()
+        };
+        #[cfg(feature = "tracing")]
+        if let Err(err) = &result {
+            tracing::warn!(index, error = ?err, "failed to decode bundle item");
+        }
+        result
+    }
+
+    // like get, but validates the index and every position taken from the file before reading,
+    // the item is decoded from a slice limited to its declared bounds
+    pub fn get_checked(&self, index: impl Into<u32>) -> byte::Result<Item>
+    where
+        Item: BundleItem<'i>,
+    {
+        let index = index.into();
+        if index >= self.count {
+            return Err(byte::Error::BadInput {
+                err: "item index out of range",
+            });
+        }
+        let contents = self.parent.contents;
+        let table_end =
+            u64::from(self.offset) + u64::from(self.count) * u64::from(Item::HEADER_SIZE);
+        if table_end > contents.len() as u64 {
+            return Err(byte::Error::BadInput {
+                err: "item table out of bounds",
+            });
+        }
+        let header = self.header(index)?;
+        Item::validate_header(&header)?;
+        let bounds = Item::bounds(&self.parent.header, &header).ok_or(byte::Error::BadInput {
+            err: "item position overflows",
+        })?;
+        let data = contents.get(bounds).ok_or(byte::Error::BadInput {
+            err: "item data out of bounds",
+        })?;
+        Item::check_limits(data, &header, &self.parent.options)?;
+        let mut item = data.read_at(0, Item::ctx(&header))?;
+        Item::apply_options(&mut item, &header, &self.parent.options)?;
+        Ok(item)
+    }
+
+    pub fn header(&self, index: impl Into<u32>) -> byte::Result<Item::Header>
+    where
+        Item: BundleItem<'i>,
+    {
+        self.parent
+            .contents
+            .read_at(self.header_pos(index)? as _, ENDIANESS)
+    }
+
+    #[inline]
+    pub(crate) fn header_pos(&self, index: impl Into<u32>) -> byte::Result<u32>
+    where
+        Item: BundleItem<'i>,
+    {
+        index
+            .into()
+            .checked_mul(Item::HEADER_SIZE)
+            .and_then(|pos| pos.checked_add(self.offset))
+            .ok_or(byte::Error::BadInput {
+                err: "item header position overflows",
+            })
+    }
+}
+
+impl<'r, 'i> ItemReader<'r, 'i, Definition<'i>> {
+    // decodes only the definitions of the given kind, the others are skipped by looking at
+    // their headers alone, the placeholder at index 0 is never returned
+    pub fn filter_kind(
+        self,
+        kind: DefinitionKind,
+    ) -> impl Iterator<Item = byte::Result<(u32, Definition<'i>)>> + 'r {
+        (1..self.count).filter_map(move |index| match self.header(index) {
+            Ok(header) if header.type_tag() == kind.tag() => {
+                Some(self.get(index).map(|def| (index, def)))
+            }
+            Ok(_) => None,
+            Err(err) => Some(Err(err)),
+        })
     }
 }
 
@@ -676,6 +2448,33 @@ pub trait BundleItem<'i>: TryRead<'i, Self::Ctx> {
 
     fn pos(parent: &Header, header: &Self::Header) -> u32;
     fn ctx(header: &Self::Header) -> Self::Ctx;
+    // the range of the file the item is allowed to occupy
+    fn bounds(parent: &Header, header: &Self::Header) -> Option<ops::Range<usize>>;
+
+    // rejects headers that decoding does not expect
+    #[inline]
+    fn validate_header(_header: &Self::Header) -> byte::Result<()> {
+        Ok(())
+    }
+
+    // rejects encoded items that exceed the parse limits before they're decoded
+    #[inline]
+    fn check_limits(
+        _bytes: &'i [u8],
+        _header: &Self::Header,
+        _options: &ReadOptions,
+    ) -> byte::Result<()> {
+        Ok(())
+    }
+
+    #[inline]
+    fn apply_options(
+        _item: &mut Self,
+        _header: &Self::Header,
+        _options: &ReadOptions,
+    ) -> byte::Result<()> {
+        Ok(())
+    }
 }
 
 impl<'i> BundleItem<'i> for &'i str {
@@ -693,6 +2492,29 @@ impl<'i> BundleItem<'i> for &'i str {
     fn ctx(_header: &Self::Header) -> Self::Ctx {
         Delimiter(0)
     }
+
+    #[inline]
+    fn apply_options(
+        item: &mut Self,
+        _header: &Self::Header,
+        options: &ReadOptions,
+    ) -> byte::Result<()> {
+        if item.len() > options.limits.max_string_length as usize {
+            return Err(byte::Error::BadInput {
+                err: "string length exceeds the limit",
+            });
+        }
+        Ok(())
+    }
+
+    #[inline]
+    fn bounds(parent: &Header, header: &Self::Header) -> Option<ops::Range<usize>> {
+        let data = &parent.string_data;
+        (*header < data.count).then(|| {
+            let start = data.offset as usize;
+            start + *header as usize..start + data.count as usize
+        })
+    }
 }
 
 impl<'i> BundleItem<'i> for Definition<'i> {
@@ -710,6 +2532,88 @@ impl<'i> BundleItem<'i> for Definition<'i> {
     fn ctx(header: &Self::Header) -> Self::Ctx {
         (byte::LE, *header)
     }
+
+    #[inline]
+    fn bounds(_parent: &Header, header: &Self::Header) -> Option<ops::Range<usize>> {
+        let start = header.offset() as usize;
+        Some(start..start.checked_add(header.size() as usize)?)
+    }
+
+    fn validate_header(header: &Self::Header) -> byte::Result<()> {
+        let parentless = matches!(
+            DefinitionKind::from_tag(header.type_tag()),
+            Some(DefinitionKind::Type | DefinitionKind::Class | DefinitionKind::Enum)
+        );
+        let source_file = header.type_tag() == DefinitionKind::SourceFile.tag();
+        if (parentless || source_file) && header.parent() != 0
+            || source_file && u32::from(header.name()) != 0
+        {
+            return Err(byte::Error::BadInput {
+                err: "unexpected parent or name in definition header",
+            });
+        }
+        Ok(())
+    }
+
+    fn check_limits(
+        bytes: &'i [u8],
+        header: &Self::Header,
+        options: &ReadOptions,
+    ) -> byte::Result<()> {
+        let max_code_size = options.limits.max_code_size;
+        if max_code_size == u32::MAX || header.type_tag() != DefinitionKind::Function.tag() {
+            return Ok(());
+        }
+        let bytes = bytes
+            .get(..header.size() as usize)
+            .ok_or(byte::Error::Incomplete)?;
+        if Function::encoded_code_size(bytes, byte::LE)? > max_code_size as usize {
+            return Err(byte::Error::BadInput {
+                err: "function code size exceeds the limit",
+            });
+        }
+        Ok(())
+    }
+
+    fn apply_options(
+        item: &mut Self,
+        header: &Self::Header,
+        options: &ReadOptions,
+    ) -> byte::Result<()> {
+        if options.strict && header.has_padding() {
+            return Err(byte::Error::BadInput {
+                err: "non-zero definition header padding",
+            });
+        }
+        if options.strict && item.has_reserved_bits() {
+            return Err(byte::Error::BadInput {
+                err: "reserved bits set in definition",
+            });
+        }
+        if !options.preserve_unknown {
+            item.clear_reserved_bits();
+        }
+        Ok(())
+    }
+}
+
+fn read_table<'i, A>(
+    _name: &'static str,
+    items: ItemReader<'_, 'i, &'i str>,
+    options: ReadOptions,
+) -> byte::Result<StringPool<'i, A>> {
+    #[cfg(feature = "tracing")]
+    let _span = tracing::debug_span!("read_table", table = _name).entered();
+    if options.round_trip || options.preserve_duplicate_strings {
+        return items.into_iter().collect();
+    }
+    let mut pool = StringPool::new();
+    for item in items {
+        pool.get_or_add(item?).map_err(|_| byte::Error::BadInput {
+            err: "too many strings in a pool",
+        })?;
+    }
+    Ok(pool)
 }
 
 #[cfg(feature = "mmap")]
@@ -717,4 +2621,33 @@ impl<'i> BundleItem<'i> for Definition<'i> {
 pub enum SaveError {
     Mmap(vmap::Error),
     Encoding(byte::Error),
+    Io(std::io::Error),
+}
+
+#[cfg(feature = "mmap")]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct SaveOptions {
+    // write to a temporary file first and rename it over the target
+    pub atomic: bool,
+    // copy the previous file to `<path>.bak` before overwriting it
+    pub backup: bool,
+}
+
+#[cfg(feature = "mmap")]
+fn with_suffix(path: &std::path::Path, suffix: &str) -> std::path::PathBuf {
+    let mut path = path.as_os_str().to_owned();
+    path.push(suffix);
+    path.into()
+}
+
+#[cfg(feature = "mmap")]
+fn write_atomic(path: &std::path::Path, bytes: &[u8]) -> std::io::Result<()> {
+    use std::io::Write;
+
+    let tmp = with_suffix(path, ".tmp");
+    let mut file = std::fs::File::create(&tmp)?;
+    file.write_all(bytes)?;
+    file.sync_all()?;
+    drop(file);
+    std::fs::rename(&tmp, path)
 }
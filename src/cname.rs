@@ -0,0 +1,90 @@
+use std::cmp::Ordering;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
+
+use crate::bundle::{PoolError, ScriptBundle};
+use crate::index::CNameIndex;
+use crate::util;
+
+// a resolved name that can outlive the bundle, equality and hashing only consider the string
+// so names from different bundles can be compared and used as map keys
+#[derive(Debug, Clone)]
+pub struct CName {
+    index: CNameIndex,
+    name: Arc<str>,
+}
+
+impl CName {
+    pub fn resolve(bundle: &ScriptBundle<'_>, index: CNameIndex) -> Option<Self> {
+        Some(Self {
+            index,
+            name: bundle.get_item(index)?.into(),
+        })
+    }
+
+    // the index in the bundle this name was resolved from
+    #[inline]
+    pub fn index(&self) -> CNameIndex {
+        self.index
+    }
+
+    #[inline]
+    pub fn as_str(&self) -> &str {
+        &self.name
+    }
+
+    // the FNV-1a 64-bit hash the game uses for names
+    #[inline]
+    pub fn name_hash(&self) -> u64 {
+        util::fnv1a64(&self.name)
+    }
+
+    // looks up the index of this name in another bundle
+    #[inline]
+    pub fn index_in(&self, bundle: &ScriptBundle<'_>) -> Option<CNameIndex> {
+        bundle.cnames().get_index(&self.name)
+    }
+
+    #[inline]
+    pub fn intern(&self, bundle: &mut ScriptBundle<'_>) -> Result<CNameIndex, PoolError> {
+        bundle.cnames_mut().get_or_add(self.name.to_string())
+    }
+}
+
+impl PartialEq for CName {
+    #[inline]
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name
+    }
+}
+
+impl Eq for CName {}
+
+impl PartialOrd for CName {
+    #[inline]
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for CName {
+    #[inline]
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.name.cmp(&other.name)
+    }
+}
+
+impl Hash for CName {
+    #[inline]
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.name.hash(state);
+    }
+}
+
+impl fmt::Display for CName {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.name)
+    }
+}
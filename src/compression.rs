@@ -0,0 +1,160 @@
+use std::borrow::Cow;
+use std::fmt;
+
+use crate::bundle::WriteableBundle;
+use crate::util;
+
+// compressed bundles start with this magic followed by a method byte
+const MAGIC: [u8; 4] = *b"REDC";
+const BUNDLE_MAGIC: [u8; 4] = *b"REDS";
+// lz4 can't compress better than this, so a larger size prefix can only come from a bad input
+#[cfg(feature = "lz4")]
+const LZ4_MAX_RATIO: usize = 255;
+// the largest decompressed size accepted by default
+pub const DEFAULT_SIZE_LIMIT: usize = 1 << 30;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    #[cfg(feature = "lz4")]
+    Lz4,
+    #[cfg(feature = "zstd")]
+    Zstd { level: i32 },
+}
+
+impl Compression {
+    fn tag(self) -> u8 {
+        match self {
+            #[cfg(feature = "lz4")]
+            Compression::Lz4 => 1,
+            #[cfg(feature = "zstd")]
+            Compression::Zstd { .. } => 2,
+        }
+    }
+
+    pub fn compress(self, bytes: &[u8]) -> Result<Vec<u8>, CompressionError> {
+        let mut out = Vec::with_capacity(bytes.len() / 2);
+        out.extend_from_slice(&MAGIC);
+        out.push(self.tag());
+        match self {
+            #[cfg(feature = "lz4")]
+            Compression::Lz4 => out.extend(lz4_flex::compress_prepend_size(bytes)),
+            #[cfg(feature = "zstd")]
+            Compression::Zstd { level } => {
+                zstd::stream::copy_encode(bytes, &mut out, level).map_err(CompressionError::Io)?;
+            }
+        }
+        Ok(out)
+    }
+}
+
+// returns the uncompressed bundle bytes, borrowing the input if it is not compressed
+#[inline]
+pub fn decompress(bytes: &[u8]) -> Result<Cow<'_, [u8]>, CompressionError> {
+    decompress_with_limit(bytes, DEFAULT_SIZE_LIMIT)
+}
+
+// same as `decompress`, but fails with an error once the output would exceed `limit` bytes
+pub fn decompress_with_limit(
+    bytes: &[u8],
+    limit: usize,
+) -> Result<Cow<'_, [u8]>, CompressionError> {
+    match bytes {
+        [a, b, c, d, method, payload @ ..] if [*a, *b, *c, *d] == MAGIC => match method {
+            #[cfg(feature = "lz4")]
+            1 => {
+                let [a, b, c, d, block @ ..] = payload else {
+                    return Err(CompressionError::UnknownFormat);
+                };
+                let size = u32::from_le_bytes([*a, *b, *c, *d]) as usize;
+                if size > limit || size > block.len().saturating_mul(LZ4_MAX_RATIO) {
+                    return Err(CompressionError::TooLarge(size));
+                }
+                lz4_flex::decompress(block, size)
+                    .map(Cow::Owned)
+                    .map_err(CompressionError::Lz4)
+            }
+            #[cfg(feature = "zstd")]
+            2 => {
+                use std::io::Read;
+
+                let decoder = zstd::stream::Decoder::new(payload).map_err(CompressionError::Io)?;
+                let mut out = vec![];
+                decoder
+                    .take(limit as u64 + 1)
+                    .read_to_end(&mut out)
+                    .map_err(CompressionError::Io)?;
+                if out.len() > limit {
+                    return Err(CompressionError::TooLarge(out.len()));
+                }
+                Ok(Cow::Owned(out))
+            }
+            other => Err(CompressionError::UnsupportedMethod(*other)),
+        },
+        [a, b, c, d, ..] if [*a, *b, *c, *d] == BUNDLE_MAGIC => Ok(Cow::Borrowed(bytes)),
+        _ => Err(CompressionError::UnknownFormat),
+    }
+}
+
+// decompresses the input of the bundle readers, returns nothing if the input is not compressed
+pub(crate) fn decompress_input(bytes: &[u8]) -> byte::Result<Option<Vec<u8>>> {
+    if !bytes.starts_with(&MAGIC) {
+        return Ok(None);
+    }
+    match decompress(bytes) {
+        Ok(bytes) => Ok(Some(bytes.into_owned())),
+        Err(_) => Err(byte::Error::BadInput {
+            err: "failed to decompress the bundle",
+        }),
+    }
+}
+
+impl WriteableBundle<'_> {
+    pub fn to_compressed_bytes(
+        &self,
+        compression: Compression,
+    ) -> Result<Vec<u8>, CompressionError> {
+        let bytes = self.to_bytes().map_err(CompressionError::Encoding)?;
+        compression.compress(&bytes)
+    }
+
+    pub fn save_compressed(
+        &self,
+        path: impl AsRef<std::path::Path>,
+        compression: Compression,
+    ) -> Result<(), CompressionError> {
+        let bytes = self.to_compressed_bytes(compression)?;
+        std::fs::write(util::extended_path(path.as_ref()), bytes).map_err(CompressionError::Io)
+    }
+}
+
+#[derive(Debug)]
+pub enum CompressionError {
+    UnknownFormat,
+    UnsupportedMethod(u8),
+    // the decompressed size exceeds the limit
+    TooLarge(usize),
+    Encoding(byte::Error),
+    Io(std::io::Error),
+    #[cfg(feature = "lz4")]
+    Lz4(lz4_flex::block::DecompressError),
+}
+
+impl fmt::Display for CompressionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CompressionError::UnknownFormat => f.write_str("not a script bundle"),
+            CompressionError::UnsupportedMethod(method) => {
+                write!(f, "unsupported compression method {method}")
+            }
+            CompressionError::TooLarge(size) => {
+                write!(f, "decompressed size of {size} bytes exceeds the limit")
+            }
+            CompressionError::Encoding(err) => write!(f, "encoding error: {err:?}"),
+            CompressionError::Io(err) => write!(f, "io error: {err}"),
+            #[cfg(feature = "lz4")]
+            CompressionError::Lz4(err) => write!(f, "lz4 error: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for CompressionError {}
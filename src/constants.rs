@@ -0,0 +1,177 @@
+use std::io;
+
+use crate::bundle::ScriptBundle;
+use crate::definition::{Definition, Function};
+use crate::index::FunctionIndex;
+use crate::instr::{self, ByteOffset, Constant, Instr};
+use crate::symbols::{self, csv_field};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ConstReplacement {
+    pub function: FunctionIndex,
+    // the offset of the instruction in the code before the replacement
+    pub offset: ByteOffset,
+}
+
+impl<'i> ScriptBundle<'i> {
+    // replaces every instruction pushing `from` with one pushing `to` in the functions accepted by
+    // the filter, jumps are adjusted when the size of the instruction changes, nothing is
+    // changed if one of the bodies can't be decoded or a jump would get out of range
+    pub fn replace_const(
+        &mut self,
+        from: Constant,
+        to: Constant,
+        filter: impl Fn(FunctionIndex, &Function<'i>) -> bool,
+    ) -> byte::Result<Vec<ConstReplacement>> {
+        let mut replacements = vec![];
+        let mut changed = vec![];
+        for (index, fun) in self.functions() {
+            if !filter(index, fun) {
+                continue;
+            }
+            let original = fun.body().code_owned()?;
+            let mut code = original.clone();
+            let mut offset = 0;
+            let mut found = false;
+            for instr in &mut code {
+                let size = instr.size();
+                if instr.constant() == Some(from) {
+                    *instr = to.to_instr();
+                    replacements.push(ConstReplacement {
+                        function: index,
+                        offset: ByteOffset::new(offset),
+                    });
+                    found = true;
+                }
+                offset += u32::from(size);
+            }
+            if found {
+                let code = instr::retarget_jumps(&original, code).ok_or(byte::Error::BadInput {
+                    err: "jump offset out of range",
+                })?;
+                changed.push((index, code));
+            }
+        }
+
+        for (index, code) in changed {
+            self.modify_definition(index.into(), |def| {
+                if let Definition::Function(fun) = def {
+                    fun.set_code(code);
+                }
+            });
+        }
+        Ok(replacements)
+    }
+}
+
+// every numeric constant in the code of a bundle, meant for spreadsheets and other tools
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ConstantTable {
+    pub constants: Vec<ConstantEntry>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ConstantEntry {
+    pub function: u32,
+    // fully-qualified name of the function, e.g. `Class.Function`
+    pub function_name: String,
+    pub offset: u32,
+    // the name of the instruction, e.g. `F32Const`
+    pub instr: String,
+    pub value: String,
+    // the innermost call the constant is an argument of, if any
+    pub call: Option<String>,
+}
+
+impl ConstantTable {
+    pub fn write_csv(&self, mut writer: impl io::Write) -> io::Result<()> {
+        writeln!(writer, "function,name,offset,instr,value,call")?;
+        for entry in &self.constants {
+            writeln!(
+                writer,
+                "{},{},{},{},{},{}",
+                entry.function,
+                csv_field(&entry.function_name),
+                entry.offset,
+                entry.instr,
+                entry.value,
+                csv_field(entry.call.as_deref().unwrap_or_default())
+            )?;
+        }
+        Ok(())
+    }
+
+    #[cfg(all(feature = "serde", feature = "serde_json"))]
+    pub fn write_json(&self, writer: impl io::Write) -> serde_json::Result<()> {
+        serde_json::to_writer(writer, self)
+    }
+}
+
+impl ScriptBundle<'_> {
+    // lists the integer and floating point constants of every function, bodies that can't be
+    // decoded are skipped
+    pub fn constant_table(&self) -> ConstantTable {
+        let mut constants = vec![];
+        for (index, fun) in self.functions() {
+            let Ok(code) = fun.body().code_owned() else {
+                continue;
+            };
+            let function_name = symbols::qualified_name(self, index.into()).unwrap_or_default();
+            // calls that have not ended yet, with the offsets they end at
+            let mut calls: Vec<(ByteOffset, Option<&str>)> = vec![];
+            let mut offset = 0;
+            for instr in &code {
+                let start = ByteOffset::new(offset);
+                calls.retain(|&(end, _)| end > start);
+                match instr {
+                    Instr::InvokeStatic { exit, function, .. } => {
+                        let name = self
+                            .get_item(*function)
+                            .and_then(|fun| self.get_item(fun.name()));
+                        calls.extend(start.jump(exit.target()).map(|end| (end, name)));
+                    }
+                    Instr::InvokeVirtual { exit, function, .. } => {
+                        let name = self.get_item(*function);
+                        calls.extend(start.jump(exit.target()).map(|end| (end, name)));
+                    }
+                    _ => {}
+                }
+                if let Some(value) = instr.constant().and_then(numeric_value) {
+                    constants.push(ConstantEntry {
+                        function: index.into(),
+                        function_name: function_name.clone(),
+                        offset,
+                        instr: instr.name().to_owned(),
+                        value,
+                        call: calls.last().and_then(|&(_, name)| name).map(str::to_owned),
+                    });
+                }
+                offset += u32::from(instr.size());
+            }
+        }
+        ConstantTable { constants }
+    }
+}
+
+fn numeric_value(constant: Constant) -> Option<String> {
+    let value = match constant {
+        Constant::I8(v) => v.to_string(),
+        Constant::I16(v) => v.to_string(),
+        Constant::I32(v) => v.to_string(),
+        Constant::I64(v) => v.to_string(),
+        Constant::U8(v) => v.to_string(),
+        Constant::U16(v) => v.to_string(),
+        Constant::U32(v) => v.to_string(),
+        Constant::U64(v) => v.to_string(),
+        Constant::F32(v) => v.to_string(),
+        Constant::F64(v) => v.to_string(),
+        Constant::Bool(_)
+        | Constant::CName(_)
+        | Constant::String(_)
+        | Constant::TweakDbId(_)
+        | Constant::Resource(_) => return None,
+    };
+    Some(value)
+}
@@ -0,0 +1,230 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::io;
+
+use crate::bundle::ScriptBundle;
+use crate::definition::{Definition, Function};
+use crate::index::FunctionIndex;
+use crate::instr::{self, Instr, InstrIndex, Jump, Offset, OffsetMap};
+use crate::symbols::{self, csv_field};
+
+// the blocks instrumented by `instrument_coverage`, it's needed to make sense of the logs
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CoverageMap {
+    pub functions: Vec<FunctionBlocks>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FunctionBlocks {
+    pub function: u32,
+    pub name: String,
+    // the offsets of the blocks in the code before it was instrumented, by block number
+    pub blocks: Vec<u32>,
+}
+
+impl CoverageMap {
+    // a report with no blocks hit yet
+    pub fn report(&self) -> CoverageReport {
+        let functions = self
+            .functions
+            .iter()
+            .map(|fun| FunctionCoverage {
+                function: fun.function,
+                name: fun.name.clone(),
+                blocks: fun.blocks.clone(),
+                hits: vec![0; fun.blocks.len()],
+            })
+            .collect();
+        CoverageReport { functions }
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CoverageReport {
+    pub functions: Vec<FunctionCoverage>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FunctionCoverage {
+    pub function: u32,
+    pub name: String,
+    pub blocks: Vec<u32>,
+    // the number of times each block was entered
+    pub hits: Vec<u64>,
+}
+
+impl FunctionCoverage {
+    #[inline]
+    pub fn covered(&self) -> usize {
+        self.hits.iter().filter(|&&hits| hits > 0).count()
+    }
+
+    // the offsets of the blocks that were never entered
+    pub fn uncovered(&self) -> impl Iterator<Item = u32> + '_ {
+        self.blocks
+            .iter()
+            .zip(&self.hits)
+            .filter(|(_, &hits)| hits == 0)
+            .map(|(&offset, _)| offset)
+    }
+}
+
+impl CoverageReport {
+    // adds the hits of a log, lines ending with a function index and a block number are taken as
+    // hits, whatever precedes them is ignored and so are the other lines, e.g. the ones of other
+    // mods, returns the number of hits recognized
+    pub fn merge_log(&mut self, log: impl io::BufRead) -> io::Result<usize> {
+        let positions = self
+            .functions
+            .iter()
+            .enumerate()
+            .map(|(i, fun)| (fun.function, i))
+            .collect::<BTreeMap<_, _>>();
+
+        let mut count = 0;
+        for line in log.lines() {
+            let line = line?;
+            let mut numbers = line
+                .rsplit(|c: char| c.is_whitespace() || c == ',' || c == ':')
+                .filter(|part| !part.is_empty())
+                .map(str::parse::<u32>);
+            let (Some(Ok(block)), Some(Ok(function))) = (numbers.next(), numbers.next()) else {
+                continue;
+            };
+            let Some(&pos) = positions.get(&function) else {
+                continue;
+            };
+            if let Some(hits) = self.functions[pos].hits.get_mut(block as usize) {
+                *hits += 1;
+                count += 1;
+            }
+        }
+        Ok(count)
+    }
+
+    pub fn write_csv(&self, mut writer: impl io::Write) -> io::Result<()> {
+        writeln!(writer, "function,name,covered,blocks")?;
+        for fun in &self.functions {
+            writeln!(
+                writer,
+                "{},{},{},{}",
+                fun.function,
+                csv_field(&fun.name),
+                fun.covered(),
+                fun.blocks.len()
+            )?;
+        }
+        Ok(())
+    }
+
+    #[cfg(all(feature = "serde", feature = "serde_json"))]
+    pub fn write_json(&self, writer: impl io::Write) -> serde_json::Result<()> {
+        serde_json::to_writer(writer, self)
+    }
+}
+
+impl<'i> ScriptBundle<'i> {
+    // inserts a call to `logger` at the entry of every basic block of the functions accepted by
+    // the filter, the logger is expected to be a static function taking the function index and
+    // the block number as two Uint32 parameters and to print them at the end of a line, nothing
+    // is changed if one of the bodies can't be decoded or a jump would get out of range
+    pub fn instrument_coverage(
+        &mut self,
+        logger: FunctionIndex,
+        filter: impl Fn(FunctionIndex, &Function<'i>) -> bool,
+    ) -> byte::Result<CoverageMap> {
+        let mut functions = vec![];
+        let mut changed = vec![];
+        for (index, fun) in self.functions() {
+            if index == logger || fun.body().is_empty() || !filter(index, fun) {
+                continue;
+            }
+            let code = fun.body().code_owned()?;
+            let offsets = OffsetMap::new(&code);
+            let entries = block_entries(&code);
+            let insertions = entries
+                .iter()
+                .enumerate()
+                .map(|(block, &pos)| (pos, probe(logger, index.into(), block as u32)))
+                .collect();
+            let code = instr::insert_code(&code, insertions).ok_or(byte::Error::BadInput {
+                err: "jump offset out of range",
+            })?;
+
+            functions.push(FunctionBlocks {
+                function: index.into(),
+                name: symbols::qualified_name(self, index.into()).unwrap_or_default(),
+                blocks: entries
+                    .iter()
+                    .filter_map(|&pos| offsets.offset(InstrIndex::new(pos as u32)))
+                    .map(u32::from)
+                    .collect(),
+            });
+            changed.push((index, code));
+        }
+
+        for (index, code) in changed {
+            self.modify_definition(index.into(), |def| {
+                if let Definition::Function(fun) = def {
+                    fun.set_code(code);
+                }
+            });
+        }
+        Ok(CoverageMap { functions })
+    }
+}
+
+// positions of the statements that start basic blocks, blocks inside of expressions, i.e. the
+// branches of conditionals, aren't recognized, neither are the labels of switches since the
+// instructions can't be inserted between them
+fn block_entries(code: &[Instr]) -> Vec<usize> {
+    let offsets = OffsetMap::new(code);
+    let target = |i: usize, offset: Offset| {
+        offsets
+            .resolve_jump(InstrIndex::new(i as u32), offset)
+            .map(usize::from)
+    };
+
+    let mut entries = BTreeSet::from([0]);
+    for (i, instr) in code.iter().enumerate() {
+        match instr {
+            Instr::Jump(jump) => entries.extend(target(i, jump.target())),
+            Instr::JumpIfFalse(jump) => {
+                entries.extend(target(i, jump.target()));
+                entries.extend(instr::expr_end(code, i + 1));
+            }
+            Instr::SwitchLabel(label) => entries.extend(target(i, label.body())),
+            Instr::SwitchDefault => {
+                entries.insert(i + 1);
+            }
+            _ => {}
+        }
+    }
+    entries
+        .into_iter()
+        .filter(|&i| {
+            code.get(i)
+                .is_some_and(|instr| !matches!(instr, Instr::SwitchLabel(_) | Instr::SwitchDefault))
+        })
+        .collect()
+}
+
+fn probe(logger: FunctionIndex, function: u32, block: u32) -> Vec<Instr> {
+    let args = [
+        Instr::U32Const(function),
+        Instr::U32Const(block),
+        Instr::ParamEnd,
+    ];
+    let call = Instr::InvokeStatic {
+        exit: Jump::new(Offset::from(0)),
+        line: 0,
+        function: logger,
+        flags: 0,
+    };
+    let size = call.size() + args.iter().map(Instr::size).sum::<u16>();
+    let call = call.map_offsets(|_| Offset::from(size as i16));
+    [call].into_iter().chain(args).collect()
+}
@@ -1,16 +1,18 @@
 use std::borrow::Cow;
-use std::{iter, mem};
+use std::collections::BTreeMap;
+use std::{fmt, iter, mem};
 
 use bitfield_struct::bitfield;
 use byte::ctx::Endianess;
 use byte::{BytesExt, Measure, TryRead, TryWrite};
 
+use crate::bundle::{EnumsByName, ScriptBundle};
 use crate::index::{
     self, CNameIndex, ClassIndex, EnumIndex, EnumValueIndex, FieldIndex, FunctionIndex, LocalIndex,
     NzPoolIndex, ParameterIndex, SourceFileIndex, TypeIndex,
 };
-use crate::instr::Instr;
-use crate::util::{self, FlagDependent, OptionalIndex, Prefixed};
+use crate::instr::{self, ByteOffset, Instr, InstrIndex, Label, Offset, OffsetMap};
+use crate::util::{self, ChildList, FlagDependent, OptionalIndex, Owned, Prefixed, Relocate};
 use crate::{Str, ENDIANESS};
 
 #[derive(Debug, Default, Clone, Copy, TryRead, TryWrite, Measure)]
@@ -35,9 +37,45 @@ impl DefinitionHeader {
         }
     }
 
-    pub(crate) fn offset(&self) -> u32 {
+    #[inline]
+    pub fn name(&self) -> CNameIndex {
+        self.name
+    }
+
+    #[inline]
+    pub fn parent(&self) -> u32 {
+        self.parent
+    }
+
+    #[inline]
+    pub fn type_tag(&self) -> u16 {
+        self.type_tag
+    }
+
+    #[inline]
+    pub fn offset(&self) -> u32 {
         self.offset
     }
+
+    #[inline]
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+
+    #[inline]
+    pub(crate) fn has_padding(&self) -> bool {
+        self.padding != [0; 2]
+    }
+
+    #[inline]
+    pub fn padding(&self) -> [u8; 2] {
+        self.padding
+    }
+
+    #[inline]
+    pub(crate) fn with_padding(self, padding: [u8; 2]) -> Self {
+        Self { padding, ..self }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -54,22 +92,109 @@ pub enum Definition<'i> {
     SourceFile(Box<SourceFile<'i>>),
 }
 
+// whether the value can be stored in an integer of the given size, signed or unsigned
+fn value_fits(value: i64, size: u8) -> bool {
+    match size {
+        0 => false,
+        1..=7 => {
+            let bits = u32::from(size) * 8;
+            value >= -(1 << (bits - 1)) && value < 1 << bits
+        }
+        _ => true,
+    }
+}
+
+fn map_all<A>(indices: &mut [NzPoolIndex<A>], mut f: impl FnMut(u32) -> u32) {
+    for index in indices {
+        *index = index.map(&mut f);
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum PoolKind {
+    CName,
+    TweakDbId,
+    Resource,
+    String,
+}
+
+// the type tag stored in definition headers
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DefinitionKind {
+    Type,
+    Class,
+    EnumMember,
+    Enum,
+    Bitfield,
+    Function,
+    Parameter,
+    Local,
+    Field,
+    SourceFile,
+}
+
+impl DefinitionKind {
+    pub(crate) const ALL: [Self; 10] = [
+        Self::Type,
+        Self::Class,
+        Self::EnumMember,
+        Self::Enum,
+        Self::Bitfield,
+        Self::Function,
+        Self::Parameter,
+        Self::Local,
+        Self::Field,
+        Self::SourceFile,
+    ];
+
+    #[inline]
+    pub fn from_tag(tag: u16) -> Option<Self> {
+        Self::ALL.get(usize::from(tag)).copied()
+    }
+
+    #[inline]
+    pub fn tag(self) -> u16 {
+        self as u16
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Type => "Type",
+            Self::Class => "Class",
+            Self::EnumMember => "EnumMember",
+            Self::Enum => "Enum",
+            Self::Bitfield => "Bitfield",
+            Self::Function => "Function",
+            Self::Parameter => "Parameter",
+            Self::Local => "Local",
+            Self::Field => "Field",
+            Self::SourceFile => "SourceFile",
+        }
+    }
+}
+
 impl Definition<'_> {
     pub(crate) const UNDEFINED: Self =
         Definition::Type(Type::new(CNameIndex::UNDEFINED, TypeKind::Primitive));
 
-    fn tag(&self) -> u16 {
+    #[inline]
+    pub(crate) fn tag(&self) -> u16 {
+        self.kind().tag()
+    }
+
+    pub fn kind(&self) -> DefinitionKind {
         match self {
-            Definition::Type(_) => 0,
-            Definition::Class(_) => 1,
-            Definition::EnumMember(_) => 2,
-            Definition::Enum(_) => 3,
-            Definition::Bitfield => 4,
-            Definition::Function(_) => 5,
-            Definition::Parameter(_) => 6,
-            Definition::Local(_) => 7,
-            Definition::Field(_) => 8,
-            Definition::SourceFile(_) => 9,
+            Definition::Type(_) => DefinitionKind::Type,
+            Definition::Class(_) => DefinitionKind::Class,
+            Definition::EnumMember(_) => DefinitionKind::EnumMember,
+            Definition::Enum(_) => DefinitionKind::Enum,
+            Definition::Bitfield => DefinitionKind::Bitfield,
+            Definition::Function(_) => DefinitionKind::Function,
+            Definition::Parameter(_) => DefinitionKind::Parameter,
+            Definition::Local(_) => DefinitionKind::Local,
+            Definition::Field(_) => DefinitionKind::Field,
+            Definition::SourceFile(_) => DefinitionKind::SourceFile,
         }
     }
 
@@ -88,7 +213,7 @@ impl Definition<'_> {
         }
     }
 
-    fn parent(&self) -> u32 {
+    pub(crate) fn parent(&self) -> u32 {
         match self {
             Definition::Type(_)
             | Definition::Class(_)
@@ -103,18 +228,220 @@ impl Definition<'_> {
         }
     }
 
+    // every pool index embedded in the definition, including its name and the constants in code,
+    // decoding stops at the first invalid instruction
+    pub fn referenced_strings(&self) -> impl Iterator<Item = (PoolKind, u32)> {
+        let mut refs = vec![];
+        if !matches!(self, Definition::Bitfield) {
+            refs.push((PoolKind::CName, self.name().into()));
+        }
+        if let Definition::Function(fun) = self {
+            if let Some(operator) = fun.operator {
+                refs.push((PoolKind::CName, operator.into()));
+            }
+            for instr in fun.body.code_iter().map_while(Result::ok) {
+                match instr {
+                    Instr::CNameConst(index)
+                    | Instr::InvokeVirtual {
+                        function: index, ..
+                    } => {
+                        refs.push((PoolKind::CName, index.into()));
+                    }
+                    Instr::StringConst(index) => refs.push((PoolKind::String, index.into())),
+                    Instr::TweakDbIdConst(index) => refs.push((PoolKind::TweakDbId, index.into())),
+                    Instr::ResourceConst(index) => refs.push((PoolKind::Resource, index.into())),
+                    _ => {}
+                }
+            }
+        }
+        refs.into_iter()
+    }
+
+    // every definition index embedded in the definition, including the ones in code
+    pub(crate) fn referenced_definitions(&self) -> Vec<u32> {
+        let mut def = self.clone();
+        let mut refs = vec![];
+        if def.decode_body().is_ok() {
+            def.map_definitions(|index| {
+                refs.push(index);
+                index
+            });
+        }
+        refs
+    }
+
+    pub(crate) fn set_name(&mut self, name: CNameIndex) {
+        match self {
+            Definition::Type(t) => t.name = name,
+            Definition::Class(c) => c.name = name,
+            Definition::EnumMember(v) => v.name = name,
+            Definition::Enum(e) => e.name = name,
+            Definition::Function(f) => f.name = name,
+            Definition::Parameter(p) => p.name = name,
+            Definition::Local(l) => l.name = name,
+            Definition::Field(f) => f.name = name,
+            Definition::SourceFile(f) => f.name = name,
+            Definition::Bitfield => {}
+        }
+    }
+
+    // returns false if the definition has no parent reference or the index is zero
+    pub(crate) fn set_parent(&mut self, parent: u32) -> bool {
+        match self {
+            Definition::EnumMember(v) => NzPoolIndex::new(parent).map(|i| v.enum_ = i),
+            Definition::Function(f) => NzPoolIndex::new(parent).map(|i| f.class = Some(i)),
+            Definition::Parameter(p) => NzPoolIndex::new(parent).map(|i| p.function = i),
+            Definition::Local(l) => NzPoolIndex::new(parent).map(|i| l.function = i),
+            Definition::Field(f) => NzPoolIndex::new(parent).map(|i| f.class = i),
+            _ => None,
+        }
+        .is_some()
+    }
+
+    pub(crate) fn shrink_to_fit(&mut self) {
+        match self {
+            Definition::Class(c) => {
+                c.methods.shrink_to_fit();
+                c.fields.shrink_to_fit();
+                c.overrides.shrink_to_fit();
+            }
+            Definition::Enum(e) => e.values.shrink_to_fit(),
+            Definition::Function(f) => {
+                f.parameters.shrink_to_fit();
+                f.locals.shrink_to_fit();
+                if let FunctionBody::Code(code) = &mut f.body {
+                    code.shrink_to_fit();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // replaces a raw function body with decoded instructions
+    pub(crate) fn decode_body(&mut self) -> byte::Result<()> {
+        if let Definition::Function(f) = self {
+            if let FunctionBody::Raw { .. } = f.body {
+                f.body = FunctionBody::Code(f.body.code_owned()?);
+            }
+        }
+        Ok(())
+    }
+
+    // rewrites every definition index, raw function bodies are left untouched
+    pub(crate) fn map_definitions(&mut self, mut f: impl FnMut(u32) -> u32) {
+        match self {
+            Definition::Type(t) => match &mut t.kind {
+                TypeKind::Box(inner)
+                | TypeKind::WeakRef(inner)
+                | TypeKind::Array(inner)
+                | TypeKind::StaticArray {
+                    element_type: inner,
+                    ..
+                }
+                | TypeKind::ScriptRef(inner) => *inner = inner.map(f),
+                TypeKind::Primitive | TypeKind::Class => {}
+            },
+            Definition::Class(c) => {
+                c.base = c.base.map(|base| base.map(&mut f));
+                map_all(&mut c.methods, &mut f);
+                map_all(&mut c.fields, &mut f);
+                map_all(&mut c.overrides, &mut f);
+            }
+            Definition::EnumMember(v) => v.enum_ = v.enum_.map(f),
+            Definition::Enum(e) => map_all(&mut e.values, &mut f),
+            Definition::Function(fun) => {
+                map_all(&mut fun.parameters, &mut f);
+                map_all(&mut fun.locals, &mut f);
+                fun.class = fun.class.map(|class| class.map(&mut f));
+                fun.source = fun.source.map(|source| SourceReference {
+                    file: source.file.map(&mut f),
+                    ..source
+                });
+                fun.return_type = fun.return_type.map(|typ| typ.map(&mut f));
+                fun.base_method = fun.base_method.map(|base| base.map(&mut f));
+                if let FunctionBody::Code(code) = &mut fun.body {
+                    for instr in code {
+                        instr.map_definitions(&mut f);
+                    }
+                }
+            }
+            Definition::Parameter(p) => {
+                p.function = p.function.map(&mut f);
+                p.type_ = p.type_.map(f);
+            }
+            Definition::Local(l) => {
+                l.function = l.function.map(&mut f);
+                l.type_ = l.type_.map(f);
+            }
+            Definition::Field(field) => {
+                field.class = field.class.map(&mut f);
+                field.type_ = field.type_.map(f);
+            }
+            Definition::SourceFile(_) | Definition::Bitfield => {}
+        }
+    }
+
+    pub(crate) fn has_reserved_bits(&self) -> bool {
+        match self {
+            Definition::Class(c) => c.flags.into_bits() & ClassFlags::RESERVED != 0,
+            Definition::Function(f) => {
+                f.flags.into_bits() & FunctionFlags::RESERVED != 0 || f.body.has_padding()
+            }
+            Definition::Parameter(p) => p.flags.into_bits() & ParameterFlags::RESERVED != 0,
+            Definition::Local(l) => l.flags.into_bits() & LocalFlags::RESERVED != 0,
+            Definition::Field(f) => f.flags.into_bits() & FieldFlags::RESERVED != 0,
+            Definition::Type(_)
+            | Definition::EnumMember(_)
+            | Definition::Enum(_)
+            | Definition::Bitfield
+            | Definition::SourceFile(_) => false,
+        }
+    }
+
+    pub(crate) fn clear_reserved_bits(&mut self) {
+        match self {
+            Definition::Class(c) => {
+                c.flags = ClassFlags::from_bits(c.flags.into_bits() & !ClassFlags::RESERVED);
+            }
+            Definition::Function(f) => {
+                f.flags = FunctionFlags::from_bits(f.flags.into_bits() & !FunctionFlags::RESERVED);
+                f.body.clear_padding();
+            }
+            Definition::Parameter(p) => {
+                p.flags =
+                    ParameterFlags::from_bits(p.flags.into_bits() & !ParameterFlags::RESERVED);
+            }
+            Definition::Local(l) => {
+                l.flags = LocalFlags::from_bits(l.flags.into_bits() & !LocalFlags::RESERVED);
+            }
+            Definition::Field(f) => {
+                f.flags = FieldFlags::from_bits(f.flags.into_bits() & !FieldFlags::RESERVED);
+            }
+            Definition::Type(_)
+            | Definition::EnumMember(_)
+            | Definition::Enum(_)
+            | Definition::Bitfield
+            | Definition::SourceFile(_) => {}
+        }
+    }
+
+    #[inline]
     pub fn into_owned(self) -> Definition<'static> {
+        self.relocate(&Owned)
+    }
+
+    pub(crate) fn relocate<'b>(self, to: &impl Relocate<'b>) -> Definition<'b> {
         match self {
             Definition::Type(t) => Definition::Type(t),
-            Definition::Class(c) => Definition::Class(c.clone()),
+            Definition::Class(c) => Definition::Class(c),
             Definition::EnumMember(v) => Definition::EnumMember(v),
-            Definition::Enum(e) => Definition::Enum(e.clone()),
+            Definition::Enum(e) => Definition::Enum(e),
             Definition::Bitfield => Definition::Bitfield,
-            Definition::Function(f) => Definition::Function(Box::new(f.into_owned())),
+            Definition::Function(f) => Definition::Function(Box::new(f.relocate(to))),
             Definition::Parameter(p) => Definition::Parameter(p),
             Definition::Local(l) => Definition::Local(l),
-            Definition::Field(f) => Definition::Field(Box::new(f.into_owned())),
-            Definition::SourceFile(f) => Definition::SourceFile(Box::new(f.into_owned())),
+            Definition::Field(f) => Definition::Field(Box::new(f.relocate(to))),
+            Definition::SourceFile(f) => Definition::SourceFile(Box::new(f.relocate(to))),
         }
     }
 }
@@ -132,7 +459,7 @@ impl<'i, Ctx: Endianess> TryRead<'i, (Ctx, DefinitionHeader)> for Definition<'i>
             ..
         } = header;
 
-        let in_bytes = &bytes[..size as _];
+        let in_bytes = bytes.get(..size as usize).ok_or(byte::Error::Incomplete)?;
         let offset = &mut 0;
 
         const BAD_INDEX: byte::Error = byte::Error::BadInput {
@@ -232,6 +559,86 @@ impl<Ctx: Endianess> TryWrite<Ctx> for Definition<'_> {
     }
 }
 
+// a field of a definition as it's encoded, used to annotate dumps
+pub(crate) struct EncodedField<'a> {
+    pub name: &'static str,
+    pub size: usize,
+    pub value: &'a dyn fmt::Debug,
+}
+
+// lists the fields of a definition in the order they're written, they're measured with the same
+// contexts as the ones they're encoded with, fields skipped by their flags are left out
+macro_rules! encoded_fields {
+    ($def:expr; $($field:ident $(if $cond:expr)? => $ctx:expr),* $(,)?) => {{
+        let def = $def;
+        let mut fields = vec![];
+        $(
+            if true $(&& $cond)? {
+                fields.push(EncodedField {
+                    name: stringify!($field),
+                    size: Measure::measure(&def.$field, $ctx),
+                    value: &def.$field,
+                });
+            }
+        )*
+        fields
+    }};
+}
+
+impl Definition<'_> {
+    pub(crate) fn encoded_fields(&self) -> Vec<EncodedField<'_>> {
+        let ctx = ENDIANESS;
+        match self {
+            Definition::Type(t) => encoded_fields![t; kind => ctx],
+            Definition::Class(c) => encoded_fields![c.as_ref();
+                visiblity => ctx,
+                flags => ctx,
+                base => OptionalIndex(ctx),
+                methods if c.flags.has_functions() => Prefixed(ctx),
+                fields if c.flags.has_fields() => Prefixed(ctx),
+                overrides if c.flags.has_overrides() => Prefixed(ctx),
+            ],
+            Definition::EnumMember(v) => encoded_fields![v; value => ctx],
+            Definition::Enum(e) => encoded_fields![e.as_ref();
+                visiblity => ctx,
+                size => ctx,
+                values => Prefixed(ctx),
+                is_native => ctx,
+            ],
+            Definition::Bitfield => vec![],
+            Definition::Function(f) => encoded_fields![f.as_ref();
+                visibility => ctx,
+                flags => ctx,
+                source if !f.flags.is_native() => FlagDependent(ctx),
+                return_type if f.flags.has_return_value() => FlagDependent(ctx),
+                is_const_return if f.flags.has_return_value() => ctx,
+                base_method if f.flags.has_base_method() => FlagDependent(ctx),
+                parameters if f.flags.has_parameters() => Prefixed(ctx),
+                locals if f.flags.has_locals() => Prefixed(ctx),
+                operator if f.flags.is_operator() => FlagDependent(ctx),
+                cast_cost if f.flags.is_cast() => ctx,
+                body => (ctx, f.flags),
+            ],
+            Definition::Parameter(p) => encoded_fields![p; type_ => ctx, flags => ctx],
+            Definition::Local(l) => encoded_fields![l; type_ => ctx, flags => ctx],
+            Definition::Field(f) => encoded_fields![f.as_ref();
+                visibility => ctx,
+                type_ => ctx,
+                flags => ctx,
+                hint if f.flags.has_hint() => FlagDependent(Prefixed(ctx)),
+                attributes => Prefixed(ctx),
+                defaults => Prefixed(ctx),
+            ],
+            Definition::SourceFile(f) => encoded_fields![f.as_ref();
+                index => ctx,
+                path_hash => ctx,
+                code_crc => ctx,
+                path => Prefixed(ctx),
+            ],
+        }
+    }
+}
+
 impl<Ctx: Copy> Measure<Ctx> for Definition<'_> {
     fn measure(&self, ctx: Ctx) -> usize {
         match self {
@@ -271,6 +678,51 @@ impl Type {
     pub fn kind(&self) -> &TypeKind {
         &self.kind
     }
+
+    // the type wrapped by a reference or an array
+    pub fn element_type(&self) -> Option<TypeIndex> {
+        match self.kind {
+            TypeKind::Primitive | TypeKind::Class => None,
+            TypeKind::Box(inner)
+            | TypeKind::WeakRef(inner)
+            | TypeKind::Array(inner)
+            | TypeKind::ScriptRef(inner)
+            | TypeKind::StaticArray {
+                element_type: inner,
+                ..
+            } => Some(inner),
+        }
+    }
+
+    #[inline]
+    pub fn static_array_size(&self) -> Option<u32> {
+        match self.kind {
+            TypeKind::StaticArray { size, .. } => Some(size),
+            _ => None,
+        }
+    }
+
+    #[inline]
+    pub fn is_ref_like(&self) -> bool {
+        matches!(
+            self.kind,
+            TypeKind::Box(_) | TypeKind::WeakRef(_) | TypeKind::ScriptRef(_)
+        )
+    }
+
+    // this type followed by each of the types nested in it, e.g. `array<ref<A>>`, `ref<A>`, `A`
+    pub fn nesting<'a>(&'a self, bundle: &'a ScriptBundle<'_>) -> impl Iterator<Item = &'a Type> {
+        // a chain longer than the number of definitions can only come from a cyclic bundle
+        iter::successors(Some(self), |typ| bundle.get_item(typ.element_type()?))
+            .take(bundle.definition_count() as usize + 1)
+    }
+
+    // the class at the bottom of the nesting chain, if there is one
+    pub fn innermost_class<'a>(&'a self, bundle: &'a ScriptBundle<'_>) -> Option<&'a Type> {
+        self.nesting(bundle)
+            .last()
+            .filter(|typ| typ.kind == TypeKind::Class)
+    }
 }
 
 impl From<Type> for Definition<'_> {
@@ -308,11 +760,11 @@ pub struct Class {
     #[byte(ctx = OptionalIndex(ctx))]
     base: Option<ClassIndex>,
     #[byte(ctx = Prefixed(ctx), skip_if = !flags.has_functions())]
-    methods: Vec<FunctionIndex>,
+    methods: ChildList<FunctionIndex>,
     #[byte(ctx = Prefixed(ctx), skip_if = !flags.has_fields())]
-    fields: Vec<FieldIndex>,
+    fields: ChildList<FieldIndex>,
     #[byte(ctx = Prefixed(ctx), skip_if = !flags.has_overrides())]
-    overrides: Vec<FieldIndex>,
+    overrides: ChildList<FieldIndex>,
 }
 
 impl Class {
@@ -323,9 +775,9 @@ impl Class {
             visiblity,
             flags,
             base: None,
-            methods: vec![],
-            fields: vec![],
-            overrides: vec![],
+            methods: ChildList::new(),
+            fields: ChildList::new(),
+            overrides: ChildList::new(),
         }
     }
 
@@ -364,6 +816,27 @@ impl Class {
         &self.overrides
     }
 
+    // methods that share their mangled name with a method listed after them, along with that
+    // last method, it usually means a patch was applied twice
+    pub fn duplicate_methods(
+        &self,
+        bundle: &ScriptBundle<'_>,
+    ) -> Vec<(FunctionIndex, FunctionIndex)> {
+        let mut last = BTreeMap::new();
+        for &method in &self.methods {
+            if let Some(fun) = bundle.get_item(method) {
+                last.insert(fun.name(), method);
+            }
+        }
+        self.methods
+            .iter()
+            .filter_map(|&method| {
+                let &kept = last.get(&bundle.get_item(method)?.name())?;
+                (kept != method).then_some((method, kept))
+            })
+            .collect()
+    }
+
     #[inline]
     pub fn with_base(mut self, base: Option<ClassIndex>) -> Self {
         self.base = base;
@@ -372,21 +845,21 @@ impl Class {
 
     #[inline]
     pub fn with_methods(mut self, methods: impl Into<Vec<FunctionIndex>>) -> Self {
-        self.methods = methods.into();
+        self.methods = ChildList::from_vec(methods.into());
         self.flags.set_has_functions(!self.methods.is_empty());
         self
     }
 
     #[inline]
     pub fn with_fields(mut self, fields: impl Into<Vec<FieldIndex>>) -> Self {
-        self.fields = fields.into();
+        self.fields = ChildList::from_vec(fields.into());
         self.flags.set_has_fields(!self.fields.is_empty());
         self
     }
 
     #[inline]
     pub fn with_overrides(mut self, overrides: impl Into<Vec<FieldIndex>>) -> Self {
-        self.overrides = overrides.into();
+        self.overrides = ChildList::from_vec(overrides.into());
         self.flags.set_has_overrides(!self.overrides.is_empty());
         self
     }
@@ -415,6 +888,10 @@ pub struct ClassFlags {
     __: u8,
 }
 
+impl ClassFlags {
+    const RESERVED: u16 = !0x01FF;
+}
+
 util::impl_bitfield_read_write!(ClassFlags);
 
 #[derive(Debug, Clone, PartialEq, Eq, TryRead, TryWrite, Measure)]
@@ -462,7 +939,7 @@ pub struct Enum {
     visiblity: Visibility,
     size: u8,
     #[byte(ctx = Prefixed(ctx))]
-    values: Vec<EnumValueIndex>,
+    values: ChildList<EnumValueIndex>,
     is_native: bool,
 }
 
@@ -473,7 +950,7 @@ impl Enum {
             name,
             visiblity,
             size,
-            values: vec![],
+            values: ChildList::new(),
             is_native: false,
         }
     }
@@ -503,9 +980,62 @@ impl Enum {
         self.is_native
     }
 
+    fn member_values<'a>(
+        &'a self,
+        bundle: &'a ScriptBundle<'_>,
+    ) -> impl Iterator<Item = (EnumValueIndex, i64)> + 'a {
+        self.values
+            .iter()
+            .filter_map(|&index| Some((index, bundle.get_item(index)?.value)))
+    }
+
+    #[inline]
+    pub fn min_value(&self, bundle: &ScriptBundle<'_>) -> Option<i64> {
+        self.member_values(bundle).map(|(_, value)| value).min()
+    }
+
+    #[inline]
+    pub fn max_value(&self, bundle: &ScriptBundle<'_>) -> Option<i64> {
+        self.member_values(bundle).map(|(_, value)| value).max()
+    }
+
+    // the smallest size in bytes that can hold every value of the enum
+    pub fn width(&self, bundle: &ScriptBundle<'_>) -> u8 {
+        let (min, max) = (self.min_value(bundle), self.max_value(bundle));
+        [1, 2, 4]
+            .into_iter()
+            .find(|&size| {
+                min.is_none_or(|min| value_fits(min, size))
+                    && max.is_none_or(|max| value_fits(max, size))
+            })
+            .unwrap_or(8)
+    }
+
+    // members whose value doesn't fit the declared size
+    pub fn values_out_of_range<'a>(
+        &'a self,
+        bundle: &'a ScriptBundle<'_>,
+    ) -> impl Iterator<Item = EnumValueIndex> + 'a {
+        self.member_values(bundle)
+            .filter(|&(_, value)| !value_fits(value, self.size))
+            .map(|(index, _)| index)
+    }
+
+    // groups of members sharing a value, in the order of the values
+    pub fn duplicate_values(&self, bundle: &ScriptBundle<'_>) -> Vec<(i64, Vec<EnumValueIndex>)> {
+        let mut by_value = BTreeMap::<_, Vec<_>>::new();
+        for (index, value) in self.member_values(bundle) {
+            by_value.entry(value).or_default().push(index);
+        }
+        by_value
+            .into_iter()
+            .filter(|(_, members)| members.len() > 1)
+            .collect()
+    }
+
     #[inline]
     pub fn with_values(mut self, values: impl Into<Vec<EnumValueIndex>>) -> Self {
-        self.values = values.into();
+        self.values = ChildList::from_vec(values.into());
         self
     }
 
@@ -540,9 +1070,9 @@ pub struct Function<'i> {
     #[byte(ctx = FlagDependent(ctx), skip_if = !flags.has_base_method())]
     base_method: Option<FunctionIndex>,
     #[byte(ctx = Prefixed(ctx), skip_if = !flags.has_parameters())]
-    parameters: Vec<ParameterIndex>,
+    parameters: ChildList<ParameterIndex>,
     #[byte(ctx = Prefixed(ctx), skip_if = !flags.has_locals())]
-    locals: Vec<LocalIndex>,
+    locals: ChildList<LocalIndex>,
     #[byte(ctx = FlagDependent(ctx), skip_if = !flags.is_operator())]
     operator: Option<CNameIndex>,
     #[byte(ctx = FlagDependent(ctx), skip_if = !flags.is_cast())]
@@ -563,14 +1093,23 @@ impl<'i> Function<'i> {
             return_type: None,
             is_const_return: false,
             base_method: None,
-            parameters: vec![],
-            locals: vec![],
+            parameters: ChildList::new(),
+            locals: ChildList::new(),
             operator: None,
             cast_cost: 0,
             body: FunctionBody::default(),
         }
     }
 
+    // the code size of an encoded function, the fields that precede the body go through
+    // the regular decoder while the code is only borrowed, so that limits can be checked before
+    // any instruction is decoded
+    pub(crate) fn encoded_code_size<Ctx: Endianess>(bytes: &[u8], ctx: Ctx) -> byte::Result<usize> {
+        let fun: Function<'_> = bytes.read_at(0, ctx)?;
+        let ctx = (ctx, fun.flags);
+        Ok(fun.body.measure(ctx) - FunctionBody::default().measure(ctx))
+    }
+
     #[inline]
     pub fn name(&self) -> CNameIndex {
         self.name
@@ -596,11 +1135,26 @@ impl<'i> Function<'i> {
         self.source
     }
 
+    // the type of the returned value, it's stored inline in the function rather than as a child
+    // definition, None if the function doesn't return anything or the type is not in the bundle
+    #[inline]
+    pub fn return_type(&self, bundle: &ScriptBundle<'_>) -> Option<TypeIndex> {
+        self.return_type
+            .filter(|&typ| !self.returns_void() && bundle.get_item(typ).is_some())
+    }
+
+    // the return type as written, it can be set even if the function isn't flagged as returning
+    // a value
     #[inline]
-    pub fn return_type(&self) -> Option<TypeIndex> {
+    pub(crate) fn declared_return_type(&self) -> Option<TypeIndex> {
         self.return_type
     }
 
+    #[inline]
+    pub fn returns_void(&self) -> bool {
+        !self.flags.has_return_value() || self.return_type.is_none()
+    }
+
     #[inline]
     pub fn is_const_return(&self) -> bool {
         self.is_const_return
@@ -626,6 +1180,16 @@ impl<'i> Function<'i> {
         self.operator
     }
 
+    #[inline]
+    pub fn is_operator(&self) -> bool {
+        self.flags.is_operator()
+    }
+
+    #[inline]
+    pub fn is_cast(&self) -> bool {
+        self.flags.is_cast()
+    }
+
     #[inline]
     pub fn cast_cost(&self) -> u8 {
         self.cast_cost
@@ -636,6 +1200,74 @@ impl<'i> Function<'i> {
         &self.body
     }
 
+    pub fn parameter_flags<'a>(
+        &'a self,
+        bundle: &'a ScriptBundle<'_>,
+    ) -> impl Iterator<Item = ParameterFlags> + 'a {
+        self.parameters
+            .iter()
+            .filter_map(|&index| bundle.get_item(index))
+            .map(Parameter::flags)
+    }
+
+    pub fn optional_param_count(&self, bundle: &ScriptBundle<'_>) -> usize {
+        self.parameter_flags(bundle)
+            .filter(ParameterFlags::is_optional)
+            .count()
+    }
+
+    pub fn required_param_count(&self, bundle: &ScriptBundle<'_>) -> usize {
+        self.parameter_flags(bundle)
+            .filter(|flags| !flags.is_optional())
+            .count()
+    }
+
+    pub fn out_param_count(&self, bundle: &ScriptBundle<'_>) -> usize {
+        self.parameter_flags(bundle)
+            .filter(ParameterFlags::is_out)
+            .count()
+    }
+
+    // offsets of `StaticArray*` instructions whose type operand is not a static array type
+    pub fn invalid_static_array_instrs<'a>(
+        &'a self,
+        bundle: &'a ScriptBundle<'_>,
+    ) -> impl Iterator<Item = ByteOffset> + 'a {
+        self.body
+            .code_iter()
+            .with_offsets()
+            .map_while(|(offset, instr)| Some((offset, instr.ok()?)))
+            .filter(|(_, instr)| {
+                instr.static_array_type().is_some_and(|typ| {
+                    bundle
+                        .get_item(typ)
+                        .and_then(Type::static_array_size)
+                        .is_none()
+                })
+            })
+            .map(|(offset, _)| offset)
+    }
+
+    // offsets of `EnumToI32` and `I32ToEnum` instructions whose size operand doesn't match
+    // the declared size of the enum, the ones ScriptBundle::repair_enum_sizes corrects
+    pub fn mismatched_enum_sizes<'a>(
+        &'a self,
+        bundle: &'a ScriptBundle<'_>,
+        enums: &'a EnumsByName<'_>,
+    ) -> impl Iterator<Item = ByteOffset> + 'a {
+        self.body
+            .code_iter()
+            .with_offsets()
+            .map_while(|(offset, instr)| Some((offset, instr.ok()?)))
+            .filter(|(_, instr)| match instr {
+                &Instr::EnumToI32 { enum_type, size } | &Instr::I32ToEnum { enum_type, size } => {
+                    enums.size_mismatch(bundle, enum_type, size).is_some()
+                }
+                _ => false,
+            })
+            .map(|(offset, _)| offset)
+    }
+
     #[inline]
     pub fn with_class(mut self, class: Option<ClassIndex>) -> Self {
         self.class = class;
@@ -674,14 +1306,14 @@ impl<'i> Function<'i> {
 
     #[inline]
     pub fn with_parameters(mut self, parameters: impl Into<Vec<ParameterIndex>>) -> Self {
-        self.parameters = parameters.into();
+        self.parameters = ChildList::from_vec(parameters.into());
         self.flags.set_has_parameters(!self.parameters.is_empty());
         self
     }
 
     #[inline]
     pub fn with_locals(mut self, locals: impl Into<Vec<LocalIndex>>) -> Self {
-        self.locals = locals.into();
+        self.locals = ChildList::from_vec(locals.into());
         self.flags.set_has_locals(!self.locals.is_empty());
         self
     }
@@ -710,12 +1342,28 @@ impl<'i> Function<'i> {
         self
     }
 
+    // replaces the code without touching the flags
+    #[inline]
+    pub(crate) fn set_code(&mut self, code: Vec<Instr>) {
+        self.body = FunctionBody::Code(code);
+    }
+
     #[inline]
     pub fn with_code(self, code: Vec<Instr>) -> Self {
         self.with_body(FunctionBody::Code(code))
     }
 
+    #[inline]
+    pub fn try_with_code(self, code: impl IntoIterator<Item = Instr>) -> Result<Self, CodeError> {
+        Ok(self.with_body(FunctionBody::from_instrs(code)?))
+    }
+
+    #[inline]
     pub fn into_owned(self) -> Function<'static> {
+        self.relocate(&Owned)
+    }
+
+    pub(crate) fn relocate<'b>(self, to: &impl Relocate<'b>) -> Function<'b> {
         Function {
             name: self.name,
             class: self.class,
@@ -729,7 +1377,7 @@ impl<'i> Function<'i> {
             locals: self.locals,
             operator: self.operator,
             cast_cost: self.cast_cost,
-            body: self.body.into_owned(),
+            body: self.body.relocate(to),
         }
     }
 }
@@ -770,6 +1418,10 @@ pub struct FunctionFlags {
     __: u16,
 }
 
+impl FunctionFlags {
+    const RESERVED: u32 = !0x0078_7FDF;
+}
+
 util::impl_bitfield_read_write!(FunctionFlags);
 
 #[derive(Debug, Clone, PartialEq, Eq, TryRead, TryWrite, Measure)]
@@ -817,6 +1469,32 @@ impl Parameter {
     pub fn flags(&self) -> ParameterFlags {
         self.flags
     }
+
+    #[inline]
+    pub fn is_optional(&self) -> bool {
+        self.flags.is_optional()
+    }
+
+    #[inline]
+    pub fn is_out(&self) -> bool {
+        self.flags.is_out()
+    }
+
+    #[inline]
+    pub fn is_short_circuit(&self) -> bool {
+        self.flags.is_short_circuit()
+    }
+
+    #[inline]
+    pub fn is_const(&self) -> bool {
+        self.flags.is_const()
+    }
+
+    #[inline]
+    pub fn with_flags(mut self, flags: ParameterFlags) -> Self {
+        self.flags = flags;
+        self
+    }
 }
 
 impl From<Parameter> for Definition<'_> {
@@ -837,6 +1515,10 @@ pub struct ParameterFlags {
     __: u8,
 }
 
+impl ParameterFlags {
+    const RESERVED: u8 = !0x0F;
+}
+
 util::impl_bitfield_read_write!(ParameterFlags);
 
 #[derive(Debug, Clone, PartialEq, Eq, TryRead, TryWrite, Measure)]
@@ -901,6 +1583,10 @@ pub struct LocalFlags {
     __: u8,
 }
 
+impl LocalFlags {
+    const RESERVED: u8 = !0x01;
+}
+
 util::impl_bitfield_read_write!(LocalFlags);
 
 #[derive(Debug, Clone, PartialEq, Eq, TryRead, TryWrite, Measure)]
@@ -1001,23 +1687,28 @@ impl<'i> Field<'i> {
         self
     }
 
+    #[inline]
     pub fn into_owned(self) -> Field<'static> {
+        self.relocate(&Owned)
+    }
+
+    pub(crate) fn relocate<'b>(self, to: &impl Relocate<'b>) -> Field<'b> {
         Field {
             name: self.name,
             class: self.class,
             visibility: self.visibility,
             type_: self.type_,
             flags: self.flags,
-            hint: self.hint.map(Str::into_owned),
+            hint: self.hint.map(|hint| to.relocate_str(hint)),
             attributes: self
                 .attributes
                 .into_iter()
-                .map(Property::into_owned)
+                .map(|prop| prop.relocate(to))
                 .collect(),
             defaults: self
                 .defaults
                 .into_iter()
-                .map(Property::into_owned)
+                .map(|prop| prop.relocate(to))
                 .collect(),
         }
     }
@@ -1048,6 +1739,10 @@ pub struct FieldFlags {
     __: u8,
 }
 
+impl FieldFlags {
+    const RESERVED: u16 = !0x07FF;
+}
+
 util::impl_bitfield_read_write!(FieldFlags);
 
 #[derive(Debug, Clone, PartialEq, Eq, TryRead, TryWrite, Measure)]
@@ -1104,13 +1799,18 @@ impl<'i> SourceFile<'i> {
         &self.path
     }
 
+    #[inline]
     pub fn into_owned(self) -> SourceFile<'static> {
+        self.relocate(&Owned)
+    }
+
+    pub(crate) fn relocate<'b>(self, to: &impl Relocate<'b>) -> SourceFile<'b> {
         SourceFile {
             name: self.name,
             index: self.index,
             path_hash: self.path_hash,
             code_crc: self.code_crc,
-            path: self.path.into_owned(),
+            path: to.relocate_str(self.path),
         }
     }
 }
@@ -1139,21 +1839,132 @@ impl<'i> FunctionBody<'i> {
         }
     }
 
+    #[inline]
     pub fn into_owned(self) -> FunctionBody<'static> {
+        self.relocate(&Owned)
+    }
+
+    pub(crate) fn relocate<'b>(self, to: &impl Relocate<'b>) -> FunctionBody<'b> {
         match self {
             FunctionBody::Raw { max_offset, bytes } => FunctionBody::Raw {
                 max_offset,
-                bytes: Cow::Owned(bytes.into_owned()),
+                bytes: to.relocate_bytes(bytes),
             },
             FunctionBody::Code(instructions) => FunctionBody::Code(instructions),
         }
     }
 
+    pub(crate) fn has_padding(&self) -> bool {
+        self.code_iter()
+            .map_while(Result::ok)
+            .any(|instr| matches!(instr, Instr::Breakpoint(breakpoint) if breakpoint.has_padding()))
+    }
+
+    // zeroes the padding of breakpoints, the body is decoded if there is any and left as it is
+    // if it can't be
+    pub(crate) fn clear_padding(&mut self) {
+        if !self.has_padding() {
+            return;
+        }
+        let Ok(mut code) = self.code_owned() else {
+            return;
+        };
+        for instr in &mut code {
+            if let Instr::Breakpoint(breakpoint) = instr {
+                breakpoint.clear_padding();
+            }
+        }
+        *self = FunctionBody::Code(code);
+    }
+
     #[inline]
     pub fn code_iter(&self) -> CowCodeIter<'_> {
         CowCodeIter::new(self)
     }
 
+    // strips nops and debug instructions and retargets jumps accordingly, meant for comparisons
+    pub fn normalized(&self) -> byte::Result<NormalizedCode> {
+        let code = self.code_owned()?;
+        let original = OffsetMap::new(&code);
+        let is_kept =
+            |instr: &Instr| !matches!(instr, Instr::Nop | Instr::Breakpoint(_) | Instr::Profile(_));
+
+        // new start offset of every original instruction, removed ones map to the next kept one
+        let mut new_offsets = Vec::with_capacity(code.len() + 1);
+        let mut offset = 0i32;
+        for instr in &code {
+            new_offsets.push(offset);
+            if is_kept(instr) {
+                offset += i32::from(instr.size());
+            }
+        }
+        new_offsets.push(offset);
+
+        let mut result = NormalizedCode::default();
+        for (i, instr) in code.into_iter().enumerate() {
+            if !is_kept(&instr) {
+                continue;
+            }
+            let index = InstrIndex::new(i as u32);
+            let start = original.offset(index).unwrap_or_default();
+            let instr = instr.map_offsets(|rel| {
+                let Some(target) = original.resolve_jump(index, rel) else {
+                    return rel;
+                };
+                let diff = new_offsets[usize::from(target)] - new_offsets[i];
+                i16::try_from(diff).map_or(rel, Offset::from)
+            });
+            result.code.push(instr);
+            result.origins.push(start);
+        }
+        Ok(result)
+    }
+
+    // normalized code with equivalent forms of instructions made the same, integer constants take
+    // their shortest forms and jumps to unconditional jumps go straight to the final target, so
+    // that the output of different compilers can be compared
+    pub fn canonicalized(&self) -> byte::Result<NormalizedCode> {
+        let mut result = self.normalized()?;
+        let offsets = OffsetMap::new(&result.code);
+        let final_target = |from: InstrIndex, rel: Offset| {
+            let mut target = offsets.resolve_jump(from, rel)?;
+            // bounded in case the jumps form a cycle
+            for _ in 0..result.code.len() {
+                let Some(Instr::Jump(jump)) = result.code.get(usize::from(target)) else {
+                    break;
+                };
+                match offsets.resolve_jump(target, jump.target()) {
+                    Some(next) if next != target => target = next,
+                    _ => break,
+                }
+            }
+            offsets.offset(from)?.offset_to(offsets.offset(target)?)
+        };
+
+        let threaded = result
+            .code
+            .iter()
+            .enumerate()
+            .map(|(i, instr)| match instr {
+                Instr::Jump(_) | Instr::JumpIfFalse(_) => instr
+                    .clone()
+                    .map_offsets(|rel| final_target(InstrIndex::new(i as u32), rel).unwrap_or(rel)),
+                _ => instr.clone(),
+            })
+            .collect::<Vec<_>>();
+        let code = threaded
+            .iter()
+            .map(|instr| match instr {
+                Instr::I32Const(0) => Instr::I32Zero,
+                Instr::I32Const(1) => Instr::I32One,
+                instr => instr.clone(),
+            })
+            .collect();
+        // shorter instructions can't get a jump out of range
+        result.code = instr::retarget_jumps(&threaded, code).unwrap_or(threaded);
+        Ok(result)
+    }
+
     #[inline]
     pub fn code_owned(&self) -> byte::Result<Vec<Instr>> {
         match self {
@@ -1161,8 +1972,88 @@ impl<'i> FunctionBody<'i> {
             FunctionBody::Code(instructions) => Ok(instructions.clone()),
         }
     }
+
+    // checks that the code can be encoded as it is, i.e. that there are no Target labels left and
+    // that every offset points at the start of an instruction or at the end of the code
+    pub fn from_instrs(instructions: impl IntoIterator<Item = Instr>) -> Result<Self, CodeError> {
+        let code = instructions.into_iter().collect::<Vec<_>>();
+        if let Some(pos) = code.iter().position(|i| matches!(i, Instr::Target(_))) {
+            return Err(CodeError::Target(InstrIndex::new(pos as u32)));
+        }
+        let size = code.iter().map(|i| u64::from(i.size())).sum::<u64>();
+        if size > u64::from(u32::MAX) {
+            return Err(CodeError::TooLarge(size));
+        }
+
+        let offsets = OffsetMap::new(&code);
+        for (pos, instr) in code.iter().enumerate() {
+            let index = InstrIndex::new(pos as u32);
+            let mut invalid = None;
+            instr.clone().map_offsets(|offset| {
+                if invalid.is_none() && offsets.resolve_jump(index, offset).is_none() {
+                    invalid = Some(offset);
+                }
+                offset
+            });
+            if let Some(offset) = invalid {
+                return Err(CodeError::InvalidOffset { index, offset });
+            }
+        }
+        Ok(FunctionBody::Code(code))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CodeError {
+    // a label that was never resolved to an offset
+    Target(InstrIndex),
+    InvalidOffset { index: InstrIndex, offset: Offset },
+    // the size of the code in bytes
+    TooLarge(u64),
+    UnresolvedLabel { index: InstrIndex, label: Label },
+    DuplicateLabel { index: InstrIndex, label: Label },
+    OffsetOutOfRange { index: InstrIndex, label: Label },
+}
+
+impl fmt::Display for CodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CodeError::Target(index) => write!(
+                f,
+                "unresolved target label at instruction {}",
+                u32::from(*index)
+            ),
+            CodeError::InvalidOffset { index, offset } => write!(
+                f,
+                "offset {} of instruction {} does not point at an instruction",
+                i16::from(*offset),
+                u32::from(*index)
+            ),
+            CodeError::TooLarge(size) => write!(f, "code of {size} bytes is too large"),
+            CodeError::UnresolvedLabel { index, label } => write!(
+                f,
+                "label {} used by instruction {} is never placed",
+                u32::from(*label),
+                u32::from(*index)
+            ),
+            CodeError::DuplicateLabel { index, label } => write!(
+                f,
+                "label {} is placed again at instruction {}",
+                u32::from(*label),
+                u32::from(*index)
+            ),
+            CodeError::OffsetOutOfRange { index, label } => write!(
+                f,
+                "label {} is too far from instruction {}",
+                u32::from(*label),
+                u32::from(*index)
+            ),
+        }
+    }
 }
 
+impl std::error::Error for CodeError {}
+
 impl Default for FunctionBody<'_> {
     #[inline]
     fn default() -> Self {
@@ -1230,6 +2121,33 @@ impl<Ctx: Copy> Measure<(Ctx, FunctionFlags)> for FunctionBody<'_> {
     }
 }
 
+// code with padding removed, comparing two of these ignores where the instructions came from
+#[derive(Debug, Default, Clone)]
+pub struct NormalizedCode {
+    code: Vec<Instr>,
+    origins: Vec<ByteOffset>,
+}
+
+impl NormalizedCode {
+    #[inline]
+    pub fn code(&self) -> &[Instr] {
+        &self.code
+    }
+
+    // offset of the instruction in the original body
+    #[inline]
+    pub fn origin(&self, index: InstrIndex) -> Option<ByteOffset> {
+        self.origins.get(usize::from(index)).copied()
+    }
+}
+
+impl PartialEq for NormalizedCode {
+    #[inline]
+    fn eq(&self, other: &Self) -> bool {
+        self.code == other.code
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum CowCodeIter<'a> {
     Raw {
@@ -1254,14 +2172,16 @@ impl<'a> CowCodeIter<'a> {
     }
 
     #[inline]
-    pub fn offset(&self) -> u32 {
+    pub fn offset(&self) -> ByteOffset {
         match self {
-            CowCodeIter::Raw { offset, .. } | CowCodeIter::Code(CodeIter { offset, .. }) => *offset,
+            CowCodeIter::Raw { offset, .. } | CowCodeIter::Code(CodeIter { offset, .. }) => {
+                ByteOffset::new(*offset)
+            }
         }
     }
 
     #[inline]
-    pub fn with_offsets(mut self) -> impl Iterator<Item = (u32, byte::Result<Instr>)> + 'a {
+    pub fn with_offsets(mut self) -> impl Iterator<Item = (ByteOffset, byte::Result<Instr>)> + 'a {
         iter::from_fn(move || Some((self.offset(), self.next()?)))
     }
 }
@@ -1311,12 +2231,12 @@ impl<'a> CodeIter<'a> {
     }
 
     #[inline]
-    pub fn offset(&self) -> u32 {
-        self.offset
+    pub fn offset(&self) -> ByteOffset {
+        ByteOffset::new(self.offset)
     }
 
     #[inline]
-    pub fn with_offsets(mut self) -> impl Iterator<Item = (u32, &'a Instr)> {
+    pub fn with_offsets(mut self) -> impl Iterator<Item = (ByteOffset, &'a Instr)> {
         iter::from_fn(move || Some((self.offset(), self.next()?)))
     }
 
@@ -1364,10 +2284,15 @@ impl<'i> Property<'i> {
         &self.value
     }
 
+    #[inline]
     pub fn into_owned(self) -> Property<'static> {
+        self.relocate(&Owned)
+    }
+
+    pub(crate) fn relocate<'b>(self, to: &impl Relocate<'b>) -> Property<'b> {
         Property {
-            name: self.name.into_owned(),
-            value: self.value.into_owned(),
+            name: to.relocate_str(self.name),
+            value: to.relocate_str(self.value),
         }
     }
 }
@@ -1393,6 +2318,11 @@ impl SourceReference {
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, TryRead, TryWrite, Measure)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "lowercase")
+)]
 #[byte(tag_type = u8)]
 pub enum Visibility {
     #[byte(tag = 0x00)]
@@ -0,0 +1,95 @@
+use std::fmt;
+
+use crate::bundle::BundleReader;
+use crate::definition::Definition;
+
+// an independent decoder to cross-check this crate against, e.g. one built on the original
+// redscript crate, both sides are brought to a common representation by the implementor
+pub trait ReferenceDecoder<'i> {
+    type Item: PartialEq + fmt::Debug;
+
+    fn definition_count(&self) -> u32;
+
+    fn decode(&mut self, index: u32) -> Result<Self::Item, String>;
+
+    // converts a definition decoded by this crate into the representation of the reference
+    fn convert(&mut self, reader: &BundleReader<'i>, index: u32, def: Definition<'i>)
+        -> Self::Item;
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Divergence {
+    pub index: u32,
+    pub kind: DivergenceKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DivergenceKind {
+    // the definition only exists on one side
+    Missing { in_reference: bool },
+    // only this crate failed to decode the definition
+    Decode(byte::Error),
+    // only the reference failed to decode the definition
+    ReferenceDecode(String),
+    // both sides decoded the definition differently, the values are debug representations
+    Mismatch { ours: String, reference: String },
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "definition {}: ", self.index)?;
+        match &self.kind {
+            DivergenceKind::Missing { in_reference: true } => f.write_str("missing in reference"),
+            DivergenceKind::Missing {
+                in_reference: false,
+            } => f.write_str("missing in ours"),
+            DivergenceKind::Decode(err) => write!(f, "failed to decode: {err:?}"),
+            DivergenceKind::ReferenceDecode(err) => write!(f, "reference failed to decode: {err}"),
+            DivergenceKind::Mismatch { ours, reference } => {
+                write!(f, "decoded as {ours}, reference has {reference}")
+            }
+        }
+    }
+}
+
+impl<'i> BundleReader<'i> {
+    // decodes every definition on its own with both decoders and reports where they disagree,
+    // definitions neither side can decode are not reported
+    pub fn compare_with_reference<R>(&self, reference: &mut R) -> Vec<Divergence>
+    where
+        R: ReferenceDecoder<'i>,
+    {
+        let definitions = self.definitions();
+        let ours = definitions.count();
+        let theirs = reference.definition_count();
+
+        let mut divergences = vec![];
+        for index in 1..ours.max(theirs) {
+            let kind = if index >= theirs {
+                DivergenceKind::Missing { in_reference: true }
+            } else if index >= ours {
+                DivergenceKind::Missing {
+                    in_reference: false,
+                }
+            } else {
+                match (definitions.get(index), reference.decode(index)) {
+                    (Ok(def), Ok(expected)) => {
+                        let actual = reference.convert(self, index, def);
+                        if actual == expected {
+                            continue;
+                        }
+                        DivergenceKind::Mismatch {
+                            ours: format!("{actual:?}"),
+                            reference: format!("{expected:?}"),
+                        }
+                    }
+                    (Err(err), Ok(_)) => DivergenceKind::Decode(err),
+                    (Ok(_), Err(err)) => DivergenceKind::ReferenceDecode(err),
+                    (Err(_), Err(_)) => continue,
+                }
+            };
+            divergences.push(Divergence { index, kind });
+        }
+        divergences
+    }
+}
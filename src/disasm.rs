@@ -0,0 +1,176 @@
+use std::fmt;
+
+use crate::annotate::AnnotationTarget;
+use crate::bundle::ScriptBundle;
+use crate::definition::{Definition, Function};
+use crate::index::FunctionIndex;
+use crate::instr::{ByteOffset, InstrIndex, Operand};
+use crate::symbols;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Verbosity {
+    // indices as they're stored, e.g. `InvokeStatic #12, 4, +20, 0`
+    Raw,
+    // names of definitions and contents of pool strings
+    #[default]
+    Names,
+    // qualified names, function signatures and types in redscript syntax
+    Full,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DisasmOptions {
+    verbosity: Verbosity,
+    offsets: bool,
+}
+
+impl DisasmOptions {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[inline]
+    pub fn with_verbosity(mut self, verbosity: Verbosity) -> Self {
+        self.verbosity = verbosity;
+        self
+    }
+
+    // prefixes every instruction with its byte offset and prints jump targets as absolute offsets
+    #[inline]
+    pub fn with_offsets(mut self, offsets: bool) -> Self {
+        self.offsets = offsets;
+        self
+    }
+}
+
+impl Default for DisasmOptions {
+    fn default() -> Self {
+        Self {
+            verbosity: Verbosity::default(),
+            offsets: true,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Disassembly<'a, 'i> {
+    function: &'a Function<'i>,
+    bundle: &'a ScriptBundle<'i>,
+    options: DisasmOptions,
+    annotated: Option<FunctionIndex>,
+}
+
+impl<'i> Function<'i> {
+    #[inline]
+    pub fn disassemble<'a>(
+        &'a self,
+        bundle: &'a ScriptBundle<'i>,
+        options: DisasmOptions,
+    ) -> Disassembly<'a, 'i> {
+        Disassembly {
+            function: self,
+            bundle,
+            options,
+            annotated: None,
+        }
+    }
+}
+
+impl Disassembly<'_, '_> {
+    // includes the annotations the bundle has for the function and its instructions as comments
+    #[inline]
+    pub fn with_annotations(mut self, function: FunctionIndex) -> Self {
+        self.annotated = Some(function);
+        self
+    }
+
+    fn write_operand(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+        at: ByteOffset,
+        operand: Operand,
+    ) -> fmt::Result {
+        let bundle = self.bundle;
+        let verbosity = self.options.verbosity;
+        let pool =
+            |f: &mut fmt::Formatter<'_>, prefix: &str, str: Option<&str>, index: u32| match str {
+                Some(str) if verbosity != Verbosity::Raw => write!(f, "{prefix}{str:?}"),
+                _ => write!(f, "#{index}"),
+            };
+        match operand {
+            Operand::Int(v) => write!(f, "{v}"),
+            Operand::UInt(v) => write!(f, "{v}"),
+            Operand::Float(v) => write!(f, "{v:?}"),
+            Operand::CName(index) => pool(f, "n", bundle.get_item(index), index.into()),
+            Operand::String(index) => pool(f, "", bundle.get_item(index), index.into()),
+            Operand::TweakDbId(index) => pool(f, "t", bundle.get_item(index), index.into()),
+            Operand::Resource(index) => pool(f, "r", bundle.get_item(index), index.into()),
+            Operand::Definition(index) => {
+                let def = bundle.definition(index);
+                match (verbosity, def) {
+                    (Verbosity::Names, Some(def)) => match bundle.get_item(def.name()) {
+                        Some(name) => f.write_str(name),
+                        None => write!(f, "#{index}"),
+                    },
+                    (Verbosity::Full, Some(def)) => {
+                        let name = symbols::qualified_name(bundle, index);
+                        f.write_str(name.as_deref().unwrap_or("<unnamed>"))?;
+                        if let Definition::Function(_) = def {
+                            f.write_str(&symbols::signature(bundle, def))?;
+                        }
+                        Ok(())
+                    }
+                    _ => write!(f, "#{index}"),
+                }
+            }
+            Operand::Type(index) => match (verbosity, bundle.get_item(index)) {
+                (Verbosity::Names, Some(typ)) => match bundle.get_item(typ.name()) {
+                    Some(name) => f.write_str(name),
+                    None => write!(f, "#{}", u32::from(index)),
+                },
+                (Verbosity::Full, Some(typ)) => write!(f, "{}", typ.display(bundle)),
+                _ => write!(f, "#{}", u32::from(index)),
+            },
+            Operand::Offset(offset) => match at.jump(offset) {
+                Some(target) if self.options.offsets => write!(f, "@{:04x}", u32::from(target)),
+                _ => write!(f, "{:+}", i16::from(offset)),
+            },
+        }
+    }
+}
+
+impl fmt::Display for Disassembly<'_, '_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let annotations = self.bundle.annotations();
+        if let Some(function) = self.annotated {
+            if let Some(annotation) = annotations.get(AnnotationTarget::Definition(function.into()))
+            {
+                writeln!(f, "; {annotation}")?;
+            }
+        }
+        let code = self.function.body().code_iter().with_offsets();
+        for (i, (offset, instr)) in code.enumerate() {
+            if self.options.offsets {
+                write!(f, "{:04x}  ", u32::from(offset))?;
+            }
+            let instr = match instr {
+                Ok(instr) => instr,
+                Err(err) => return writeln!(f, "error: {err:?}"),
+            };
+            f.write_str(instr.name())?;
+            for (i, (_, operand)) in instr.operands().into_iter().enumerate() {
+                f.write_str(if i == 0 { " " } else { ", " })?;
+                self.write_operand(f, offset, operand)?;
+            }
+            let annotation = self.annotated.and_then(|function| {
+                annotations.get(AnnotationTarget::Instr(function, InstrIndex::new(i as u32)))
+            });
+            if let Some(annotation) = annotation {
+                write!(f, "  ; {annotation}")?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,110 @@
+use std::fmt;
+
+use crate::bundle::BundleReader;
+use crate::definition::{Definition, DefinitionHeader, DefinitionKind};
+
+// annotated hex dump of a single definition as it's laid out in the file
+#[derive(Debug)]
+pub struct DefinitionDump<'i> {
+    index: u32,
+    header_pos: u32,
+    header: DefinitionHeader,
+    name: Option<&'i str>,
+    bytes: &'i [u8],
+    definition: byte::Result<Definition<'i>>,
+}
+
+impl<'i> BundleReader<'i> {
+    pub fn dump_definition(&self, index: u32) -> byte::Result<DefinitionDump<'i>> {
+        let definitions = self.definitions();
+        let header = definitions.header(index)?;
+        let start = header.offset() as usize;
+        let bytes = start
+            .checked_add(header.size() as usize)
+            .and_then(|end| self.contents().get(start..end))
+            .ok_or(byte::Error::BadInput {
+                err: "definition data out of bounds",
+            })?;
+        Ok(DefinitionDump {
+            index,
+            header_pos: definitions.header_pos(index)?,
+            header,
+            name: self.cnames().get(header.name()).ok(),
+            bytes,
+            definition: definitions.get(index),
+        })
+    }
+}
+
+impl fmt::Display for DefinitionDump<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let header = &self.header;
+        let kind =
+            DefinitionKind::from_tag(header.type_tag()).map_or("<unknown>", DefinitionKind::name);
+        writeln!(
+            f,
+            "definition {} ({kind}) {}",
+            self.index,
+            self.name.unwrap_or("<unnamed>")
+        )?;
+
+        writeln!(f, "header:")?;
+        // positions are widened so that they can't overflow for headers at the end of the range
+        let pos = u64::from(self.header_pos);
+        writeln!(f, "  {:08x}  name      {:?}", pos, header.name())?;
+        writeln!(f, "  {:08x}  parent    {}", pos + 4, header.parent())?;
+        writeln!(f, "  {:08x}  offset    {:08x}", pos + 8, header.offset())?;
+        writeln!(f, "  {:08x}  size      {}", pos + 12, header.size())?;
+        writeln!(f, "  {:08x}  type_tag  {}", pos + 16, header.type_tag())?;
+        writeln!(f, "  {:08x}  padding   {:?}", pos + 18, header.padding())?;
+
+        writeln!(f, "data:")?;
+        let start = header.offset() as usize;
+        let mut pos = 0;
+        // the fields can only be told apart once the definition has been decoded
+        if let Ok(def) = &self.definition {
+            for field in def.encoded_fields() {
+                writeln!(f, "  {} = {:?}", field.name, field.value)?;
+                let end = (pos + field.size).min(self.bytes.len());
+                write_rows(f, &self.bytes[pos..end], start + pos)?;
+                pos = end;
+            }
+            if pos < self.bytes.len() {
+                writeln!(f, "  <trailing>")?;
+            }
+        }
+        write_rows(f, &self.bytes[pos..], start + pos)?;
+
+        writeln!(f, "decoded:")?;
+        match &self.definition {
+            Ok(Definition::Function(fun)) => {
+                writeln!(f, "  {fun:?}")?;
+                writeln!(f, "code:")?;
+                for (offset, instr) in fun.body().code_iter().with_offsets() {
+                    match instr {
+                        Ok(instr) => writeln!(f, "  {:04x}  {instr:?}", u32::from(offset))?,
+                        Err(err) => {
+                            writeln!(f, "  {:04x}  error: {err:?}", u32::from(offset))?;
+                            break;
+                        }
+                    }
+                }
+            }
+            Ok(def) => writeln!(f, "  {def:?}")?,
+            Err(err) => writeln!(f, "  error: {err:?}")?,
+        }
+        Ok(())
+    }
+}
+
+// rows of up to 16 bytes prefixed with their offset in the file
+fn write_rows(f: &mut fmt::Formatter<'_>, bytes: &[u8], offset: usize) -> fmt::Result {
+    for (i, row) in bytes.chunks(16).enumerate() {
+        write!(f, "  {:08x} ", offset + i * 16)?;
+        for byte in row {
+            write!(f, " {byte:02x}")?;
+        }
+        writeln!(f)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,211 @@
+use std::fmt;
+
+use crate::bundle::ScriptBundle;
+use crate::definition::{Field, Local, Parameter};
+use crate::index::{CNameIndex, FieldIndex, LocalIndex, ParameterIndex, StringIndex, TypeIndex};
+use crate::instr::Instr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExprType {
+    Defined(TypeIndex),
+    Primitive(&'static str),
+    // the type could not be determined, no checks are performed against it
+    Unknown,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TypedExpr {
+    code: Vec<Instr>,
+    typ: ExprType,
+}
+
+impl TypedExpr {
+    #[inline]
+    pub fn new(code: Vec<Instr>, typ: ExprType) -> Self {
+        Self { code, typ }
+    }
+
+    #[inline]
+    pub fn typ(&self) -> ExprType {
+        self.typ
+    }
+
+    #[inline]
+    pub fn code(&self) -> &[Instr] {
+        &self.code
+    }
+
+    #[inline]
+    pub fn into_code(self) -> Vec<Instr> {
+        self.code
+    }
+}
+
+// emits code while checking that the types of assigned and returned expressions agree
+#[derive(Debug)]
+pub struct TypedEmitter<'a, 'i> {
+    bundle: &'a ScriptBundle<'i>,
+    return_type: Option<TypeIndex>,
+    code: Vec<Instr>,
+}
+
+impl<'a, 'i> TypedEmitter<'a, 'i> {
+    #[inline]
+    pub fn new(bundle: &'a ScriptBundle<'i>) -> Self {
+        Self {
+            bundle,
+            return_type: None,
+            code: vec![],
+        }
+    }
+
+    #[inline]
+    pub fn with_return_type(mut self, return_type: Option<TypeIndex>) -> Self {
+        self.return_type = return_type;
+        self
+    }
+
+    #[inline]
+    pub fn i32(&self, value: i32) -> TypedExpr {
+        TypedExpr::new(vec![Instr::I32Const(value)], ExprType::Primitive("Int32"))
+    }
+
+    #[inline]
+    pub fn i64(&self, value: i64) -> TypedExpr {
+        TypedExpr::new(vec![Instr::I64Const(value)], ExprType::Primitive("Int64"))
+    }
+
+    #[inline]
+    pub fn f32(&self, value: f32) -> TypedExpr {
+        TypedExpr::new(vec![Instr::F32Const(value)], ExprType::Primitive("Float"))
+    }
+
+    #[inline]
+    pub fn f64(&self, value: f64) -> TypedExpr {
+        TypedExpr::new(vec![Instr::F64Const(value)], ExprType::Primitive("Double"))
+    }
+
+    #[inline]
+    pub fn bool(&self, value: bool) -> TypedExpr {
+        let instr = if value {
+            Instr::TrueConst
+        } else {
+            Instr::FalseConst
+        };
+        TypedExpr::new(vec![instr], ExprType::Primitive("Bool"))
+    }
+
+    #[inline]
+    pub fn cname(&self, value: CNameIndex) -> TypedExpr {
+        TypedExpr::new(vec![Instr::CNameConst(value)], ExprType::Primitive("CName"))
+    }
+
+    #[inline]
+    pub fn string(&self, value: StringIndex) -> TypedExpr {
+        TypedExpr::new(
+            vec![Instr::StringConst(value)],
+            ExprType::Primitive("String"),
+        )
+    }
+
+    pub fn local(&self, index: LocalIndex) -> TypedExpr {
+        let typ = self.bundle.get_item(index).map(Local::typ);
+        TypedExpr::new(vec![Instr::Local(index)], Self::defined(typ))
+    }
+
+    pub fn param(&self, index: ParameterIndex) -> TypedExpr {
+        let typ = self.bundle.get_item(index).map(Parameter::typ);
+        TypedExpr::new(vec![Instr::Param(index)], Self::defined(typ))
+    }
+
+    // a field of the current context object
+    pub fn field(&self, index: FieldIndex) -> TypedExpr {
+        let typ = self.bundle.get_item(index).map(Field::typ);
+        TypedExpr::new(vec![Instr::ObjectField(index)], Self::defined(typ))
+    }
+
+    pub fn assign(&mut self, lhs: TypedExpr, rhs: TypedExpr) -> Result<(), TypeError> {
+        self.check(lhs.typ, rhs.typ)?;
+        self.code.push(Instr::Assign);
+        self.code.extend(lhs.code);
+        self.code.extend(rhs.code);
+        Ok(())
+    }
+
+    pub fn ret(&mut self, expr: Option<TypedExpr>) -> Result<(), TypeError> {
+        let expected = Self::defined(self.return_type);
+        match expr {
+            Some(expr) if self.return_type.is_some() => {
+                self.check(expected, expr.typ)?;
+                self.code.push(Instr::Return);
+                self.code.extend(expr.code);
+            }
+            Some(expr) => {
+                return Err(TypeError {
+                    expected: "Void".to_owned(),
+                    found: self.type_name(expr.typ),
+                })
+            }
+            None if self.return_type.is_some() => {
+                return Err(TypeError {
+                    expected: self.type_name(expected),
+                    found: "Void".to_owned(),
+                })
+            }
+            None => self.code.extend([Instr::Return, Instr::Nop]),
+        }
+        Ok(())
+    }
+
+    // appends instructions without any checks
+    #[inline]
+    pub fn emit(&mut self, instr: Instr) {
+        self.code.push(instr);
+    }
+
+    #[inline]
+    pub fn finish(self) -> Vec<Instr> {
+        self.code
+    }
+
+    fn check(&self, expected: ExprType, found: ExprType) -> Result<(), TypeError> {
+        match (self.resolve_name(expected), self.resolve_name(found)) {
+            (Some(lhs), Some(rhs)) if lhs != rhs => Err(TypeError {
+                expected: lhs.to_owned(),
+                found: rhs.to_owned(),
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    fn resolve_name(&self, typ: ExprType) -> Option<&'a str> {
+        match typ {
+            ExprType::Defined(index) => self.bundle.get_item(self.bundle.get_item(index)?.name()),
+            ExprType::Primitive(name) => Some(name),
+            ExprType::Unknown => None,
+        }
+    }
+
+    fn type_name(&self, typ: ExprType) -> String {
+        self.resolve_name(typ).unwrap_or("<unknown>").to_owned()
+    }
+
+    #[inline]
+    fn defined(typ: Option<TypeIndex>) -> ExprType {
+        typ.map_or(ExprType::Unknown, ExprType::Defined)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TypeError {
+    pub expected: String,
+    pub found: String,
+}
+
+impl fmt::Display for TypeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "expected {}, found {}", self.expected, self.found)
+    }
+}
+
+impl std::error::Error for TypeError {}
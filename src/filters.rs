@@ -0,0 +1,79 @@
+use crate::definition::{Class, Function};
+use crate::index::{ClassIndex, FunctionIndex};
+
+// narrows down iterators like ScriptBundle::functions by flags,
+// e.g. `bundle.functions().natives().statics()`
+pub trait FunctionFilters<'a, 'i: 'a>:
+    Iterator<Item = (FunctionIndex, &'a Function<'i>)> + Sized
+{
+    #[inline]
+    fn natives(self) -> impl Iterator<Item = (FunctionIndex, &'a Function<'i>)> {
+        self.filter(|(_, f)| f.flags().is_native())
+    }
+
+    #[inline]
+    fn callbacks(self) -> impl Iterator<Item = (FunctionIndex, &'a Function<'i>)> {
+        self.filter(|(_, f)| f.flags().is_callback())
+    }
+
+    #[inline]
+    fn statics(self) -> impl Iterator<Item = (FunctionIndex, &'a Function<'i>)> {
+        self.filter(|(_, f)| f.flags().is_static())
+    }
+
+    #[inline]
+    fn finals(self) -> impl Iterator<Item = (FunctionIndex, &'a Function<'i>)> {
+        self.filter(|(_, f)| f.flags().is_final())
+    }
+
+    #[inline]
+    fn execs(self) -> impl Iterator<Item = (FunctionIndex, &'a Function<'i>)> {
+        self.filter(|(_, f)| f.flags().is_exec())
+    }
+
+    // functions declared in classes
+    #[inline]
+    fn methods(self) -> impl Iterator<Item = (FunctionIndex, &'a Function<'i>)> {
+        self.filter(|(_, f)| f.class().is_some())
+    }
+
+    #[inline]
+    fn globals(self) -> impl Iterator<Item = (FunctionIndex, &'a Function<'i>)> {
+        self.filter(|(_, f)| f.class().is_none())
+    }
+}
+
+impl<'a, 'i: 'a, I> FunctionFilters<'a, 'i> for I where
+    I: Iterator<Item = (FunctionIndex, &'a Function<'i>)>
+{
+}
+
+// narrows down iterators like ScriptBundle::classes by flags
+pub trait ClassFilters<'a>: Iterator<Item = (ClassIndex, &'a Class)> + Sized {
+    #[inline]
+    fn natives(self) -> impl Iterator<Item = (ClassIndex, &'a Class)> {
+        self.filter(|(_, c)| c.flags().is_native())
+    }
+
+    #[inline]
+    fn abstracts(self) -> impl Iterator<Item = (ClassIndex, &'a Class)> {
+        self.filter(|(_, c)| c.flags().is_abstract())
+    }
+
+    #[inline]
+    fn finals(self) -> impl Iterator<Item = (ClassIndex, &'a Class)> {
+        self.filter(|(_, c)| c.flags().is_final())
+    }
+
+    #[inline]
+    fn structs(self) -> impl Iterator<Item = (ClassIndex, &'a Class)> {
+        self.filter(|(_, c)| c.flags().is_struct())
+    }
+
+    #[inline]
+    fn import_only(self) -> impl Iterator<Item = (ClassIndex, &'a Class)> {
+        self.filter(|(_, c)| c.flags().is_import_only())
+    }
+}
+
+impl<'a, I> ClassFilters<'a> for I where I: Iterator<Item = (ClassIndex, &'a Class)> {}
@@ -0,0 +1,373 @@
+use std::collections::HashMap;
+
+use crate::bundle::ScriptBundle;
+use crate::definition::Function;
+use crate::index::FunctionIndex;
+use crate::instr::{Instr, Operand};
+use crate::symbols;
+use crate::util::{fnv1a64, RandomState};
+
+const NGRAM_SIZE: usize = 3;
+// the minimum similarity for functions whose names differ to be matched
+const MIN_SIMILARITY: f32 = 0.6;
+
+// a set of hashed opcode n-grams, operands are included by name so the fingerprint does not
+// depend on how definitions are numbered in a particular bundle
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash)]
+pub struct Fingerprint {
+    hashes: Vec<u64>,
+}
+
+impl Fingerprint {
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.hashes.is_empty()
+    }
+
+    // jaccard similarity of the two n-gram sets, between 0 and 1
+    pub fn similarity(&self, other: &Fingerprint) -> f32 {
+        if self.hashes.is_empty() && other.hashes.is_empty() {
+            return 1.;
+        }
+        let (mut i, mut j, mut common) = (0, 0, 0usize);
+        while i < self.hashes.len() && j < other.hashes.len() {
+            match self.hashes[i].cmp(&other.hashes[j]) {
+                std::cmp::Ordering::Less => i += 1,
+                std::cmp::Ordering::Greater => j += 1,
+                std::cmp::Ordering::Equal => {
+                    common += 1;
+                    i += 1;
+                    j += 1;
+                }
+            }
+        }
+        let total = self.hashes.len() + other.hashes.len() - common;
+        common as f32 / total as f32
+    }
+}
+
+impl Function<'_> {
+    // instructions that can't be decoded end the fingerprint early
+    pub fn fingerprint(&self, bundle: &ScriptBundle<'_>) -> Fingerprint {
+        let code = self.body().code_iter().map_while(Result::ok).collect();
+        Self::fingerprint_of(code, bundle)
+    }
+
+    // the fingerprint of the canonicalized code, see FunctionBody::canonicalized, it's empty if
+    // the code can't be decoded
+    pub fn canonical_fingerprint(&self, bundle: &ScriptBundle<'_>) -> Fingerprint {
+        let code = self
+            .body()
+            .canonicalized()
+            .map(|code| code.code().to_vec())
+            .unwrap_or_default();
+        Self::fingerprint_of(code, bundle)
+    }
+
+    fn fingerprint_of(code: Vec<Instr>, bundle: &ScriptBundle<'_>) -> Fingerprint {
+        let tokens = semantic_tokens(code, bundle);
+        let mut hashes = if tokens.is_empty() {
+            vec![]
+        } else {
+            tokens
+                .windows(NGRAM_SIZE.min(tokens.len()))
+                .map(|gram| fnv1a64(&gram.join("\n")))
+                .collect::<Vec<_>>()
+        };
+        hashes.sort_unstable();
+        hashes.dedup();
+        Fingerprint { hashes }
+    }
+}
+
+// opcodes with their operands resolved to names, jump offsets and debug info are left out
+fn semantic_tokens(code: Vec<Instr>, bundle: &ScriptBundle<'_>) -> Vec<String> {
+    code.into_iter()
+        .map(|instr| {
+            let mut token = instr.name().to_owned();
+            for (_, operand) in instr.operands() {
+                let operand = match operand {
+                    Operand::Int(v) => v.to_string(),
+                    Operand::UInt(v) => v.to_string(),
+                    Operand::Float(v) => v.to_string(),
+                    Operand::CName(index) => bundle.get_item(index).unwrap_or_default().into(),
+                    Operand::String(index) => bundle.get_item(index).unwrap_or_default().into(),
+                    Operand::TweakDbId(index) => bundle.get_item(index).unwrap_or_default().into(),
+                    Operand::Resource(index) => bundle.get_item(index).unwrap_or_default().into(),
+                    Operand::Definition(index) => {
+                        symbols::qualified_name(bundle, index).unwrap_or_default()
+                    }
+                    Operand::Type(index) => bundle
+                        .get_item(index)
+                        .map(|typ| typ.display(bundle).to_string())
+                        .unwrap_or_default(),
+                    Operand::Offset(_) => continue,
+                };
+                token.push(' ');
+                token.push_str(&operand);
+            }
+            token
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FunctionMatch {
+    pub old: FunctionIndex,
+    pub new: FunctionIndex,
+    pub similarity: f32,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct MatchOptions {
+    canonical_code: bool,
+}
+
+impl MatchOptions {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // compares canonicalized code, so that functions recompiled by another compiler still match,
+    // see FunctionBody::canonicalized
+    #[inline]
+    pub fn with_canonical_code(mut self, canonical_code: bool) -> Self {
+        self.canonical_code = canonical_code;
+        self
+    }
+}
+
+// maps functions of the old bundle to their counterparts in the new one, functions are matched
+// by their qualified names first and the remaining ones by the most similar fingerprint
+#[inline]
+pub fn match_functions(old: &ScriptBundle<'_>, new: &ScriptBundle<'_>) -> Vec<FunctionMatch> {
+    match_functions_with(old, new, MatchOptions::default())
+}
+
+pub fn match_functions_with(
+    old: &ScriptBundle<'_>,
+    new: &ScriptBundle<'_>,
+    options: MatchOptions,
+) -> Vec<FunctionMatch> {
+    let fingerprints = |bundle: &ScriptBundle<'_>| {
+        bundle
+            .functions()
+            .map(|(index, fun)| {
+                let name = symbols::qualified_name(bundle, index.into()).unwrap_or_default();
+                let fingerprint = if options.canonical_code {
+                    fun.canonical_fingerprint(bundle)
+                } else {
+                    fun.fingerprint(bundle)
+                };
+                (index, name, fingerprint)
+            })
+            .collect::<Vec<_>>()
+    };
+    let old_funs = fingerprints(old);
+    let new_funs = fingerprints(new);
+
+    let mut matches = vec![];
+    let mut unmatched = vec![];
+    let by_name: HashMap<_, _, RandomState> = new_funs
+        .iter()
+        .enumerate()
+        .map(|(i, (_, name, _))| (name.clone(), i))
+        .collect();
+    let mut taken = vec![false; new_funs.len()];
+    for (index, name, fingerprint) in &old_funs {
+        match by_name.get(name) {
+            Some(&i) if !taken[i] => {
+                taken[i] = true;
+                matches.push(FunctionMatch {
+                    old: *index,
+                    new: new_funs[i].0,
+                    similarity: fingerprint.similarity(&new_funs[i].2),
+                });
+            }
+            _ => unmatched.push((*index, fingerprint)),
+        }
+    }
+
+    let mut candidates = vec![];
+    for (old_index, fingerprint) in unmatched.iter().filter(|(_, fp)| !fp.is_empty()) {
+        for (i, (new_index, _, other)) in new_funs.iter().enumerate() {
+            if taken[i] || other.is_empty() {
+                continue;
+            }
+            let similarity = fingerprint.similarity(other);
+            if similarity >= MIN_SIMILARITY {
+                candidates.push((similarity, *old_index, i, *new_index));
+            }
+        }
+    }
+    // greedily take the most similar pairs first
+    candidates.sort_by(|a, b| b.0.total_cmp(&a.0));
+    let mut old_taken = vec![];
+    for (similarity, old_index, i, new_index) in candidates {
+        if taken[i] || old_taken.contains(&old_index) {
+            continue;
+        }
+        taken[i] = true;
+        old_taken.push(old_index);
+        matches.push(FunctionMatch {
+            old: old_index,
+            new: new_index,
+            similarity,
+        });
+    }
+    matches
+}
+
+const WINNOW_KGRAM: usize = 5;
+const WINNOW_WINDOW: usize = 4;
+// hashes shared by more functions than this are too common to tell anything about similarity
+const MAX_POSTINGS: usize = 256;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CloneOptions {
+    min_instructions: usize,
+    threshold: f32,
+    canonical_code: bool,
+}
+
+impl CloneOptions {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // shorter functions are ignored, they tend to be similar by accident
+    #[inline]
+    pub fn with_min_instructions(mut self, min_instructions: usize) -> Self {
+        self.min_instructions = min_instructions;
+        self
+    }
+
+    // the minimum similarity of two bodies to be considered clones, between 0 and 1
+    #[inline]
+    pub fn with_threshold(mut self, threshold: f32) -> Self {
+        self.threshold = threshold;
+        self
+    }
+
+    // compares canonicalized code, see FunctionBody::canonicalized
+    #[inline]
+    pub fn with_canonical_code(mut self, canonical_code: bool) -> Self {
+        self.canonical_code = canonical_code;
+        self
+    }
+}
+
+impl Default for CloneOptions {
+    fn default() -> Self {
+        Self {
+            min_instructions: 16,
+            threshold: 0.9,
+            canonical_code: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct CloneMember {
+    // position of the bundle in the slice passed to `find_clones`
+    pub bundle: usize,
+    pub function: FunctionIndex,
+}
+
+// groups functions with near-identical bodies across the given bundles, bodies are compared
+// by winnowed hashes of their opcode sequences so operands like names and constants are ignored,
+// only groups with at least two members are returned
+pub fn find_clones(bundles: &[&ScriptBundle<'_>], options: CloneOptions) -> Vec<Vec<CloneMember>> {
+    let mut members = vec![];
+    let mut fingerprints = vec![];
+    for (bundle_index, bundle) in bundles.iter().enumerate() {
+        for (function, fun) in bundle.functions() {
+            let opcodes = if options.canonical_code {
+                fun.body()
+                    .canonicalized()
+                    .map(|code| code.code().iter().map(Instr::name).collect())
+                    .unwrap_or_default()
+            } else {
+                fun.body()
+                    .code_iter()
+                    .map_while(Result::ok)
+                    .map(|instr| instr.name())
+                    .collect::<Vec<_>>()
+            };
+            if opcodes.len() < options.min_instructions.max(WINNOW_KGRAM) {
+                continue;
+            }
+            members.push(CloneMember {
+                bundle: bundle_index,
+                function,
+            });
+            fingerprints.push(winnow(&opcodes));
+        }
+    }
+
+    let mut postings: HashMap<u64, Vec<usize>, RandomState> = HashMap::default();
+    for (i, fingerprint) in fingerprints.iter().enumerate() {
+        for &hash in &fingerprint.hashes {
+            postings.entry(hash).or_default().push(i);
+        }
+    }
+
+    let mut parents = (0..members.len()).collect::<Vec<_>>();
+    for (i, fingerprint) in fingerprints.iter().enumerate() {
+        let mut candidates = fingerprint
+            .hashes
+            .iter()
+            .filter_map(|hash| postings.get(hash))
+            .filter(|list| list.len() <= MAX_POSTINGS)
+            .flatten()
+            .copied()
+            .filter(|&j| j > i)
+            .collect::<Vec<_>>();
+        candidates.sort_unstable();
+        candidates.dedup();
+        for j in candidates {
+            if fingerprint.similarity(&fingerprints[j]) >= options.threshold {
+                let (a, b) = (find_root(&mut parents, i), find_root(&mut parents, j));
+                parents[a.max(b)] = a.min(b);
+            }
+        }
+    }
+
+    let mut groups: HashMap<usize, Vec<CloneMember>, RandomState> = HashMap::default();
+    for (i, &member) in members.iter().enumerate() {
+        let root = find_root(&mut parents, i);
+        groups.entry(root).or_default().push(member);
+    }
+    let mut groups = groups
+        .into_values()
+        .filter(|group| group.len() > 1)
+        .collect::<Vec<_>>();
+    groups.sort_unstable();
+    groups
+}
+
+// keeps the minimum hash of every window of k-gram hashes, see "Winnowing: Local Algorithms
+// for Document Fingerprinting" by Schleimer et al.
+fn winnow(opcodes: &[&str]) -> Fingerprint {
+    let grams = opcodes
+        .windows(WINNOW_KGRAM)
+        .map(|gram| fnv1a64(&gram.join(" ")))
+        .collect::<Vec<_>>();
+    let mut hashes = grams
+        .windows(WINNOW_WINDOW.min(grams.len()))
+        .filter_map(|window| window.iter().min().copied())
+        .collect::<Vec<_>>();
+    hashes.sort_unstable();
+    hashes.dedup();
+    Fingerprint { hashes }
+}
+
+fn find_root(parents: &mut [usize], mut i: usize) -> usize {
+    while parents[i] != i {
+        parents[i] = parents[parents[i]];
+        i = parents[i];
+    }
+    i
+}
@@ -0,0 +1,153 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::definition::{
+    ClassFlags, FieldFlags, FunctionFlags, LocalFlags, ParameterFlags, Visibility,
+};
+
+impl Visibility {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Public => "public",
+            Self::Protected => "protected",
+            Self::Private => "private",
+        }
+    }
+}
+
+impl fmt::Display for Visibility {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Visibility {
+    type Err = ParseFlagsError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "public" => Ok(Self::Public),
+            "protected" => Ok(Self::Protected),
+            "private" => Ok(Self::Private),
+            other => Err(ParseFlagsError::new("Visibility", other)),
+        }
+    }
+}
+
+// flag sets are written as names separated by `|`, e.g. `native|final`, an empty string has no flags
+macro_rules! flag_names {
+    ($ty:ident { $($name:literal => $get:ident / $set:ident),* $(,)? }) => {
+        impl fmt::Display for $ty {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                let mut sep = "";
+                $(
+                    if self.$get() {
+                        write!(f, "{sep}{}", $name)?;
+                        sep = "|";
+                    }
+                )*
+                let _ = sep;
+                Ok(())
+            }
+        }
+
+        impl FromStr for $ty {
+            type Err = ParseFlagsError;
+
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                let mut flags = Self::new();
+                for name in s.split('|').map(str::trim).filter(|name| !name.is_empty()) {
+                    match name {
+                        $($name => flags.$set(true),)*
+                        other => return Err(ParseFlagsError::new(stringify!($ty), other)),
+                    }
+                }
+                Ok(flags)
+            }
+        }
+
+        #[cfg(feature = "serde")]
+        impl serde::Serialize for $ty {
+            fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.collect_str(self)
+            }
+        }
+
+        #[cfg(feature = "serde")]
+        impl<'de> serde::Deserialize<'de> for $ty {
+            fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                let str = <std::borrow::Cow<'de, str>>::deserialize(deserializer)?;
+                str.parse().map_err(serde::de::Error::custom)
+            }
+        }
+    };
+}
+
+flag_names!(ClassFlags {
+    "native" => is_native / set_is_native,
+    "abstract" => is_abstract / set_is_abstract,
+    "final" => is_final / set_is_final,
+    "struct" => is_struct / set_is_struct,
+    "import_only" => is_import_only / set_is_import_only,
+    "test_only" => is_test_only / set_is_test_only,
+});
+
+flag_names!(FunctionFlags {
+    "static" => is_static / set_is_static,
+    "exec" => is_exec / set_is_exec,
+    "timer" => is_timer / set_is_timer,
+    "final" => is_final / set_is_final,
+    "native" => is_native / set_is_native,
+    "callback" => is_callback / set_is_callback,
+    "base_method" => has_base_method / set_has_base_method,
+    "implicit_cast" => is_implicit_cast / set_is_implicit_cast,
+    "const" => is_const / set_is_const,
+    "thread_safe" => is_thread_safe / set_is_thread_safe,
+    "quest" => is_quest / set_is_quest,
+});
+
+flag_names!(ParameterFlags {
+    "optional" => is_optional / set_is_optional,
+    "out" => is_out / set_is_out,
+    "short_circuit" => is_short_circuit / set_is_short_circuit,
+    "const" => is_const / set_is_const,
+});
+
+flag_names!(LocalFlags {
+    "const" => is_const / set_is_const,
+});
+
+flag_names!(FieldFlags {
+    "native" => is_native / set_is_native,
+    "editable" => is_editable / set_is_editable,
+    "inline" => is_inline / set_is_inline,
+    "const" => is_const / set_is_const,
+    "replicated" => is_replicated / set_is_replicated,
+    "instance_editable" => is_instance_editable / set_is_instance_editable,
+    "persistent" => is_persistent / set_is_persistent,
+    "test_only" => is_test_only / set_is_test_only,
+    "browsable" => is_browsable / set_is_browsable,
+});
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseFlagsError {
+    kind: &'static str,
+    name: String,
+}
+
+impl ParseFlagsError {
+    fn new(kind: &'static str, name: &str) -> Self {
+        Self {
+            kind,
+            name: name.to_owned(),
+        }
+    }
+}
+
+impl fmt::Display for ParseFlagsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unknown {} flag: {}", self.kind, self.name)
+    }
+}
+
+impl std::error::Error for ParseFlagsError {}
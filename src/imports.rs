@@ -0,0 +1,129 @@
+use std::collections::{BTreeMap, HashSet};
+use std::io;
+
+use crate::bundle::ScriptBundle;
+use crate::definition::Definition;
+use crate::filters::{ClassFilters, FunctionFilters};
+use crate::instr::Instr;
+use crate::symbols::{self, csv_field};
+
+// what a runtime has to supply for a bundle to run, i.e. the native classes and functions and
+// the virtual calls no script function can satisfy
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ImportTable {
+    pub imports: Vec<NativeImport>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NativeImport {
+    // zero for unresolved calls
+    pub index: u32,
+    pub kind: ImportKind,
+    // fully-qualified name for definitions, the called name for unresolved calls
+    pub name: String,
+    pub signature: String,
+    // the number of instructions referring to the import
+    pub uses: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "kebab-case")
+)]
+pub enum ImportKind {
+    Class,
+    Function,
+    // a virtual call to a name that no function of the bundle has
+    UnresolvedCall,
+}
+
+impl ImportKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Class => "class",
+            Self::Function => "function",
+            Self::UnresolvedCall => "unresolved-call",
+        }
+    }
+}
+
+impl ImportTable {
+    pub fn write_csv(&self, mut writer: impl io::Write) -> io::Result<()> {
+        writeln!(writer, "index,kind,name,signature,uses")?;
+        for import in &self.imports {
+            writeln!(
+                writer,
+                "{},{},{},{},{}",
+                import.index,
+                import.kind.name(),
+                csv_field(&import.name),
+                csv_field(&import.signature),
+                import.uses
+            )?;
+        }
+        Ok(())
+    }
+
+    #[cfg(all(feature = "serde", feature = "serde_json"))]
+    pub fn write_json(&self, writer: impl io::Write) -> serde_json::Result<()> {
+        serde_json::to_writer(writer, self)
+    }
+}
+
+impl ScriptBundle<'_> {
+    // natives are listed whether or not they're used, since the runtime is expected to provide
+    // every class and function the bundle declares
+    pub fn import_table(&self) -> ImportTable {
+        let lookup = self.symbol_lookup();
+        let import =
+            |index: u32, kind: ImportKind, def: &Definition<'_>, uses: usize| NativeImport {
+                index,
+                kind,
+                name: symbols::qualified_name(self, index).unwrap_or_default(),
+                signature: symbols::signature(self, def),
+                uses: uses as u32,
+            };
+
+        let mut imports = vec![];
+        for (index, _) in self.classes().natives() {
+            if let Some(def) = self.definition(index.into()) {
+                let uses = lookup.references(index).count();
+                imports.push(import(index.into(), ImportKind::Class, def, uses));
+            }
+        }
+        for (index, _) in self.functions().natives() {
+            if let Some(def) = self.definition(index.into()) {
+                let uses = lookup.references(index).count();
+                imports.push(import(index.into(), ImportKind::Function, def, uses));
+            }
+        }
+
+        let defined = self
+            .functions()
+            .map(|(_, fun)| fun.name())
+            .collect::<HashSet<_>>();
+        let mut unresolved = BTreeMap::new();
+        for (_, fun) in self.functions() {
+            for instr in fun.body().code_iter().map_while(Result::ok) {
+                if let Instr::InvokeVirtual { function, .. } = instr {
+                    if !defined.contains(&function) {
+                        *unresolved.entry(function).or_insert(0) += 1;
+                    }
+                }
+            }
+        }
+        imports.extend(unresolved.into_iter().map(|(name, uses)| NativeImport {
+            index: 0,
+            kind: ImportKind::UnresolvedCall,
+            name: self.get_item(name).unwrap_or_default().to_owned(),
+            signature: String::new(),
+            uses,
+        }));
+
+        ImportTable { imports }
+    }
+}
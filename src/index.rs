@@ -1,11 +1,14 @@
 use std::hash::Hash;
 use std::marker::PhantomData;
 use std::num::NonZeroU32;
+use std::ops::Range;
 use std::{fmt, mem};
 
 use byte::ctx::Endianess;
 use byte::{Measure, TryRead, TryWrite};
 
+use crate::bundle::{PoolItemIndex, ScriptBundle};
+
 pub type CNameIndex = PoolIndex<types::CName>;
 pub type TweakDbIndex = PoolIndex<types::TweakDbId>;
 pub type ResourceIndex = PoolIndex<types::Resource>;
@@ -21,6 +24,41 @@ pub type LocalIndex = NzPoolIndex<types::Local>;
 pub type FieldIndex = NzPoolIndex<types::Field>;
 pub type SourceFileIndex = NzPoolIndex<types::SourceFile>;
 
+// the integer type indices are stored as in the file, sizes and counts are converted through it
+// so that they're range checked instead of being truncated
+pub trait RawIndex: Copy + Ord + fmt::Debug {
+    const MAX: Self;
+
+    fn from_usize(value: usize) -> Option<Self>;
+    fn to_usize(self) -> usize;
+}
+
+impl RawIndex for u32 {
+    const MAX: Self = u32::MAX;
+
+    #[inline]
+    fn from_usize(value: usize) -> Option<Self> {
+        value.try_into().ok()
+    }
+
+    #[inline]
+    fn to_usize(self) -> usize {
+        self as usize
+    }
+}
+
+// returned when a pool has no index left to give to a new item
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IndexOverflow;
+
+impl fmt::Display for IndexOverflow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("index exceeds the range of the format")
+    }
+}
+
+impl std::error::Error for IndexOverflow {}
+
 #[repr(transparent)]
 pub struct PoolIndex<A>(u32, PhantomData<A>);
 
@@ -31,11 +69,31 @@ impl<A> PoolIndex<A> {
     pub(crate) const fn new(index: u32) -> Self {
         PoolIndex(index, PhantomData)
     }
+
+    #[inline]
+    pub fn successor(self) -> Option<Self> {
+        self.0.checked_add(1).map(Self::new)
+    }
+
+    #[inline]
+    pub fn predecessor(self) -> Option<Self> {
+        self.0.checked_sub(1).map(Self::new)
+    }
 }
 
-impl<A> fmt::Debug for PoolIndex<A> {
+impl<A: types::Kind> PoolIndex<A> {
+    #[inline]
+    pub fn display_with<'a, 'i>(self, bundle: &'a ScriptBundle<'i>) -> DisplayWith<'a, 'i, Self> {
+        DisplayWith {
+            index: self,
+            bundle,
+        }
+    }
+}
+
+impl<A: types::Kind> fmt::Debug for PoolIndex<A> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        f.debug_tuple("PoolIndex").field(&self.0).finish()
+        write!(f, "{}Index({})", A::NAME, self.0)
     }
 }
 
@@ -78,9 +136,10 @@ impl<A> Hash for PoolIndex<A> {
     }
 }
 
-impl<A> fmt::Display for PoolIndex<A> {
+impl<A: types::Kind> fmt::Display for PoolIndex<A> {
+    #[inline]
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}", self.0)
+        fmt::Debug::fmt(self, f)
     }
 }
 
@@ -131,11 +190,43 @@ impl<A> NzPoolIndex<A> {
             None => None,
         }
     }
+
+    // None for the reserved first index and for positions out of the range of indices
+    #[inline]
+    pub(crate) fn from_usize(index: usize) -> Option<Self> {
+        Self::new(u32::from_usize(index)?)
+    }
+
+    #[inline]
+    pub fn successor(self) -> Option<Self> {
+        self.0.checked_add(1).map(|i| NzPoolIndex(i, PhantomData))
+    }
+
+    // returns None for the first index since zero is not a valid definition index
+    #[inline]
+    pub fn predecessor(self) -> Option<Self> {
+        Self::new(self.0.get() - 1)
+    }
+
+    #[inline]
+    pub(crate) fn map(self, f: impl FnOnce(u32) -> u32) -> Self {
+        Self::new(f(self.0.get())).expect("definition index set to zero")
+    }
+}
+
+impl<A: types::Kind> NzPoolIndex<A> {
+    #[inline]
+    pub fn display_with<'a, 'i>(self, bundle: &'a ScriptBundle<'i>) -> DisplayWith<'a, 'i, Self> {
+        DisplayWith {
+            index: self,
+            bundle,
+        }
+    }
 }
 
-impl<A> fmt::Debug for NzPoolIndex<A> {
+impl<A: types::Kind> fmt::Debug for NzPoolIndex<A> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        f.debug_tuple("DefIndex").field(&self.0).finish()
+        write!(f, "{}Index({})", A::NAME, self.0)
     }
 }
 
@@ -185,9 +276,10 @@ impl<A> Default for NzPoolIndex<A> {
     }
 }
 
-impl<A> fmt::Display for NzPoolIndex<A> {
+impl<A: types::Kind> fmt::Display for NzPoolIndex<A> {
+    #[inline]
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}", self.0)
+        fmt::Debug::fmt(self, f)
     }
 }
 
@@ -223,32 +315,146 @@ impl<A, Ctx> Measure<Ctx> for NzPoolIndex<A> {
     }
 }
 
+// a range of definition indices, created from `start..end` or ScriptBundle::index_range
+pub struct IndexRange<A> {
+    start: u32,
+    end: u32,
+    phantom: PhantomData<A>,
+}
+
+impl<A> IndexRange<A> {
+    #[inline]
+    pub(crate) fn new(start: u32, end: u32) -> Self {
+        Self {
+            start: start.max(1),
+            end: end.max(1),
+            phantom: PhantomData,
+        }
+    }
+
+    #[inline]
+    pub fn contains(&self, index: NzPoolIndex<A>) -> bool {
+        (self.start..self.end).contains(&index.0.get())
+    }
+}
+
+impl<A> From<Range<NzPoolIndex<A>>> for IndexRange<A> {
+    #[inline]
+    fn from(range: Range<NzPoolIndex<A>>) -> Self {
+        Self::new(range.start.into(), range.end.into())
+    }
+}
+
+impl<A> Iterator for IndexRange<A> {
+    type Item = NzPoolIndex<A>;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.start >= self.end {
+            return None;
+        }
+        let index = NzPoolIndex::new(self.start);
+        self.start += 1;
+        index
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.end.saturating_sub(self.start) as usize;
+        (len, Some(len))
+    }
+}
+
+impl<A> DoubleEndedIterator for IndexRange<A> {
+    #[inline]
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.start >= self.end {
+            return None;
+        }
+        self.end -= 1;
+        NzPoolIndex::new(self.end)
+    }
+}
+
+impl<A> ExactSizeIterator for IndexRange<A> {}
+
+impl<A> Clone for IndexRange<A> {
+    #[inline]
+    fn clone(&self) -> Self {
+        Self::new(self.start, self.end)
+    }
+}
+
+impl<A> fmt::Debug for IndexRange<A> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "IndexRange({}..{})", self.start, self.end)
+    }
+}
+
+// pairs an index with a bundle to print the name it resolves to
+#[derive(Debug)]
+pub struct DisplayWith<'a, 'i, I> {
+    index: I,
+    bundle: &'a ScriptBundle<'i>,
+}
+
+impl<'i, A> fmt::Display for DisplayWith<'_, 'i, PoolIndex<A>>
+where
+    A: types::Kind,
+    PoolIndex<A>: PoolItemIndex<'i, Output = str>,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.bundle.get_item(self.index) {
+            Some(str) => write!(f, "{}Index({}: {})", A::NAME, self.index.0, str),
+            None => write!(f, "{:?}", self.index),
+        }
+    }
+}
+
+impl<A: types::Kind> fmt::Display for DisplayWith<'_, '_, NzPoolIndex<A>> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = self
+            .bundle
+            .definition(self.index.into())
+            .and_then(|def| self.bundle.get_item(def.name()));
+        match name {
+            Some(name) => write!(f, "{}Index({}: {})", A::NAME, self.index.0, name),
+            None => write!(f, "{:?}", self.index),
+        }
+    }
+}
+
 pub mod types {
-    #[derive(Debug, Default)]
-    pub struct CName;
-    #[derive(Debug, Default)]
-    pub struct TweakDbId;
-    #[derive(Debug, Default)]
-    pub struct Resource;
-    #[derive(Debug, Default)]
-    pub struct String;
-
-    #[derive(Debug, Default)]
-    pub struct Type;
-    #[derive(Debug, Default)]
-    pub struct Class;
-    #[derive(Debug, Default)]
-    pub struct EnumValue;
-    #[derive(Debug, Default)]
-    pub struct Enum;
-    #[derive(Debug, Default)]
-    pub struct Function;
-    #[derive(Debug, Default)]
-    pub struct Parameter;
-    #[derive(Debug, Default)]
-    pub struct Local;
-    #[derive(Debug, Default)]
-    pub struct Field;
-    #[derive(Debug, Default)]
-    pub struct SourceFile;
+    pub trait Kind {
+        const NAME: &'static str;
+    }
+
+    macro_rules! define_kinds {
+        ($($ty:ident => $name:literal),* $(,)?) => {
+            $(
+                #[derive(Debug, Default)]
+                pub struct $ty;
+
+                impl Kind for $ty {
+                    const NAME: &'static str = $name;
+                }
+            )*
+        };
+    }
+
+    define_kinds!(
+        CName => "CName",
+        TweakDbId => "TweakDb",
+        Resource => "Resource",
+        String => "String",
+        Type => "Type",
+        Class => "Class",
+        EnumValue => "EnumValue",
+        Enum => "Enum",
+        Function => "Function",
+        Parameter => "Parameter",
+        Local => "Local",
+        Field => "Field",
+        SourceFile => "SourceFile",
+    );
 }
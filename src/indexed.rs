@@ -0,0 +1,177 @@
+use std::collections::HashMap;
+use std::ops;
+
+use crate::bundle::{PoolItemIndex, PoolItemIndexMut, ScriptBundle, StringPool};
+use crate::definition::{Definition, DefinitionIndex};
+use crate::index::{self, CNameIndex, NzPoolIndex};
+use crate::journal::{Edit, EditMismatch};
+use crate::rename::RenameError;
+use crate::util::RandomState;
+
+#[derive(Debug)]
+pub struct IndexedBundle<'i> {
+    bundle: ScriptBundle<'i>,
+    by_name: HashMap<CNameIndex, Vec<u32>, RandomState>,
+    names: Vec<Option<CNameIndex>>,
+}
+
+impl<'i> IndexedBundle<'i> {
+    pub fn new(bundle: ScriptBundle<'i>) -> Self {
+        let mut indexed = IndexedBundle {
+            bundle,
+            by_name: HashMap::default(),
+            names: vec![],
+        };
+        for index in 0..indexed.bundle.definition_count() {
+            indexed.reindex(index);
+        }
+        indexed
+    }
+
+    #[inline]
+    pub fn into_inner(self) -> ScriptBundle<'i> {
+        self.bundle
+    }
+
+    pub fn lookup(&self, name: &str) -> &[u32] {
+        self.bundle
+            .cnames()
+            .get_index(name)
+            .map_or(&[], |name| self.lookup_by_index(name))
+    }
+
+    pub fn lookup_by_index(&self, name: CNameIndex) -> &[u32] {
+        self.by_name.get(&name).map_or(&[], Vec::as_slice)
+    }
+
+    pub fn find<A>(&self, name: &str) -> impl Iterator<Item = NzPoolIndex<A>> + use<'_, 'i, A>
+    where
+        NzPoolIndex<A>: PoolItemIndex<'i>,
+    {
+        self.lookup(name)
+            .iter()
+            .filter_map(|&index| NzPoolIndex::new(index))
+            .filter(|&index: &NzPoolIndex<A>| self.bundle.get_item(index).is_some())
+    }
+
+    #[inline]
+    pub fn find_first<A>(&self, name: &str) -> Option<NzPoolIndex<A>>
+    where
+        NzPoolIndex<A>: PoolItemIndex<'i>,
+    {
+        self.find(name).next()
+    }
+
+    pub fn define<A>(&mut self, def: A) -> NzPoolIndex<A::Index>
+    where
+        A: DefinitionIndex<'i>,
+    {
+        let index = self.bundle.define(def);
+        self.reindex(index.into());
+        index
+    }
+
+    pub fn modify<I>(&mut self, index: I, f: impl FnOnce(&mut I::Output)) -> bool
+    where
+        I: PoolItemIndexMut<'i> + Into<u32> + Copy,
+    {
+        let modified = self.bundle.modify(index, f);
+        self.reindex(index.into());
+        modified
+    }
+
+    // names are only ever appended to the pool, so the ones already indexed keep their indices
+    #[inline]
+    pub fn cnames_mut(&mut self) -> &mut StringPool<'i, index::types::CName> {
+        self.bundle.cnames_mut()
+    }
+
+    pub fn apply_renames<'a>(
+        &mut self,
+        renames: impl IntoIterator<Item = (&'a str, &'a str)>,
+    ) -> Result<Vec<u32>, RenameError> {
+        let modified = self.bundle.apply_renames(renames)?;
+        for &index in &modified {
+            self.reindex(index);
+        }
+        Ok(modified)
+    }
+
+    pub fn remove<A>(&mut self, index: NzPoolIndex<A>) -> Option<Definition<'i>> {
+        let removed = self.bundle.remove(index);
+        self.reindex(index.into());
+        removed
+    }
+
+    pub fn undo(&mut self) -> Result<bool, EditMismatch> {
+        let Some(index) = self.last_edit() else {
+            return Ok(false);
+        };
+        self.bundle.undo()?;
+        self.reindex(index);
+        Ok(true)
+    }
+
+    pub fn redo(&mut self) -> Result<bool, EditMismatch> {
+        let Some(index) = self
+            .bundle
+            .journal()
+            .and_then(|journal| journal.undone().last())
+            .map(Edit::index)
+        else {
+            return Ok(false);
+        };
+        self.bundle.redo()?;
+        self.reindex(index);
+        Ok(true)
+    }
+
+    #[inline]
+    pub fn enable_journal(&mut self) {
+        self.bundle.enable_journal();
+    }
+
+    fn last_edit(&self) -> Option<u32> {
+        self.bundle
+            .journal()
+            .and_then(|journal| journal.edits().last())
+            .map(Edit::index)
+    }
+
+    fn reindex(&mut self, index: u32) {
+        let pos = index as usize;
+        if let Some(&Some(old)) = self.names.get(pos) {
+            if let Some(indices) = self.by_name.get_mut(&old) {
+                indices.retain(|&i| i != index);
+            }
+        }
+        let new = self
+            .bundle
+            .definition(index)
+            .filter(|def| **def != Definition::UNDEFINED)
+            .map(Definition::name);
+        if self.names.len() <= pos {
+            self.names.resize(pos + 1, None);
+        }
+        self.names[pos] = new;
+        if let Some(name) = new {
+            self.by_name.entry(name).or_default().push(index);
+        }
+    }
+}
+
+impl<'i> ops::Deref for IndexedBundle<'i> {
+    type Target = ScriptBundle<'i>;
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        &self.bundle
+    }
+}
+
+impl<'i> From<ScriptBundle<'i>> for IndexedBundle<'i> {
+    #[inline]
+    fn from(bundle: ScriptBundle<'i>) -> Self {
+        Self::new(bundle)
+    }
+}
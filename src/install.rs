@@ -0,0 +1,94 @@
+use std::path::{Path, PathBuf};
+use std::{fs, io};
+
+use crate::util;
+
+const CACHE_DIR: &str = "r6/cache";
+const CACHE_FILE: &str = "final.redscripts";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum CacheKind {
+    // r6/cache/modded/final.redscripts, written by REDmod deployment and preferred by the game
+    Modded,
+    // r6/cache/final.redscripts
+    Base,
+    // a backup of the original cache made by the script compiler, final.redscripts.bk
+    Backup,
+    // any other `*_final.redscripts` file in the cache directory
+    Variant,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CacheLocation {
+    pub path: PathBuf,
+    pub kind: CacheKind,
+}
+
+impl CacheLocation {
+    #[inline]
+    pub fn read(&self) -> io::Result<Vec<u8>> {
+        fs::read(util::extended_path(&self.path))
+    }
+}
+
+// script caches found in a game installation
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GameCaches {
+    caches: Vec<CacheLocation>,
+}
+
+impl GameCaches {
+    pub fn discover(game_dir: impl AsRef<Path>) -> io::Result<Self> {
+        let dir = game_dir.as_ref().join(CACHE_DIR);
+        let mut caches = vec![];
+
+        let modded = dir.join("modded").join(CACHE_FILE);
+        if modded.is_file() {
+            caches.push(CacheLocation {
+                path: modded,
+                kind: CacheKind::Modded,
+            });
+        }
+        for entry in fs::read_dir(&dir)? {
+            let path = entry?.path();
+            let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+                continue;
+            };
+            let kind = match name {
+                CACHE_FILE => CacheKind::Base,
+                "final.redscripts.bk" => CacheKind::Backup,
+                _ if name.ends_with("_final.redscripts") => CacheKind::Variant,
+                _ => continue,
+            };
+            if path.is_file() {
+                caches.push(CacheLocation { path, kind });
+            }
+        }
+        caches.sort_by(|a, b| a.kind.cmp(&b.kind).then_with(|| a.path.cmp(&b.path)));
+        Ok(Self { caches })
+    }
+
+    #[inline]
+    pub fn caches(&self) -> &[CacheLocation] {
+        &self.caches
+    }
+
+    // the cache the game is going to load
+    #[inline]
+    pub fn active(&self) -> Option<&CacheLocation> {
+        self.caches
+            .first()
+            .filter(|cache| matches!(cache.kind, CacheKind::Modded | CacheKind::Base))
+    }
+
+    // the unmodified cache shipped with the game, if it can be determined
+    pub fn original(&self) -> Option<&CacheLocation> {
+        self.find(CacheKind::Backup)
+            .or_else(|| self.find(CacheKind::Base))
+    }
+
+    #[inline]
+    pub fn find(&self, kind: CacheKind) -> Option<&CacheLocation> {
+        self.caches.iter().find(|cache| cache.kind == kind)
+    }
+}
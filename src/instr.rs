@@ -1,240 +1,398 @@
+use std::collections::{BTreeMap, HashMap};
 use std::ops::{Add, Sub};
 
 use byte::{Measure, TryRead, TryWrite};
 
+use crate::definition::CodeError;
 use crate::util::Prefixed;
 use crate::{
     CNameIndex, ClassIndex, EnumIndex, EnumValueIndex, FieldIndex, FunctionIndex, LocalIndex,
     ParameterIndex, ResourceIndex, StringIndex, TweakDbIndex, TypeIndex,
 };
 
-#[derive(Debug, Clone, PartialEq, TryRead, TryWrite, Measure)]
-#[byte(tag_type = u8)]
-pub enum Instr<Loc = Offset> {
-    #[byte(tag = 0x00)]
-    Nop,
-    #[byte(tag = 0x01)]
-    Null,
-    #[byte(tag = 0x02)]
-    I32One,
-    #[byte(tag = 0x03)]
-    I32Zero,
-    #[byte(tag = 0x04)]
-    I8Const(i8),
-    #[byte(tag = 0x05)]
-    I16Const(i16),
-    #[byte(tag = 0x06)]
-    I32Const(i32),
-    #[byte(tag = 0x07)]
-    I64Const(i64),
-    #[byte(tag = 0x08)]
-    U8Const(u8),
-    #[byte(tag = 0x09)]
-    U16Const(u16),
-    #[byte(tag = 0x0A)]
-    U32Const(u32),
-    #[byte(tag = 0x0B)]
-    U64Const(u64),
-    #[byte(tag = 0x0C)]
-    F32Const(f32),
-    #[byte(tag = 0x0D)]
-    F64Const(f64),
-    #[byte(tag = 0x0E)]
-    CNameConst(CNameIndex),
-    #[byte(tag = 0x0F)]
-    EnumConst {
-        enum_: EnumIndex,
-        value: EnumValueIndex,
-    },
-    #[byte(tag = 0x10)]
-    StringConst(StringIndex),
-    #[byte(tag = 0x11)]
-    TweakDbIdConst(TweakDbIndex),
-    #[byte(tag = 0x12)]
-    ResourceConst(ResourceIndex),
-    #[byte(tag = 0x13)]
-    TrueConst,
-    #[byte(tag = 0x14)]
-    FalseConst,
-    #[byte(tag = 0x15)]
-    Breakpoint(Box<Breakpoint>),
-    #[byte(tag = 0x16)]
-    Assign,
-    #[byte(tag = 0x17)]
-    Target(Loc),
-    #[byte(tag = 0x18)]
-    Local(LocalIndex),
-    #[byte(tag = 0x19)]
-    Param(ParameterIndex),
-    #[byte(tag = 0x1A)]
-    ObjectField(FieldIndex),
-    #[byte(tag = 0x1B)]
-    ExternalVar,
-    #[byte(tag = 0x1C)]
-    Switch(Switch<Loc>),
-    #[byte(tag = 0x1D)]
-    SwitchLabel(SwitchLabel<Loc>),
-    #[byte(tag = 0x1E)]
-    SwitchDefault,
-    #[byte(tag = 0x1F)]
-    Jump(Jump<Loc>),
-    #[byte(tag = 0x20)]
-    JumpIfFalse(Jump<Loc>),
-    #[byte(tag = 0x21)]
-    Skip(Jump<Loc>),
-    #[byte(tag = 0x22)]
-    Conditional(Conditional<Loc>),
-    #[byte(tag = 0x23)]
-    Construct { arg_count: u8, class: ClassIndex },
-    #[byte(tag = 0x24)]
-    InvokeStatic {
-        exit: Jump<Loc>,
-        line: u16,
-        function: FunctionIndex,
-        flags: u16,
-    },
-    #[byte(tag = 0x25)]
-    InvokeVirtual {
-        exit: Jump<Loc>,
-        line: u16,
-        function: CNameIndex,
-        flags: u16,
-    },
-    #[byte(tag = 0x26)]
-    ParamEnd,
-    #[byte(tag = 0x27)]
-    Return,
-    #[byte(tag = 0x28)]
-    StructField(FieldIndex),
-    #[byte(tag = 0x29)]
-    Context(Jump<Loc>),
-    #[byte(tag = 0x2A)]
-    Equals(TypeIndex),
-    #[byte(tag = 0x2B)]
-    RefStringEqualsString(TypeIndex),
-    #[byte(tag = 0x2C)]
-    StringEqualsRefString(TypeIndex),
-    #[byte(tag = 0x2D)]
-    NotEquals(TypeIndex),
-    #[byte(tag = 0x2E)]
-    RefStringNotEqualsString(TypeIndex),
-    #[byte(tag = 0x2F)]
-    StringNotEqualsRefString(TypeIndex),
-    #[byte(tag = 0x30)]
-    New(ClassIndex),
-    #[byte(tag = 0x31)]
-    Delete,
-    #[byte(tag = 0x32)]
-    This,
-    #[byte(tag = 0x33)]
-    Profile(Box<Profile>),
-    #[byte(tag = 0x34)]
-    ArrayClear(TypeIndex),
-    #[byte(tag = 0x35)]
-    ArraySize(TypeIndex),
-    #[byte(tag = 0x36)]
-    ArrayResize(TypeIndex),
-    #[byte(tag = 0x37)]
-    ArrayFindFirst(TypeIndex),
-    #[byte(tag = 0x38)]
-    ArrayFindFirstFast(TypeIndex),
-    #[byte(tag = 0x39)]
-    ArrayFindLast(TypeIndex),
-    #[byte(tag = 0x3A)]
-    ArrayFindLastFast(TypeIndex),
-    #[byte(tag = 0x3B)]
-    ArrayContains(TypeIndex),
-    #[byte(tag = 0x3C)]
-    ArrayContainsFast(TypeIndex),
-    #[byte(tag = 0x3D)]
-    ArrayCount(TypeIndex),
-    #[byte(tag = 0x3E)]
-    ArrayCountFast(TypeIndex),
-    #[byte(tag = 0x3F)]
-    ArrayPush(TypeIndex),
-    #[byte(tag = 0x40)]
-    ArrayPop(TypeIndex),
-    #[byte(tag = 0x41)]
-    ArrayInsert(TypeIndex),
-    #[byte(tag = 0x42)]
-    ArrayRemove(TypeIndex),
-    #[byte(tag = 0x43)]
-    ArrayRemoveFast(TypeIndex),
-    #[byte(tag = 0x44)]
-    ArrayGrow(TypeIndex),
-    #[byte(tag = 0x45)]
-    ArrayErase(TypeIndex),
-    #[byte(tag = 0x46)]
-    ArrayEraseFast(TypeIndex),
-    #[byte(tag = 0x47)]
-    ArrayLast(TypeIndex),
-    #[byte(tag = 0x48)]
-    ArrayElement(TypeIndex),
-    #[byte(tag = 0x49)]
-    ArraySort(TypeIndex),
-    #[byte(tag = 0x4A)]
-    ArraySortByPredicate(TypeIndex),
-    #[byte(tag = 0x4B)]
-    StaticArraySize(TypeIndex),
-    #[byte(tag = 0x4C)]
-    StaticArrayFindFirst(TypeIndex),
-    #[byte(tag = 0x4D)]
-    StaticArrayFindFirstFast(TypeIndex),
-    #[byte(tag = 0x4E)]
-    StaticArrayFindLast(TypeIndex),
-    #[byte(tag = 0x4F)]
-    StaticArrayFindLastFast(TypeIndex),
-    #[byte(tag = 0x50)]
-    StaticArrayContains(TypeIndex),
-    #[byte(tag = 0x51)]
-    StaticArrayContainsFast(TypeIndex),
-    #[byte(tag = 0x52)]
-    StaticArrayCount(TypeIndex),
-    #[byte(tag = 0x53)]
-    StaticArrayCountFast(TypeIndex),
-    #[byte(tag = 0x54)]
-    StaticArrayLast(TypeIndex),
-    #[byte(tag = 0x55)]
-    StaticArrayElement(TypeIndex),
-    #[byte(tag = 0x56)]
-    RefToBool,
-    #[byte(tag = 0x57)]
-    WeakRefToBool,
-    #[byte(tag = 0x58)]
-    EnumToI32 { enum_type: TypeIndex, size: u8 },
-    #[byte(tag = 0x59)]
-    I32ToEnum { enum_type: TypeIndex, size: u8 },
-    #[byte(tag = 0x5A)]
-    DynamicCast { class: ClassIndex, flags: u8 },
-    #[byte(tag = 0x5B)]
-    ToString(TypeIndex),
-    #[byte(tag = 0x5C)]
-    ToVariant(TypeIndex),
-    #[byte(tag = 0x5D)]
-    FromVariant(TypeIndex),
-    #[byte(tag = 0x5E)]
-    VariantIsDefined,
-    #[byte(tag = 0x5F)]
-    VariantIsRef,
-    #[byte(tag = 0x60)]
-    VariantIsArray,
-    #[byte(tag = 0x61)]
-    VariantTypeName,
-    #[byte(tag = 0x62)]
-    VariantToString,
-    #[byte(tag = 0x63)]
-    WeakRefToRef,
-    #[byte(tag = 0x64)]
-    RefToWeakRef,
-    #[byte(tag = 0x65)]
-    WeakRefNull,
-    #[byte(tag = 0x66)]
-    AsRef(TypeIndex),
-    #[byte(tag = 0x67)]
-    Deref(TypeIndex),
+// matches the instructions that take nothing but a type and binds the type
+macro_rules! type_operand {
+    ($typ:pat) => {
+        $crate::Instr::Equals($typ)
+            | $crate::Instr::RefStringEqualsString($typ)
+            | $crate::Instr::StringEqualsRefString($typ)
+            | $crate::Instr::NotEquals($typ)
+            | $crate::Instr::RefStringNotEqualsString($typ)
+            | $crate::Instr::StringNotEqualsRefString($typ)
+            | $crate::Instr::ArrayClear($typ)
+            | $crate::Instr::ArraySize($typ)
+            | $crate::Instr::ArrayResize($typ)
+            | $crate::Instr::ArrayFindFirst($typ)
+            | $crate::Instr::ArrayFindFirstFast($typ)
+            | $crate::Instr::ArrayFindLast($typ)
+            | $crate::Instr::ArrayFindLastFast($typ)
+            | $crate::Instr::ArrayContains($typ)
+            | $crate::Instr::ArrayContainsFast($typ)
+            | $crate::Instr::ArrayCount($typ)
+            | $crate::Instr::ArrayCountFast($typ)
+            | $crate::Instr::ArrayPush($typ)
+            | $crate::Instr::ArrayPop($typ)
+            | $crate::Instr::ArrayInsert($typ)
+            | $crate::Instr::ArrayRemove($typ)
+            | $crate::Instr::ArrayRemoveFast($typ)
+            | $crate::Instr::ArrayGrow($typ)
+            | $crate::Instr::ArrayErase($typ)
+            | $crate::Instr::ArrayEraseFast($typ)
+            | $crate::Instr::ArrayLast($typ)
+            | $crate::Instr::ArrayElement($typ)
+            | $crate::Instr::ArraySort($typ)
+            | $crate::Instr::ArraySortByPredicate($typ)
+            | $crate::Instr::StaticArraySize($typ)
+            | $crate::Instr::StaticArrayFindFirst($typ)
+            | $crate::Instr::StaticArrayFindFirstFast($typ)
+            | $crate::Instr::StaticArrayFindLast($typ)
+            | $crate::Instr::StaticArrayFindLastFast($typ)
+            | $crate::Instr::StaticArrayContains($typ)
+            | $crate::Instr::StaticArrayContainsFast($typ)
+            | $crate::Instr::StaticArrayCount($typ)
+            | $crate::Instr::StaticArrayCountFast($typ)
+            | $crate::Instr::StaticArrayLast($typ)
+            | $crate::Instr::StaticArrayElement($typ)
+            | $crate::Instr::ToString($typ)
+            | $crate::Instr::ToVariant($typ)
+            | $crate::Instr::FromVariant($typ)
+            | $crate::Instr::AsRef($typ)
+            | $crate::Instr::Deref($typ)
+    };
+}
+
+// expands to the first token, binds an operand in a pattern that repeats over its type
+macro_rules! first {
+    ($first:tt $($rest:tt)*) => {
+        $first
+    };
+}
+
+// converts an operand with MapLocations, a bare location is relative to the instruction itself
+macro_rules! map_operand {
+    ($f:ident, $val:ident, Loc) => {
+        $f($val, 0)
+    };
+    ($f:ident, $val:ident, $($ty:tt)+) => {
+        MapLocations::map_locations($val, &mut $f)
+    };
+}
+
+// declares the instructions along with their opcodes, the opcode tables are indexed by the tags,
+// so the instructions have to be listed in the order of their tags
+macro_rules! instructions {
+    ($($tag:tt => $name:ident $(($($ty:tt)+))? $({ $($field:ident: $fty:ty),* })?,)*) => {
+        #[derive(Debug, Clone, PartialEq, TryRead, TryWrite, Measure)]
+        #[byte(tag_type = u8)]
+        pub enum Instr<Loc = Offset> {
+            $(
+                #[byte(tag = $tag)]
+                $name $(($($ty)+))? $({ $($field: $fty),* })?,
+            )*
+        }
+
+        // the opcodes of the instructions, the discriminants are the tags used in bytecode
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+        #[repr(u8)]
+        pub enum Opcode {
+            $($name = $tag,)*
+        }
+
+        impl Opcode {
+            pub const ALL: [Self; [$(stringify!($name)),*].len()] = [$(Opcode::$name),*];
+        }
+
+        impl Instr {
+            // names of the opcodes indexed by their tags
+            pub const NAMES: [&'static str; Opcode::ALL.len()] = [$(stringify!($name)),*];
+        }
+
+        impl<L> Instr<L> {
+            pub fn opcode(&self) -> Opcode {
+                match self {
+                    $(Instr::$name { .. } => Opcode::$name,)*
+                }
+            }
+
+            // the name of the opcode, e.g. `InvokeStatic`
+            pub fn name(&self) -> &'static str {
+                match self {
+                    $(Instr::$name { .. } => stringify!($name),)*
+                }
+            }
+
+            // converts the code locations of the instruction, `f` is given the raw operands along
+            // with their biases, see Offset::encode
+            fn map_locations<B>(self, mut f: impl FnMut(L, i16) -> B) -> Instr<B> {
+                match self {
+                    $(
+                        Instr::$name $((first!(val $($ty)+)))? $({ $($field),* })? => {
+                            Instr::$name
+                                $((map_operand!(f, val, $($ty)+)))?
+                                $({ $($field: MapLocations::map_locations($field, &mut f)),* })?
+                        }
+                    )*
+                }
+            }
+        }
+    };
+}
+
+instructions! {
+    0x00 => Nop,
+    0x01 => Null,
+    0x02 => I32One,
+    0x03 => I32Zero,
+    0x04 => I8Const(i8),
+    0x05 => I16Const(i16),
+    0x06 => I32Const(i32),
+    0x07 => I64Const(i64),
+    0x08 => U8Const(u8),
+    0x09 => U16Const(u16),
+    0x0A => U32Const(u32),
+    0x0B => U64Const(u64),
+    0x0C => F32Const(f32),
+    0x0D => F64Const(f64),
+    0x0E => CNameConst(CNameIndex),
+    0x0F => EnumConst { enum_: EnumIndex, value: EnumValueIndex },
+    0x10 => StringConst(StringIndex),
+    0x11 => TweakDbIdConst(TweakDbIndex),
+    0x12 => ResourceConst(ResourceIndex),
+    0x13 => TrueConst,
+    0x14 => FalseConst,
+    0x15 => Breakpoint(Box<Breakpoint>),
+    0x16 => Assign,
+    0x17 => Target(Loc),
+    0x18 => Local(LocalIndex),
+    0x19 => Param(ParameterIndex),
+    0x1A => ObjectField(FieldIndex),
+    0x1B => ExternalVar,
+    0x1C => Switch(Switch<Loc>),
+    0x1D => SwitchLabel(SwitchLabel<Loc>),
+    0x1E => SwitchDefault,
+    0x1F => Jump(Jump<Loc>),
+    0x20 => JumpIfFalse(Jump<Loc>),
+    0x21 => Skip(Jump<Loc>),
+    0x22 => Conditional(Conditional<Loc>),
+    0x23 => Construct { arg_count: u8, class: ClassIndex },
+    0x24 => InvokeStatic { exit: Jump<Loc>, line: u16, function: FunctionIndex, flags: u16 },
+    0x25 => InvokeVirtual { exit: Jump<Loc>, line: u16, function: CNameIndex, flags: u16 },
+    0x26 => ParamEnd,
+    0x27 => Return,
+    0x28 => StructField(FieldIndex),
+    0x29 => Context(Jump<Loc>),
+    0x2A => Equals(TypeIndex),
+    0x2B => RefStringEqualsString(TypeIndex),
+    0x2C => StringEqualsRefString(TypeIndex),
+    0x2D => NotEquals(TypeIndex),
+    0x2E => RefStringNotEqualsString(TypeIndex),
+    0x2F => StringNotEqualsRefString(TypeIndex),
+    0x30 => New(ClassIndex),
+    0x31 => Delete,
+    0x32 => This,
+    0x33 => Profile(Box<Profile>),
+    0x34 => ArrayClear(TypeIndex),
+    0x35 => ArraySize(TypeIndex),
+    0x36 => ArrayResize(TypeIndex),
+    0x37 => ArrayFindFirst(TypeIndex),
+    0x38 => ArrayFindFirstFast(TypeIndex),
+    0x39 => ArrayFindLast(TypeIndex),
+    0x3A => ArrayFindLastFast(TypeIndex),
+    0x3B => ArrayContains(TypeIndex),
+    0x3C => ArrayContainsFast(TypeIndex),
+    0x3D => ArrayCount(TypeIndex),
+    0x3E => ArrayCountFast(TypeIndex),
+    0x3F => ArrayPush(TypeIndex),
+    0x40 => ArrayPop(TypeIndex),
+    0x41 => ArrayInsert(TypeIndex),
+    0x42 => ArrayRemove(TypeIndex),
+    0x43 => ArrayRemoveFast(TypeIndex),
+    0x44 => ArrayGrow(TypeIndex),
+    0x45 => ArrayErase(TypeIndex),
+    0x46 => ArrayEraseFast(TypeIndex),
+    0x47 => ArrayLast(TypeIndex),
+    0x48 => ArrayElement(TypeIndex),
+    0x49 => ArraySort(TypeIndex),
+    0x4A => ArraySortByPredicate(TypeIndex),
+    0x4B => StaticArraySize(TypeIndex),
+    0x4C => StaticArrayFindFirst(TypeIndex),
+    0x4D => StaticArrayFindFirstFast(TypeIndex),
+    0x4E => StaticArrayFindLast(TypeIndex),
+    0x4F => StaticArrayFindLastFast(TypeIndex),
+    0x50 => StaticArrayContains(TypeIndex),
+    0x51 => StaticArrayContainsFast(TypeIndex),
+    0x52 => StaticArrayCount(TypeIndex),
+    0x53 => StaticArrayCountFast(TypeIndex),
+    0x54 => StaticArrayLast(TypeIndex),
+    0x55 => StaticArrayElement(TypeIndex),
+    0x56 => RefToBool,
+    0x57 => WeakRefToBool,
+    0x58 => EnumToI32 { enum_type: TypeIndex, size: u8 },
+    0x59 => I32ToEnum { enum_type: TypeIndex, size: u8 },
+    0x5A => DynamicCast { class: ClassIndex, flags: u8 },
+    0x5B => ToString(TypeIndex),
+    0x5C => ToVariant(TypeIndex),
+    0x5D => FromVariant(TypeIndex),
+    0x5E => VariantIsDefined,
+    0x5F => VariantIsRef,
+    0x60 => VariantIsArray,
+    0x61 => VariantTypeName,
+    0x62 => VariantToString,
+    0x63 => WeakRefToRef,
+    0x64 => RefToWeakRef,
+    0x65 => WeakRefNull,
+    0x66 => AsRef(TypeIndex),
+    0x67 => Deref(TypeIndex),
+}
+
+// operands that contain code locations, the other ones are kept as they are
+trait MapLocations<L, B> {
+    type Output;
+
+    fn map_locations(self, f: &mut impl FnMut(L, i16) -> B) -> Self::Output;
+}
+
+macro_rules! impl_no_locations {
+    ($($ty:ty),*) => {
+        $(
+            impl<L, B> MapLocations<L, B> for $ty {
+                type Output = Self;
+
+                #[inline]
+                fn map_locations(self, _f: &mut impl FnMut(L, i16) -> B) -> Self {
+                    self
+                }
+            }
+        )*
+    };
+}
+
+impl_no_locations!(
+    i8,
+    i16,
+    i32,
+    i64,
+    u8,
+    u16,
+    u32,
+    u64,
+    f32,
+    f64,
+    CNameIndex,
+    TweakDbIndex,
+    ResourceIndex,
+    StringIndex,
+    TypeIndex,
+    ClassIndex,
+    EnumIndex,
+    EnumValueIndex,
+    FunctionIndex,
+    ParameterIndex,
+    LocalIndex,
+    FieldIndex,
+    Box<Breakpoint>,
+    Box<Profile>
+);
+
+impl<L, B> MapLocations<L, B> for Jump<L> {
+    type Output = Jump<B>;
+
+    #[inline]
+    fn map_locations(self, f: &mut impl FnMut(L, i16) -> B) -> Jump<B> {
+        Jump {
+            target: f(self.target, Jump::TARGET_BIAS),
+        }
+    }
+}
+
+impl<L, B> MapLocations<L, B> for Conditional<L> {
+    type Output = Conditional<B>;
+
+    #[inline]
+    fn map_locations(self, f: &mut impl FnMut(L, i16) -> B) -> Conditional<B> {
+        Conditional {
+            false_label: f(self.false_label, Conditional::FALSE_LABEL_BIAS),
+            exit: f(self.exit, Conditional::EXIT_BIAS),
+        }
+    }
+}
+
+impl<L, B> MapLocations<L, B> for Switch<L> {
+    type Output = Switch<B>;
+
+    #[inline]
+    fn map_locations(self, f: &mut impl FnMut(L, i16) -> B) -> Switch<B> {
+        Switch {
+            expr_type: self.expr_type,
+            first_case: f(self.first_case, Switch::FIRST_CASE_BIAS),
+        }
+    }
+}
+
+impl<L, B> MapLocations<L, B> for SwitchLabel<L> {
+    type Output = SwitchLabel<B>;
+
+    #[inline]
+    fn map_locations(self, f: &mut impl FnMut(L, i16) -> B) -> SwitchLabel<B> {
+        SwitchLabel {
+            next_case: f(self.next_case, SwitchLabel::NEXT_CASE_BIAS),
+            body: f(self.body, SwitchLabel::BODY_BIAS),
+        }
+    }
+}
+
+impl Opcode {
+    #[inline]
+    pub const fn tag(self) -> u8 {
+        self as u8
+    }
+
+    #[inline]
+    pub fn from_tag(tag: u8) -> Option<Self> {
+        Self::ALL.get(usize::from(tag)).copied()
+    }
+
+    #[inline]
+    pub const fn name(self) -> &'static str {
+        Instr::NAMES[self as usize]
+    }
+}
+
+// builds a vector of instructions, e.g. `instr![This, ObjectField(field), I32Const(5), Assign]`
+#[macro_export]
+macro_rules! instr {
+    ($($variant:ident $(($($arg:expr),* $(,)?))? $({$($field:ident $(: $value:expr)?),* $(,)?})?),* $(,)?) => {
+        vec![$($crate::Instr::$variant $(($($arg),*))? $({$($field $(: $value)?),*})?),*]
+    };
 }
 
 impl<L> Instr<L> {
+    // the value of a constant instruction, the short forms like `I32One` included
+    pub fn constant(&self) -> Option<Constant> {
+        let constant = match *self {
+            Instr::I32One => Constant::I32(1),
+            Instr::I32Zero => Constant::I32(0),
+            Instr::TrueConst => Constant::Bool(true),
+            Instr::FalseConst => Constant::Bool(false),
+            Instr::I8Const(v) => Constant::I8(v),
+            Instr::I16Const(v) => Constant::I16(v),
+            Instr::I32Const(v) => Constant::I32(v),
+            Instr::I64Const(v) => Constant::I64(v),
+            Instr::U8Const(v) => Constant::U8(v),
+            Instr::U16Const(v) => Constant::U16(v),
+            Instr::U32Const(v) => Constant::U32(v),
+            Instr::U64Const(v) => Constant::U64(v),
+            Instr::F32Const(v) => Constant::F32(v),
+            Instr::F64Const(v) => Constant::F64(v),
+            Instr::CNameConst(v) => Constant::CName(v),
+            Instr::StringConst(v) => Constant::String(v),
+            Instr::TweakDbIdConst(v) => Constant::TweakDbId(v),
+            Instr::ResourceConst(v) => Constant::Resource(v),
+            _ => return None,
+        };
+        Some(constant)
+    }
+
     pub fn size(&self) -> u16 {
         let op_size = match self {
             Instr::Breakpoint(_) => 19,
@@ -344,6 +502,217 @@ impl<L> Instr<L> {
         };
         1 + op_size
     }
+
+    // the index of the class, function, field or enum an instruction refers to
+    pub fn referenced_definition(&self) -> Option<u32> {
+        match self {
+            Instr::InvokeStatic { function, .. } => Some((*function).into()),
+            Instr::ObjectField(field) | Instr::StructField(field) => Some((*field).into()),
+            Instr::New(class)
+            | Instr::Construct { class, .. }
+            | Instr::DynamicCast { class, .. } => Some((*class).into()),
+            Instr::EnumConst { value, .. } => Some((*value).into()),
+            _ => None,
+        }
+    }
+
+    // the type operand of instructions that take nothing but a type
+    pub fn type_operand(&self) -> Option<TypeIndex> {
+        match self {
+            type_operand!(typ) => Some(*typ),
+            _ => None,
+        }
+    }
+
+    // rewrites every definition index of the instruction
+    pub(crate) fn map_definitions(&mut self, mut f: impl FnMut(u32) -> u32) {
+        match self {
+            Instr::EnumConst { enum_, value } => {
+                *enum_ = enum_.map(&mut f);
+                *value = value.map(f);
+            }
+            Instr::Local(local) => *local = local.map(f),
+            Instr::Param(param) => *param = param.map(f),
+            Instr::ObjectField(field) | Instr::StructField(field) => *field = field.map(f),
+            Instr::New(class)
+            | Instr::Construct { class, .. }
+            | Instr::DynamicCast { class, .. } => *class = class.map(f),
+            Instr::InvokeStatic { function, .. } => *function = function.map(f),
+            Instr::Switch(switch) => switch.expr_type = switch.expr_type.map(f),
+            Instr::EnumToI32 { enum_type, .. } | Instr::I32ToEnum { enum_type, .. } => {
+                *enum_type = enum_type.map(f);
+            }
+            type_operand!(typ) => *typ = typ.map(f),
+            _ => {}
+        }
+    }
+
+    // the static array type operand of the `StaticArray*` instructions
+    pub fn static_array_type(&self) -> Option<TypeIndex> {
+        match self {
+            Instr::StaticArraySize(typ)
+            | Instr::StaticArrayFindFirst(typ)
+            | Instr::StaticArrayFindFirstFast(typ)
+            | Instr::StaticArrayFindLast(typ)
+            | Instr::StaticArrayFindLastFast(typ)
+            | Instr::StaticArrayContains(typ)
+            | Instr::StaticArrayContainsFast(typ)
+            | Instr::StaticArrayCount(typ)
+            | Instr::StaticArrayCountFast(typ)
+            | Instr::StaticArrayLast(typ)
+            | Instr::StaticArrayElement(typ) => Some(*typ),
+            _ => None,
+        }
+    }
+}
+
+impl Instr<Offset> {
+    // named operands of the instruction, in the order they're encoded
+    pub(crate) fn operands(&self) -> Vec<(&'static str, Operand)> {
+        let def = |index: u32| Operand::Definition(index);
+        match self {
+            Instr::I8Const(v) => vec![("value", Operand::Int((*v).into()))],
+            Instr::I16Const(v) => vec![("value", Operand::Int((*v).into()))],
+            Instr::I32Const(v) => vec![("value", Operand::Int((*v).into()))],
+            Instr::I64Const(v) => vec![("value", Operand::Int(*v))],
+            Instr::U8Const(v) => vec![("value", Operand::UInt((*v).into()))],
+            Instr::U16Const(v) => vec![("value", Operand::UInt((*v).into()))],
+            Instr::U32Const(v) => vec![("value", Operand::UInt((*v).into()))],
+            Instr::U64Const(v) => vec![("value", Operand::UInt(*v))],
+            Instr::F32Const(v) => vec![("value", Operand::Float((*v).into()))],
+            Instr::F64Const(v) => vec![("value", Operand::Float(*v))],
+            Instr::CNameConst(index) => vec![("value", Operand::CName(*index))],
+            Instr::StringConst(index) => vec![("value", Operand::String(*index))],
+            Instr::TweakDbIdConst(index) => vec![("value", Operand::TweakDbId(*index))],
+            Instr::ResourceConst(index) => vec![("value", Operand::Resource(*index))],
+            Instr::EnumConst { enum_, value } => {
+                vec![
+                    ("enum", def((*enum_).into())),
+                    ("value", def((*value).into())),
+                ]
+            }
+            Instr::Target(target) => vec![("target", Operand::Offset(*target))],
+            Instr::Local(index) => vec![("local", def((*index).into()))],
+            Instr::Param(index) => vec![("param", def((*index).into()))],
+            Instr::ObjectField(index) | Instr::StructField(index) => {
+                vec![("field", def((*index).into()))]
+            }
+            Instr::Switch(switch) => vec![
+                ("type", Operand::Type(switch.expr_type())),
+                ("first_case", Operand::Offset(switch.first_case())),
+            ],
+            Instr::SwitchLabel(label) => vec![
+                ("next_case", Operand::Offset(label.next_case())),
+                ("body", Operand::Offset(label.body())),
+            ],
+            Instr::Jump(jump)
+            | Instr::JumpIfFalse(jump)
+            | Instr::Skip(jump)
+            | Instr::Context(jump) => {
+                vec![("target", Operand::Offset(jump.target()))]
+            }
+            Instr::Conditional(cond) => vec![
+                ("false_label", Operand::Offset(cond.false_label())),
+                ("exit", Operand::Offset(cond.exit())),
+            ],
+            Instr::Construct { arg_count, class } => vec![
+                ("class", def((*class).into())),
+                ("arg_count", Operand::UInt((*arg_count).into())),
+            ],
+            Instr::InvokeStatic {
+                exit,
+                line,
+                function,
+                flags,
+            } => vec![
+                ("function", def((*function).into())),
+                ("line", Operand::UInt((*line).into())),
+                ("exit", Operand::Offset(exit.target())),
+                ("flags", Operand::UInt((*flags).into())),
+            ],
+            Instr::InvokeVirtual {
+                exit,
+                line,
+                function,
+                flags,
+            } => vec![
+                ("function", Operand::CName(*function)),
+                ("line", Operand::UInt((*line).into())),
+                ("exit", Operand::Offset(exit.target())),
+                ("flags", Operand::UInt((*flags).into())),
+            ],
+            Instr::New(class) => vec![("class", def((*class).into()))],
+            Instr::DynamicCast { class, flags } => vec![
+                ("class", def((*class).into())),
+                ("flags", Operand::UInt((*flags).into())),
+            ],
+            Instr::EnumToI32 { enum_type, size } | Instr::I32ToEnum { enum_type, size } => vec![
+                ("type", Operand::Type(*enum_type)),
+                ("size", Operand::UInt((*size).into())),
+            ],
+            instr => instr
+                .type_operand()
+                .map(|typ| ("type", Operand::Type(typ)))
+                .into_iter()
+                .collect(),
+        }
+    }
+
+    // rewrites every code location of the instruction, offsets are relative to its start
+    pub fn map_offsets(self, mut f: impl FnMut(Offset) -> Offset) -> Self {
+        let mut jump = |jump: Jump<Offset>| Jump::new(f(jump.target()));
+        match self {
+            Instr::Jump(j) => Instr::Jump(jump(j)),
+            Instr::JumpIfFalse(j) => Instr::JumpIfFalse(jump(j)),
+            Instr::Skip(j) => Instr::Skip(jump(j)),
+            Instr::Context(j) => Instr::Context(jump(j)),
+            Instr::InvokeStatic {
+                exit,
+                line,
+                function,
+                flags,
+            } => Instr::InvokeStatic {
+                exit: jump(exit),
+                line,
+                function,
+                flags,
+            },
+            Instr::InvokeVirtual {
+                exit,
+                line,
+                function,
+                flags,
+            } => Instr::InvokeVirtual {
+                exit: jump(exit),
+                line,
+                function,
+                flags,
+            },
+            Instr::Conditional(c) => {
+                Instr::Conditional(Conditional::new(f(c.false_label()), f(c.exit())))
+            }
+            Instr::Switch(s) => Instr::Switch(Switch::new(s.expr_type, f(s.first_case()))),
+            Instr::SwitchLabel(l) => {
+                Instr::SwitchLabel(SwitchLabel::new(f(l.next_case()), f(l.body())))
+            }
+            other => other,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Operand {
+    Int(i64),
+    UInt(u64),
+    Float(f64),
+    CName(CNameIndex),
+    String(StringIndex),
+    TweakDbId(TweakDbIndex),
+    Resource(ResourceIndex),
+    Definition(u32),
+    Type(TypeIndex),
+    // relative to the start of the instruction
+    Offset(Offset),
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, TryRead, TryWrite, Measure)]
@@ -352,14 +721,45 @@ pub struct Jump<Loc> {
 }
 
 impl Jump<Offset> {
+    pub const TARGET_BIAS: i16 = 3;
+
     #[inline]
     pub fn new(target: Offset) -> Self {
-        Jump { target: target - 3 }
+        Self::from_encoded(Self::encode_target(target))
+    }
+
+    #[inline]
+    pub fn from_encoded(target: EncodedOffset) -> Self {
+        Jump {
+            target: target.into_operand(),
+        }
     }
 
     #[inline]
     pub fn target(&self) -> Offset {
-        self.target + 3
+        Self::decode_target(self.encoded_target())
+    }
+
+    #[inline]
+    pub fn encoded_target(&self) -> EncodedOffset {
+        EncodedOffset::operand(self.target)
+    }
+
+    #[inline]
+    pub fn encode_target(target: Offset) -> EncodedOffset {
+        target.encode(Self::TARGET_BIAS)
+    }
+
+    #[inline]
+    pub fn decode_target(target: EncodedOffset) -> Offset {
+        target.decode(Self::TARGET_BIAS)
+    }
+}
+
+impl Jump<Label> {
+    #[inline]
+    pub fn labeled(target: Label) -> Self {
+        Jump { target }
     }
 }
 
@@ -370,22 +770,70 @@ pub struct Conditional<Loc> {
 }
 
 impl Conditional<Offset> {
+    pub const FALSE_LABEL_BIAS: i16 = 3;
+    pub const EXIT_BIAS: i16 = 5;
+
     #[inline]
     pub fn new(false_label: Offset, exit: Offset) -> Self {
+        Self::from_encoded(
+            Self::encode_false_label(false_label),
+            Self::encode_exit(exit),
+        )
+    }
+
+    #[inline]
+    pub fn from_encoded(false_label: EncodedOffset, exit: EncodedOffset) -> Self {
         Conditional {
-            false_label: false_label - 3,
-            exit: exit - 5,
+            false_label: false_label.into_operand(),
+            exit: exit.into_operand(),
         }
     }
 
     #[inline]
     pub fn false_label(&self) -> Offset {
-        self.false_label + 3
+        Self::decode_false_label(self.encoded_false_label())
     }
 
     #[inline]
     pub fn exit(&self) -> Offset {
-        self.exit + 5
+        Self::decode_exit(self.encoded_exit())
+    }
+
+    #[inline]
+    pub fn encoded_false_label(&self) -> EncodedOffset {
+        EncodedOffset::operand(self.false_label)
+    }
+
+    #[inline]
+    pub fn encoded_exit(&self) -> EncodedOffset {
+        EncodedOffset::operand(self.exit)
+    }
+
+    #[inline]
+    pub fn encode_false_label(false_label: Offset) -> EncodedOffset {
+        false_label.encode(Self::FALSE_LABEL_BIAS)
+    }
+
+    #[inline]
+    pub fn decode_false_label(false_label: EncodedOffset) -> Offset {
+        false_label.decode(Self::FALSE_LABEL_BIAS)
+    }
+
+    #[inline]
+    pub fn encode_exit(exit: Offset) -> EncodedOffset {
+        exit.encode(Self::EXIT_BIAS)
+    }
+
+    #[inline]
+    pub fn decode_exit(exit: EncodedOffset) -> Offset {
+        exit.decode(Self::EXIT_BIAS)
+    }
+}
+
+impl Conditional<Label> {
+    #[inline]
+    pub fn labeled(false_label: Label, exit: Label) -> Self {
+        Conditional { false_label, exit }
     }
 }
 
@@ -396,17 +844,54 @@ pub struct Switch<Loc> {
 }
 
 impl Switch<Offset> {
+    pub const FIRST_CASE_BIAS: i16 = 11;
+
     #[inline]
     pub fn new(expr_type: TypeIndex, first_case: Offset) -> Self {
+        Self::from_encoded(expr_type, Self::encode_first_case(first_case))
+    }
+
+    #[inline]
+    pub fn from_encoded(expr_type: TypeIndex, first_case: EncodedOffset) -> Self {
         Switch {
             expr_type,
-            first_case: first_case - 11,
+            first_case: first_case.into_operand(),
         }
     }
 
+    #[inline]
+    pub fn expr_type(&self) -> TypeIndex {
+        self.expr_type
+    }
+
     #[inline]
     pub fn first_case(&self) -> Offset {
-        self.first_case + 11
+        Self::decode_first_case(self.encoded_first_case())
+    }
+
+    #[inline]
+    pub fn encoded_first_case(&self) -> EncodedOffset {
+        EncodedOffset::operand(self.first_case)
+    }
+
+    #[inline]
+    pub fn encode_first_case(first_case: Offset) -> EncodedOffset {
+        first_case.encode(Self::FIRST_CASE_BIAS)
+    }
+
+    #[inline]
+    pub fn decode_first_case(first_case: EncodedOffset) -> Offset {
+        first_case.decode(Self::FIRST_CASE_BIAS)
+    }
+}
+
+impl Switch<Label> {
+    #[inline]
+    pub fn labeled(expr_type: TypeIndex, first_case: Label) -> Self {
+        Switch {
+            expr_type,
+            first_case,
+        }
     }
 }
 
@@ -417,19 +902,57 @@ pub struct SwitchLabel<Loc> {
 }
 
 impl SwitchLabel<Offset> {
+    pub const NEXT_CASE_BIAS: i16 = 3;
+    pub const BODY_BIAS: i16 = 5;
+
     pub fn new(next_case: Offset, body: Offset) -> Self {
+        Self::from_encoded(Self::encode_next_case(next_case), Self::encode_body(body))
+    }
+
+    pub fn from_encoded(next_case: EncodedOffset, body: EncodedOffset) -> Self {
         SwitchLabel {
-            next_case: next_case - 3,
-            body: body - 5,
+            next_case: next_case.into_operand(),
+            body: body.into_operand(),
         }
     }
 
     pub fn next_case(&self) -> Offset {
-        self.next_case + 3
+        Self::decode_next_case(self.encoded_next_case())
     }
 
     pub fn body(&self) -> Offset {
-        self.body + 5
+        Self::decode_body(self.encoded_body())
+    }
+
+    pub fn encoded_next_case(&self) -> EncodedOffset {
+        EncodedOffset::operand(self.next_case)
+    }
+
+    pub fn encoded_body(&self) -> EncodedOffset {
+        EncodedOffset::operand(self.body)
+    }
+
+    pub fn encode_next_case(next_case: Offset) -> EncodedOffset {
+        next_case.encode(Self::NEXT_CASE_BIAS)
+    }
+
+    pub fn decode_next_case(next_case: EncodedOffset) -> Offset {
+        next_case.decode(Self::NEXT_CASE_BIAS)
+    }
+
+    pub fn encode_body(body: Offset) -> EncodedOffset {
+        body.encode(Self::BODY_BIAS)
+    }
+
+    pub fn decode_body(body: EncodedOffset) -> Offset {
+        body.decode(Self::BODY_BIAS)
+    }
+}
+
+impl SwitchLabel<Label> {
+    #[inline]
+    pub fn labeled(next_case: Label, body: Label) -> Self {
+        SwitchLabel { next_case, body }
     }
 }
 
@@ -443,6 +966,18 @@ pub struct Breakpoint {
     padding: [u8; 8],
 }
 
+impl Breakpoint {
+    #[inline]
+    pub(crate) fn has_padding(&self) -> bool {
+        self.padding != [0; 8]
+    }
+
+    #[inline]
+    pub(crate) fn clear_padding(&mut self) {
+        self.padding = [0; 8];
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, TryRead, TryWrite, Measure)]
 pub struct Profile {
     #[byte(ctx = Prefixed(ctx))]
@@ -469,6 +1004,56 @@ impl From<i16> for Offset {
     }
 }
 
+impl Offset {
+    // `bias` is the distance from the start of the instruction to the end of the operand, the
+    // instructions define it for each of their offsets, e.g. Jump::TARGET_BIAS, it's only applied
+    // through the conversions of each instruction so that it can't be picked wrong
+    #[inline]
+    fn encode(self, bias: i16) -> EncodedOffset {
+        EncodedOffset {
+            value: self.value - bias,
+        }
+    }
+}
+
+// an offset as stored in the bytecode, it's relative to the end of the operand rather than the
+// start of the instruction, it can only be made by the instruction it belongs to, e.g.
+// Jump::encode_target
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct EncodedOffset {
+    value: i16,
+}
+
+impl EncodedOffset {
+    // the operands of Offset instructions hold encoded values, these conversions are kept private
+    // so that the bias can't be applied twice
+    #[inline]
+    fn operand(operand: Offset) -> Self {
+        EncodedOffset {
+            value: operand.value,
+        }
+    }
+
+    #[inline]
+    fn into_operand(self) -> Offset {
+        Offset { value: self.value }
+    }
+
+    #[inline]
+    fn decode(self, bias: i16) -> Offset {
+        Offset {
+            value: self.value + bias,
+        }
+    }
+}
+
+impl From<EncodedOffset> for i16 {
+    #[inline]
+    fn from(offset: EncodedOffset) -> Self {
+        offset.value
+    }
+}
+
 impl Add<i16> for Offset {
     type Output = Self;
 
@@ -490,3 +1075,379 @@ impl Sub<i16> for Offset {
         }
     }
 }
+
+// absolute position of an instruction within a function's code
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ByteOffset(u32);
+
+impl ByteOffset {
+    #[inline]
+    pub const fn new(value: u32) -> Self {
+        Self(value)
+    }
+
+    // resolves a relative offset stored in an instruction that starts at this position
+    #[inline]
+    pub fn jump(self, offset: Offset) -> Option<Self> {
+        self.0.checked_add_signed(offset.value.into()).map(Self)
+    }
+
+    // computes the relative offset from this position to the target
+    #[inline]
+    pub fn offset_to(self, target: Self) -> Option<Offset> {
+        let diff = i64::from(target.0) - i64::from(self.0);
+        i16::try_from(diff).ok().map(Offset::from)
+    }
+}
+
+impl From<ByteOffset> for u32 {
+    #[inline]
+    fn from(offset: ByteOffset) -> Self {
+        offset.0
+    }
+}
+
+// position of an instruction within a function's instruction list
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct InstrIndex(u32);
+
+impl InstrIndex {
+    #[inline]
+    pub const fn new(value: u32) -> Self {
+        Self(value)
+    }
+}
+
+impl From<InstrIndex> for u32 {
+    #[inline]
+    fn from(index: InstrIndex) -> Self {
+        index.0
+    }
+}
+
+impl From<InstrIndex> for usize {
+    #[inline]
+    fn from(index: InstrIndex) -> Self {
+        index.0 as usize
+    }
+}
+
+// a code location referred to before its offset is known, it's placed in code with a Target
+// instruction right before the instruction it names, see `resolve_targets`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Label(u32);
+
+impl Label {
+    #[inline]
+    pub const fn new(value: u32) -> Self {
+        Self(value)
+    }
+}
+
+impl From<Label> for u32 {
+    #[inline]
+    fn from(label: Label) -> Self {
+        label.0
+    }
+}
+
+// a value pushed by one of the constant instructions
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Constant {
+    Bool(bool),
+    I8(i8),
+    I16(i16),
+    I32(i32),
+    I64(i64),
+    U8(u8),
+    U16(u16),
+    U32(u32),
+    U64(u64),
+    F32(f32),
+    F64(f64),
+    CName(CNameIndex),
+    String(StringIndex),
+    TweakDbId(TweakDbIndex),
+    Resource(ResourceIndex),
+}
+
+impl Constant {
+    // the shortest instruction that pushes the value
+    pub fn to_instr<L>(self) -> Instr<L> {
+        match self {
+            Constant::Bool(true) => Instr::TrueConst,
+            Constant::Bool(false) => Instr::FalseConst,
+            Constant::I32(1) => Instr::I32One,
+            Constant::I32(0) => Instr::I32Zero,
+            Constant::I8(v) => Instr::I8Const(v),
+            Constant::I16(v) => Instr::I16Const(v),
+            Constant::I32(v) => Instr::I32Const(v),
+            Constant::I64(v) => Instr::I64Const(v),
+            Constant::U8(v) => Instr::U8Const(v),
+            Constant::U16(v) => Instr::U16Const(v),
+            Constant::U32(v) => Instr::U32Const(v),
+            Constant::U64(v) => Instr::U64Const(v),
+            Constant::F32(v) => Instr::F32Const(v),
+            Constant::F64(v) => Instr::F64Const(v),
+            Constant::CName(v) => Instr::CNameConst(v),
+            Constant::String(v) => Instr::StringConst(v),
+            Constant::TweakDbId(v) => Instr::TweakDbIdConst(v),
+            Constant::Resource(v) => Instr::ResourceConst(v),
+        }
+    }
+}
+
+// turns labeled code into code with offsets that can be passed to FunctionBody::from_instrs, the
+// Target instructions are dropped, each label has to be placed by exactly one of them
+pub fn resolve_targets(
+    code: impl IntoIterator<Item = Instr<Label>>,
+) -> Result<Vec<Instr>, CodeError> {
+    let code = code.into_iter().collect::<Vec<_>>();
+    let offsets = OffsetMap::new(&code);
+    let mut labels = HashMap::new();
+    for (i, instr) in code.iter().enumerate() {
+        if let &Instr::Target(label) = instr {
+            let index = InstrIndex(i as u32);
+            let offset = offsets.offset(index).unwrap_or_default();
+            if labels.insert(label, offset).is_some() {
+                return Err(CodeError::DuplicateLabel { index, label });
+            }
+        }
+    }
+
+    let mut out = Vec::with_capacity(code.len() - labels.len());
+    for (i, instr) in code.into_iter().enumerate() {
+        if matches!(instr, Instr::Target(_)) {
+            continue;
+        }
+        let index = InstrIndex(i as u32);
+        let from = offsets.offset(index).unwrap_or_default();
+        let mut error = None;
+        let instr = instr.map_locations(|label, bias| {
+            let Some(&to) = labels.get(&label) else {
+                error.get_or_insert(CodeError::UnresolvedLabel { index, label });
+                return Offset::from(0);
+            };
+            let offset = from.offset_to(to).unwrap_or_else(|| {
+                error.get_or_insert(CodeError::OffsetOutOfRange { index, label });
+                Offset::from(0)
+            });
+            offset.encode(bias).into_operand()
+        });
+        if let Some(error) = error {
+            return Err(error);
+        }
+        out.push(instr);
+    }
+    Ok(out)
+}
+
+// updates the jumps of `code` after its instructions were replaced one for one with ones of
+// possibly different sizes, the targets are taken from the original code, returns None if a jump
+// no longer fits its offset
+pub(crate) fn retarget_jumps(original: &[Instr], code: Vec<Instr>) -> Option<Vec<Instr>> {
+    let old = OffsetMap::new(original);
+    let new = OffsetMap::new(&code);
+    let mut overflow = false;
+    let code = code
+        .into_iter()
+        .enumerate()
+        .map(|(i, instr)| {
+            let index = InstrIndex(i as u32);
+            instr.map_offsets(|rel| {
+                let Some(target) = old.resolve_jump(index, rel) else {
+                    return rel;
+                };
+                let from = new.offset(index).unwrap_or_default();
+                let to = new.offset(target).unwrap_or_default();
+                from.offset_to(to).unwrap_or_else(|| {
+                    overflow = true;
+                    rel
+                })
+            })
+        })
+        .collect();
+    (!overflow).then_some(code)
+}
+
+// inserts instructions before positions of the code, jumps to a position land on the
+// instructions inserted before it, the inserted instructions are expected to have their own
+// offsets set already, returns None if a jump no longer fits its offset
+pub(crate) fn insert_code(
+    code: &[Instr],
+    mut insertions: BTreeMap<usize, Vec<Instr>>,
+) -> Option<Vec<Instr>> {
+    let mut out = vec![];
+    // where each position starts in the new code, followed by the end
+    let mut starts = Vec::with_capacity(code.len() + 1);
+    let mut moved = Vec::with_capacity(code.len());
+    for (i, instr) in code.iter().enumerate() {
+        starts.push(out.len());
+        out.extend(insertions.remove(&i).unwrap_or_default());
+        moved.push(out.len());
+        out.push(instr.clone());
+    }
+    starts.push(out.len());
+
+    let old = OffsetMap::new(code);
+    let new = OffsetMap::new(&out);
+    let mut overflow = false;
+    for (i, &pos) in moved.iter().enumerate() {
+        let from = new.offset(InstrIndex(pos as u32)).unwrap_or_default();
+        let instr = out[pos].clone().map_offsets(|rel| {
+            let Some(target) = old.resolve_jump(InstrIndex(i as u32), rel) else {
+                return rel;
+            };
+            let to = new
+                .offset(InstrIndex(starts[usize::from(target)] as u32))
+                .unwrap_or_default();
+            from.offset_to(to).unwrap_or_else(|| {
+                overflow = true;
+                rel
+            })
+        });
+        out[pos] = instr;
+    }
+    (!overflow).then_some(out)
+}
+
+// returns the position right after the expression that starts at `start`, expressions are
+// stored as trees in prefix order
+pub(crate) fn expr_end<L>(code: &[Instr<L>], start: usize) -> Option<usize> {
+    let mut pc = start + 1;
+    let operands = match code.get(start)? {
+        Instr::InvokeStatic { .. } | Instr::InvokeVirtual { .. } => {
+            while !matches!(code.get(pc)?, Instr::ParamEnd) {
+                pc = expr_end(code, pc)?;
+            }
+            return Some(pc + 1);
+        }
+        Instr::Construct { arg_count, .. } => usize::from(*arg_count),
+        Instr::Conditional(_) | Instr::ArrayInsert(_) => 3,
+        Instr::Assign
+        | Instr::Context(_)
+        | Instr::Equals(_)
+        | Instr::RefStringEqualsString(_)
+        | Instr::StringEqualsRefString(_)
+        | Instr::NotEquals(_)
+        | Instr::RefStringNotEqualsString(_)
+        | Instr::StringNotEqualsRefString(_)
+        | Instr::ArrayResize(_)
+        | Instr::ArrayFindFirst(_)
+        | Instr::ArrayFindFirstFast(_)
+        | Instr::ArrayFindLast(_)
+        | Instr::ArrayFindLastFast(_)
+        | Instr::ArrayContains(_)
+        | Instr::ArrayContainsFast(_)
+        | Instr::ArrayCount(_)
+        | Instr::ArrayCountFast(_)
+        | Instr::ArrayPush(_)
+        | Instr::ArrayRemove(_)
+        | Instr::ArrayRemoveFast(_)
+        | Instr::ArrayGrow(_)
+        | Instr::ArrayErase(_)
+        | Instr::ArrayEraseFast(_)
+        | Instr::ArrayElement(_)
+        | Instr::ArraySortByPredicate(_)
+        | Instr::StaticArrayFindFirst(_)
+        | Instr::StaticArrayFindFirstFast(_)
+        | Instr::StaticArrayFindLast(_)
+        | Instr::StaticArrayFindLastFast(_)
+        | Instr::StaticArrayContains(_)
+        | Instr::StaticArrayContainsFast(_)
+        | Instr::StaticArrayCount(_)
+        | Instr::StaticArrayCountFast(_)
+        | Instr::StaticArrayElement(_) => 2,
+        Instr::Switch(_)
+        | Instr::SwitchLabel(_)
+        | Instr::JumpIfFalse(_)
+        | Instr::Return
+        | Instr::StructField(_)
+        | Instr::Delete
+        | Instr::ArrayClear(_)
+        | Instr::ArraySize(_)
+        | Instr::ArrayPop(_)
+        | Instr::ArrayLast(_)
+        | Instr::ArraySort(_)
+        | Instr::StaticArraySize(_)
+        | Instr::StaticArrayLast(_)
+        | Instr::RefToBool
+        | Instr::WeakRefToBool
+        | Instr::EnumToI32 { .. }
+        | Instr::I32ToEnum { .. }
+        | Instr::DynamicCast { .. }
+        | Instr::ToString(_)
+        | Instr::ToVariant(_)
+        | Instr::FromVariant(_)
+        | Instr::VariantIsDefined
+        | Instr::VariantIsRef
+        | Instr::VariantIsArray
+        | Instr::VariantTypeName
+        | Instr::VariantToString
+        | Instr::WeakRefToRef
+        | Instr::RefToWeakRef
+        | Instr::AsRef(_)
+        | Instr::Deref(_) => 1,
+        _ => 0,
+    };
+    for _ in 0..operands {
+        pc = expr_end(code, pc)?;
+    }
+    Some(pc)
+}
+
+// converts between instruction indices and byte offsets of a sequence of instructions
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OffsetMap {
+    // start offset of every instruction followed by the end offset of the last one
+    offsets: Vec<u32>,
+}
+
+impl OffsetMap {
+    pub fn new<'a, L: 'a>(instructions: impl IntoIterator<Item = &'a Instr<L>>) -> Self {
+        let mut offsets = vec![0];
+        let mut offset = 0;
+        for instr in instructions {
+            offset += u32::from(instr.size());
+            offsets.push(offset);
+        }
+        Self { offsets }
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.offsets.len() - 1
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // the end offset is returned for an index one past the last instruction
+    #[inline]
+    pub fn offset(&self, index: InstrIndex) -> Option<ByteOffset> {
+        self.offsets
+            .get(usize::from(index))
+            .copied()
+            .map(ByteOffset)
+    }
+
+    // returns none for offsets that do not point at the start of an instruction
+    pub fn index(&self, offset: ByteOffset) -> Option<InstrIndex> {
+        self.offsets
+            .binary_search(&offset.0)
+            .ok()
+            .map(|i| InstrIndex(i as u32))
+    }
+
+    #[inline]
+    pub fn resolve_jump(&self, from: InstrIndex, offset: Offset) -> Option<InstrIndex> {
+        self.index(self.offset(from)?.jump(offset)?)
+    }
+
+    #[inline]
+    pub fn end(&self) -> ByteOffset {
+        ByteOffset(self.offsets[self.offsets.len() - 1])
+    }
+}
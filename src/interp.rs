@@ -0,0 +1,819 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::bundle::ScriptBundle;
+use crate::definition::{Function, Parameter, TypeKind};
+use crate::index::{FunctionIndex, TypeIndex};
+use crate::instr::{Instr, InstrIndex, Offset, OffsetMap, Opcode};
+use crate::operator::OperatorKind;
+use crate::util::RandomState;
+
+const MAX_DEPTH: u32 = 256;
+
+// evaluates side-effect-free code, i.e. constants, locals, parameters, control flow, operators,
+// calls to scripted functions and arrays held in memory, anything else fails with Unsupported
+pub struct Interpreter<'a, 'i> {
+    bundle: &'a ScriptBundle<'i>,
+    natives: HashMap<FunctionIndex, Box<NativeFn<'a>>, RandomState>,
+    step_limit: u64,
+    pub(crate) steps: u64,
+    depth: u32,
+    // values that can't be computed are Unknown instead of an error, see SymbolicWalker
+    pub(crate) symbolic: bool,
+}
+
+pub type NativeFn<'a> = dyn Fn(&[Value]) -> Result<Value, InterpError> + 'a;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    // the result of calls to functions that return nothing
+    Void,
+    Null,
+    Bool(bool),
+    // integers of every size are widened
+    Int(i64),
+    Float(f64),
+    String(String),
+    CName(String),
+    Array(Vec<Value>),
+    // only produced by the symbolic walker, e.g. for parameters and results of calls
+    Unknown,
+}
+
+impl Value {
+    pub fn as_bool(&self) -> Result<bool, InterpError> {
+        match self {
+            Value::Bool(b) => Ok(*b),
+            _ => Err(InterpError::TypeMismatch),
+        }
+    }
+
+    pub fn as_int(&self) -> Result<i64, InterpError> {
+        match self {
+            Value::Int(i) => Ok(*i),
+            _ => Err(InterpError::TypeMismatch),
+        }
+    }
+
+    fn as_array(&self) -> Result<&Vec<Value>, InterpError> {
+        match self {
+            Value::Array(arr) => Ok(arr),
+            _ => Err(InterpError::TypeMismatch),
+        }
+    }
+
+    fn as_array_mut(&mut self) -> Result<&mut Vec<Value>, InterpError> {
+        match self {
+            Value::Array(arr) => Ok(arr),
+            _ => Err(InterpError::TypeMismatch),
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Void => Ok(()),
+            Value::Unknown => f.write_str("?"),
+            Value::Null => f.write_str("null"),
+            Value::Bool(b) => write!(f, "{b}"),
+            Value::Int(i) => write!(f, "{i}"),
+            Value::Float(x) => write!(f, "{x}"),
+            Value::String(s) | Value::CName(s) => f.write_str(s),
+            Value::Array(arr) => {
+                f.write_str("[")?;
+                for (i, val) in arr.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(", ")?;
+                    }
+                    write!(f, "{val}")?;
+                }
+                f.write_str("]")
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum InterpError {
+    Unsupported(Opcode),
+    // a native function without a built-in implementation nor one registered with `with_native`
+    NativeCall(String),
+    MissingDefinition(u32),
+    Decoding(byte::Error),
+    InvalidJump(InstrIndex),
+    EndOfCode,
+    TypeMismatch,
+    DivisionByZero,
+    // a division whose result doesn't fit the operand type, e.g. the minimum value divided by -1
+    Overflow,
+    IndexOutOfBounds(i64),
+    StepLimitExceeded,
+    DepthLimitExceeded,
+}
+
+impl fmt::Display for InterpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InterpError::Unsupported(opcode) => {
+                write!(f, "instruction {} is not supported", opcode.name())
+            }
+            InterpError::NativeCall(name) => write!(f, "native function {name} is not available"),
+            InterpError::MissingDefinition(index) => write!(f, "definition {index} not found"),
+            InterpError::Decoding(err) => write!(f, "failed to decode code: {err}"),
+            InterpError::InvalidJump(index) => {
+                write!(f, "invalid jump at instruction {}", u32::from(*index))
+            }
+            InterpError::EndOfCode => f.write_str("unexpected end of code"),
+            InterpError::TypeMismatch => f.write_str("operand of an unexpected type"),
+            InterpError::DivisionByZero => f.write_str("division by zero"),
+            InterpError::Overflow => f.write_str("arithmetic overflow"),
+            InterpError::IndexOutOfBounds(index) => write!(f, "array index {index} out of bounds"),
+            InterpError::StepLimitExceeded => f.write_str("step limit exceeded"),
+            InterpError::DepthLimitExceeded => f.write_str("call depth limit exceeded"),
+        }
+    }
+}
+
+impl std::error::Error for InterpError {}
+
+#[derive(Clone)]
+pub(crate) struct Frame {
+    pub(crate) code: Vec<Instr>,
+    offsets: OffsetMap,
+    locals: HashMap<u32, Value, RandomState>,
+    params: HashMap<u32, Value, RandomState>,
+}
+
+impl Frame {
+    pub(crate) fn instr(&self, pc: usize) -> Result<&Instr, InterpError> {
+        self.code.get(pc).ok_or(InterpError::EndOfCode)
+    }
+
+    pub(crate) fn jump(&self, from: usize, offset: Offset) -> Result<usize, InterpError> {
+        let from = InstrIndex::new(from as u32);
+        self.offsets
+            .resolve_jump(from, offset)
+            .map(usize::from)
+            .ok_or(InterpError::InvalidJump(from))
+    }
+
+    pub(crate) fn resolve(&mut self, place: &Place) -> Result<&mut Value, InterpError> {
+        match place {
+            Place::Local(index) => self
+                .locals
+                .get_mut(index)
+                .ok_or(InterpError::MissingDefinition(*index)),
+            Place::Param(index) => self
+                .params
+                .get_mut(index)
+                .ok_or(InterpError::MissingDefinition(*index)),
+            Place::Element(array, index) => {
+                let array = self.resolve(array)?.as_array_mut()?;
+                usize::try_from(*index)
+                    .ok()
+                    .and_then(|i| array.get_mut(i))
+                    .ok_or(InterpError::IndexOutOfBounds(*index))
+            }
+        }
+    }
+}
+
+pub(crate) enum Place {
+    Local(u32),
+    Param(u32),
+    Element(Box<Place>, i64),
+}
+
+impl<'a, 'i> Interpreter<'a, 'i> {
+    pub fn new(bundle: &'a ScriptBundle<'i>) -> Self {
+        Self {
+            bundle,
+            natives: HashMap::default(),
+            step_limit: 1_000_000,
+            steps: 0,
+            depth: 0,
+            symbolic: false,
+        }
+    }
+
+    // bounds the number of instructions executed by a single call, guards against infinite loops
+    #[inline]
+    pub fn with_step_limit(mut self, step_limit: u64) -> Self {
+        self.step_limit = step_limit;
+        self
+    }
+
+    // provides an implementation of a native function, it's used instead of the built-in
+    // operators too
+    pub fn with_native(
+        mut self,
+        function: FunctionIndex,
+        f: impl Fn(&[Value]) -> Result<Value, InterpError> + 'a,
+    ) -> Self {
+        self.natives.insert(function, Box::new(f));
+        self
+    }
+
+    pub fn call(&mut self, function: FunctionIndex, args: &[Value]) -> Result<Value, InterpError> {
+        self.steps = 0;
+        let mut args = args.to_vec();
+        self.invoke(function, &mut args)
+    }
+
+    // out parameters are written back to `args`
+    fn invoke(&mut self, index: FunctionIndex, args: &mut [Value]) -> Result<Value, InterpError> {
+        let fun = self
+            .bundle
+            .get_item(index)
+            .ok_or(InterpError::MissingDefinition(index.into()))?;
+        if let Some(native) = self.natives.get(&index) {
+            return native(args);
+        }
+        if fun.flags().is_native() || fun.body().is_empty() {
+            // natives of the game are recognized by name, they don't have the operator flag
+            let name = self.bundle.get_item(fun.name()).unwrap_or_default();
+            if let Some(op) = fun
+                .operator_kind(self.bundle)
+                .or_else(|| OperatorKind::from_name(name))
+            {
+                return operator(op, self.operand_type(fun, name), args);
+            }
+            if self.symbolic {
+                return Ok(Value::Unknown);
+            }
+            return Err(InterpError::NativeCall(name.to_owned()));
+        }
+        // the symbolic walker doesn't descend into calls
+        if self.symbolic {
+            return Ok(Value::Unknown);
+        }
+
+        if self.depth >= MAX_DEPTH {
+            return Err(InterpError::DepthLimitExceeded);
+        }
+        let mut frame = self.frame(fun, args)?;
+        self.depth += 1;
+        let result = self.run(&mut frame);
+        self.depth -= 1;
+
+        for (arg, param) in args.iter_mut().zip(fun.parameters()) {
+            if let Some(val) = frame.params.remove(&u32::from(*param)) {
+                *arg = val;
+            }
+        }
+        result
+    }
+
+    // the type operands are computed in, taken from the first parameter or, for natives declared
+    // without parameters, from the mangled name
+    fn operand_type(&self, fun: &Function<'_>, name: &str) -> Option<Numeric> {
+        fun.parameters()
+            .first()
+            .and_then(|&param| self.bundle.get_item(self.bundle.get_item(param)?.typ()))
+            .and_then(|typ| self.bundle.get_item(typ.name()))
+            .and_then(Numeric::from_name)
+            .or_else(|| Numeric::from_signature(name))
+    }
+
+    pub(crate) fn frame(&self, fun: &Function<'_>, args: &[Value]) -> Result<Frame, InterpError> {
+        let code = fun.body().code_owned().map_err(InterpError::Decoding)?;
+        let offsets = OffsetMap::new(&code);
+        let params = fun
+            .parameters()
+            .iter()
+            .zip(args)
+            .map(|(&param, arg)| (param.into(), arg.clone()))
+            .collect();
+        let mut locals = HashMap::default();
+        for &local in fun.locals() {
+            let typ = self
+                .bundle
+                .get_item(local)
+                .ok_or(InterpError::MissingDefinition(local.into()))?
+                .typ();
+            locals.insert(local.into(), self.default_value(typ));
+        }
+        Ok(Frame {
+            code,
+            offsets,
+            locals,
+            params,
+        })
+    }
+
+    fn default_value(&self, typ: TypeIndex) -> Value {
+        let Some(typ) = self.bundle.get_item(typ) else {
+            return Value::Null;
+        };
+        match typ.kind() {
+            TypeKind::Array(_) => return Value::Array(vec![]),
+            TypeKind::Primitive => {}
+            _ => return Value::Null,
+        }
+        match self.bundle.get_item(typ.name()).unwrap_or_default() {
+            "Bool" => Value::Bool(false),
+            "Float" | "Double" => Value::Float(0.),
+            "String" => Value::String(String::new()),
+            "CName" => Value::CName(String::new()),
+            "Int8" | "Int16" | "Int32" | "Int64" | "Uint8" | "Uint16" | "Uint32" | "Uint64" => {
+                Value::Int(0)
+            }
+            _ => Value::Null,
+        }
+    }
+
+    pub(crate) fn step(&mut self) -> Result<(), InterpError> {
+        self.steps += 1;
+        if self.steps > self.step_limit {
+            return Err(InterpError::StepLimitExceeded);
+        }
+        Ok(())
+    }
+
+    fn run(&mut self, frame: &mut Frame) -> Result<Value, InterpError> {
+        let mut pc = 0;
+        while let Some(instr) = frame.code.get(pc) {
+            self.step()?;
+            match instr {
+                Instr::Nop | Instr::Breakpoint(_) | Instr::Profile(_) | Instr::SwitchDefault => {
+                    pc += 1;
+                }
+                Instr::Return => {
+                    pc += 1;
+                    return match frame.code.get(pc) {
+                        None | Some(Instr::Nop) => Ok(Value::Void),
+                        Some(_) => self.eval(frame, &mut pc),
+                    };
+                }
+                Instr::Jump(jump) => pc = frame.jump(pc, jump.target())?,
+                Instr::JumpIfFalse(jump) => {
+                    let target = frame.jump(pc, jump.target())?;
+                    pc += 1;
+                    if !self.eval(frame, &mut pc)?.as_bool()? {
+                        pc = target;
+                    }
+                }
+                Instr::Switch(switch) => {
+                    let mut label = frame.jump(pc, switch.first_case())?;
+                    pc += 1;
+                    let value = self.eval(frame, &mut pc)?;
+                    pc = loop {
+                        self.step()?;
+                        match frame.code.get(label) {
+                            Some(Instr::SwitchLabel(case)) => {
+                                let (body, next) = (case.body(), case.next_case());
+                                let mut case_pc = label + 1;
+                                if self.eval(frame, &mut case_pc)? == value {
+                                    break frame.jump(label, body)?;
+                                }
+                                label = frame.jump(label, next)?;
+                            }
+                            Some(Instr::SwitchDefault) => break label + 1,
+                            // no case matched and there's no default
+                            _ => break label,
+                        }
+                    };
+                }
+                // a case reached by falling through from the previous one
+                Instr::SwitchLabel(case) => pc = frame.jump(pc, case.body())?,
+                Instr::Assign => {
+                    pc += 1;
+                    let place = self.place(frame, &mut pc)?;
+                    let value = self.eval(frame, &mut pc)?;
+                    *frame.resolve(&place)? = value;
+                }
+                _ => {
+                    self.eval(frame, &mut pc)?;
+                }
+            }
+        }
+        Ok(Value::Void)
+    }
+
+    pub(crate) fn place(
+        &mut self,
+        frame: &mut Frame,
+        pc: &mut usize,
+    ) -> Result<Place, InterpError> {
+        let instr = frame.instr(*pc)?;
+        *pc += 1;
+        match instr {
+            Instr::Local(local) => Ok(Place::Local((*local).into())),
+            Instr::Param(param) => Ok(Place::Param((*param).into())),
+            Instr::ArrayElement(_) => {
+                let array = self.place(frame, pc)?;
+                let index = self.eval(frame, pc)?.as_int()?;
+                Ok(Place::Element(Box::new(array), index))
+            }
+            other => Err(InterpError::Unsupported(other.opcode())),
+        }
+    }
+
+    pub(crate) fn eval(&mut self, frame: &mut Frame, pc: &mut usize) -> Result<Value, InterpError> {
+        self.step()?;
+        let start = *pc;
+        let instr = frame.instr(start)?.clone();
+        *pc += 1;
+        let value = match instr {
+            // omitted optional arguments
+            Instr::Nop => Value::Void,
+            Instr::Null => Value::Null,
+            Instr::I32One => Value::Int(1),
+            Instr::I32Zero => Value::Int(0),
+            Instr::I8Const(v) => Value::Int(v.into()),
+            Instr::I16Const(v) => Value::Int(v.into()),
+            Instr::I32Const(v) => Value::Int(v.into()),
+            Instr::I64Const(v) => Value::Int(v),
+            Instr::U8Const(v) => Value::Int(v.into()),
+            Instr::U16Const(v) => Value::Int(v.into()),
+            Instr::U32Const(v) => Value::Int(v.into()),
+            Instr::U64Const(v) => Value::Int(v as i64),
+            Instr::F32Const(v) => Value::Float(v.into()),
+            Instr::F64Const(v) => Value::Float(v),
+            Instr::TrueConst => Value::Bool(true),
+            Instr::FalseConst => Value::Bool(false),
+            Instr::CNameConst(name) => {
+                Value::CName(self.bundle.get_item(name).unwrap_or_default().to_owned())
+            }
+            Instr::StringConst(str) => {
+                Value::String(self.bundle.get_item(str).unwrap_or_default().to_owned())
+            }
+            Instr::EnumConst { value, .. } => {
+                let member = self
+                    .bundle
+                    .get_item(value)
+                    .ok_or(InterpError::MissingDefinition(value.into()))?;
+                Value::Int(member.value())
+            }
+            Instr::Local(_) | Instr::Param(_) | Instr::ArrayElement(_) => {
+                *pc = start;
+                let place = self.place(frame, pc)?;
+                frame.resolve(&place)?.clone()
+            }
+            Instr::Conditional(cond) => {
+                let cond_value = self.eval(frame, pc)?;
+                if cond_value == Value::Unknown {
+                    self.eval(frame, pc)?;
+                    *pc = frame.jump(start, cond.exit())?;
+                    Value::Unknown
+                } else if cond_value.as_bool()? {
+                    let value = self.eval(frame, pc)?;
+                    *pc = frame.jump(start, cond.exit())?;
+                    value
+                } else {
+                    *pc = frame.jump(start, cond.false_label())?;
+                    self.eval(frame, pc)?
+                }
+            }
+            Instr::InvokeStatic { function, .. } => self.eval_call(frame, pc, function)?,
+            Instr::Equals(_) | Instr::NotEquals(_) => {
+                let (lhs, rhs) = (self.eval(frame, pc)?, self.eval(frame, pc)?);
+                if lhs == Value::Unknown || rhs == Value::Unknown {
+                    Value::Unknown
+                } else {
+                    Value::Bool((lhs == rhs) == matches!(instr, Instr::Equals(_)))
+                }
+            }
+            Instr::EnumToI32 { .. } | Instr::I32ToEnum { .. } => self.eval(frame, pc)?,
+            Instr::ToString(_) => match self.eval(frame, pc)? {
+                Value::Unknown => Value::Unknown,
+                val => Value::String(val.to_string()),
+            },
+            Instr::RefToBool | Instr::WeakRefToBool => match self.eval(frame, pc)? {
+                Value::Unknown => Value::Unknown,
+                val => Value::Bool(val != Value::Null),
+            },
+            // objects aren't modelled, only their presence is
+            Instr::New(_) | Instr::This if self.symbolic => Value::Unknown,
+            Instr::ArraySize(_) => Value::Int(self.eval(frame, pc)?.as_array()?.len() as i64),
+            Instr::ArrayLast(_) => {
+                let array = self.eval(frame, pc)?;
+                let array = array.as_array()?;
+                array
+                    .last()
+                    .cloned()
+                    .ok_or(InterpError::IndexOutOfBounds(-1))?
+            }
+            Instr::ArrayContains(_) | Instr::ArrayContainsFast(_) => {
+                let array = self.eval(frame, pc)?;
+                let value = self.eval(frame, pc)?;
+                Value::Bool(array.as_array()?.contains(&value))
+            }
+            Instr::ArrayCount(_) | Instr::ArrayCountFast(_) => {
+                let array = self.eval(frame, pc)?;
+                let value = self.eval(frame, pc)?;
+                Value::Int(array.as_array()?.iter().filter(|&v| *v == value).count() as i64)
+            }
+            Instr::ArrayFindFirst(_) | Instr::ArrayFindFirstFast(_) => {
+                let array = self.eval(frame, pc)?;
+                let value = self.eval(frame, pc)?;
+                let pos = array.as_array()?.iter().position(|v| *v == value);
+                Value::Int(pos.map_or(-1, |pos| pos as i64))
+            }
+            Instr::ArrayFindLast(_) | Instr::ArrayFindLastFast(_) => {
+                let array = self.eval(frame, pc)?;
+                let value = self.eval(frame, pc)?;
+                let pos = array.as_array()?.iter().rposition(|v| *v == value);
+                Value::Int(pos.map_or(-1, |pos| pos as i64))
+            }
+            Instr::ArrayClear(_) => {
+                let place = self.place(frame, pc)?;
+                frame.resolve(&place)?.as_array_mut()?.clear();
+                Value::Void
+            }
+            Instr::ArrayResize(_) => {
+                let place = self.place(frame, pc)?;
+                let len = self.eval(frame, pc)?.as_int()?;
+                let len = usize::try_from(len).map_err(|_| InterpError::IndexOutOfBounds(len))?;
+                frame
+                    .resolve(&place)?
+                    .as_array_mut()?
+                    .resize(len, Value::Null);
+                Value::Void
+            }
+            Instr::ArrayPush(_) => {
+                let place = self.place(frame, pc)?;
+                let value = self.eval(frame, pc)?;
+                frame.resolve(&place)?.as_array_mut()?.push(value);
+                Value::Void
+            }
+            Instr::ArrayPop(_) => {
+                let place = self.place(frame, pc)?;
+                let array = frame.resolve(&place)?.as_array_mut()?;
+                array.pop().ok_or(InterpError::IndexOutOfBounds(-1))?
+            }
+            Instr::ArrayInsert(_) => {
+                let place = self.place(frame, pc)?;
+                let index = self.eval(frame, pc)?.as_int()?;
+                let value = self.eval(frame, pc)?;
+                let array = frame.resolve(&place)?.as_array_mut()?;
+                match usize::try_from(index) {
+                    Ok(i) if i <= array.len() => array.insert(i, value),
+                    _ => return Err(InterpError::IndexOutOfBounds(index)),
+                }
+                Value::Void
+            }
+            Instr::ArrayRemove(_) | Instr::ArrayRemoveFast(_) => {
+                let place = self.place(frame, pc)?;
+                let value = self.eval(frame, pc)?;
+                let array = frame.resolve(&place)?.as_array_mut()?;
+                let pos = array.iter().position(|v| *v == value);
+                Value::Bool(pos.map(|pos| array.remove(pos)).is_some())
+            }
+            Instr::ArrayErase(_) | Instr::ArrayEraseFast(_) => {
+                let place = self.place(frame, pc)?;
+                let index = self.eval(frame, pc)?.as_int()?;
+                let array = frame.resolve(&place)?.as_array_mut()?;
+                match usize::try_from(index) {
+                    Ok(i) if i < array.len() => {
+                        array.remove(i);
+                        Value::Bool(true)
+                    }
+                    _ => Value::Bool(false),
+                }
+            }
+            other => return Err(InterpError::Unsupported(other.opcode())),
+        };
+        Ok(value)
+    }
+
+    fn eval_call(
+        &mut self,
+        frame: &mut Frame,
+        pc: &mut usize,
+        function: FunctionIndex,
+    ) -> Result<Value, InterpError> {
+        let fun = self
+            .bundle
+            .get_item(function)
+            .ok_or(InterpError::MissingDefinition(function.into()))?;
+        let params = fun.parameters().to_vec();
+
+        let mut args = vec![];
+        let mut outs = vec![];
+        for (i, param) in params.into_iter().enumerate() {
+            if matches!(frame.instr(*pc)?, Instr::ParamEnd) {
+                break;
+            }
+            let is_out = self.bundle.get_item(param).is_some_and(Parameter::is_out);
+            if is_out && !matches!(frame.instr(*pc)?, Instr::Nop) {
+                let place = self.place(frame, pc)?;
+                args.push(frame.resolve(&place)?.clone());
+                outs.push((i, place));
+            } else {
+                args.push(self.eval(frame, pc)?);
+            }
+        }
+        match frame.instr(*pc)? {
+            Instr::ParamEnd => *pc += 1,
+            other => return Err(InterpError::Unsupported(other.opcode())),
+        }
+
+        let result = if self.symbolic && args.contains(&Value::Unknown) {
+            Value::Unknown
+        } else {
+            self.invoke(function, &mut args)?
+        };
+        for (i, place) in outs {
+            let arg = if result == Value::Unknown {
+                Value::Unknown
+            } else {
+                args[i].clone()
+            };
+            *frame.resolve(&place)? = arg;
+        }
+        Ok(result)
+    }
+}
+
+// the numeric types of the game, values are widened to `i64` and `f64` but the results of
+// arithmetic wrap around or get rounded the way they would at the actual width
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Numeric {
+    I8,
+    I16,
+    I32,
+    I64,
+    U8,
+    U16,
+    U32,
+    U64,
+    F32,
+    F64,
+}
+
+impl Numeric {
+    const ALL: [Numeric; 10] = [
+        Numeric::I8,
+        Numeric::I16,
+        Numeric::I32,
+        Numeric::I64,
+        Numeric::U8,
+        Numeric::U16,
+        Numeric::U32,
+        Numeric::U64,
+        Numeric::F32,
+        Numeric::F64,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Numeric::I8 => "Int8",
+            Numeric::I16 => "Int16",
+            Numeric::I32 => "Int32",
+            Numeric::I64 => "Int64",
+            Numeric::U8 => "Uint8",
+            Numeric::U16 => "Uint16",
+            Numeric::U32 => "Uint32",
+            Numeric::U64 => "Uint64",
+            Numeric::F32 => "Float",
+            Numeric::F64 => "Double",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|num| num.name() == name)
+    }
+
+    // the type of the first parameter in a name like `OperatorAdd;Int32Int32;Int32`
+    fn from_signature(name: &str) -> Option<Self> {
+        let (_, params) = name.split_once(';')?;
+        Self::ALL
+            .into_iter()
+            .find(|num| params.starts_with(num.name()))
+    }
+
+    fn wrap(self, i: i64) -> i64 {
+        match self {
+            Numeric::I8 => (i as i8).into(),
+            Numeric::I16 => (i as i16).into(),
+            Numeric::I32 => (i as i32).into(),
+            Numeric::U8 => (i as u8).into(),
+            Numeric::U16 => (i as u16).into(),
+            Numeric::U32 => (i as u32).into(),
+            Numeric::I64 | Numeric::U64 | Numeric::F32 | Numeric::F64 => i,
+        }
+    }
+
+    fn round(self, x: f64) -> f64 {
+        match self {
+            Numeric::F32 => (x as f32).into(),
+            _ => x,
+        }
+    }
+
+    fn normalize(self, value: Value) -> Value {
+        match value {
+            Value::Int(i) => Value::Int(self.wrap(i)),
+            Value::Float(x) => Value::Float(self.round(x)),
+            other => other,
+        }
+    }
+}
+
+fn operator(
+    op: OperatorKind,
+    typ: Option<Numeric>,
+    args: &mut [Value],
+) -> Result<Value, InterpError> {
+    use OperatorKind as Op;
+
+    let assign = |args: &mut [Value], op: Op| {
+        let [lhs, rhs] = args else {
+            return Err(InterpError::TypeMismatch);
+        };
+        let result = binary(op, typ, lhs, rhs)?;
+        *lhs = result.clone();
+        Ok(result)
+    };
+    match (op, &*args) {
+        (Op::AssignAdd, _) => assign(args, Op::Add),
+        (Op::AssignSubtract, _) => assign(args, Op::Subtract),
+        (Op::AssignMultiply, _) => assign(args, Op::Multiply),
+        (Op::AssignDivide, _) => assign(args, Op::Divide),
+        (Op::AssignOr, _) => assign(args, Op::Or),
+        (Op::AssignAnd, _) => assign(args, Op::And),
+        (Op::LogicNot, [Value::Bool(b)]) => Ok(Value::Bool(!b)),
+        (Op::BitNot, [Value::Int(i)]) => Ok(normalize(typ, Value::Int(!i))),
+        (Op::Neg, [Value::Int(i)]) => Ok(normalize(typ, Value::Int(i.wrapping_neg()))),
+        (Op::Neg, [Value::Float(x)]) => Ok(Value::Float(-x)),
+        (_, [lhs, rhs]) => binary(op, typ, lhs, rhs),
+        _ => Err(InterpError::TypeMismatch),
+    }
+}
+
+fn binary(
+    op: OperatorKind,
+    typ: Option<Numeric>,
+    lhs: &Value,
+    rhs: &Value,
+) -> Result<Value, InterpError> {
+    use OperatorKind as Op;
+
+    let value = match (op, lhs, rhs) {
+        (Op::Equal, _, _) => Value::Bool(lhs == rhs),
+        (Op::NotEqual, _, _) => Value::Bool(lhs != rhs),
+        (Op::LogicAnd, Value::Bool(a), Value::Bool(b)) => Value::Bool(*a && *b),
+        (Op::LogicOr, Value::Bool(a), Value::Bool(b)) => Value::Bool(*a || *b),
+        (Op::And, Value::Bool(a), Value::Bool(b)) => Value::Bool(a & b),
+        (Op::Or, Value::Bool(a), Value::Bool(b)) => Value::Bool(a | b),
+        (Op::Xor, Value::Bool(a), Value::Bool(b)) => Value::Bool(a ^ b),
+        (Op::Add, Value::String(a), Value::String(b)) => Value::String(format!("{a}{b}")),
+        (_, Value::Int(a), Value::Int(b)) => match op {
+            Op::Add => Value::Int(a.wrapping_add(*b)),
+            Op::Subtract => Value::Int(a.wrapping_sub(*b)),
+            Op::Multiply => Value::Int(a.wrapping_mul(*b)),
+            Op::Divide | Op::Modulo if *b == 0 => return Err(InterpError::DivisionByZero),
+            Op::Divide | Op::Modulo => {
+                let result = if op == Op::Divide {
+                    a.checked_div(*b)
+                } else {
+                    a.checked_rem(*b)
+                };
+                // unlike the other operations, division can't wrap around
+                match result {
+                    Some(i) if typ.map_or(i, |typ| typ.wrap(i)) == i => Value::Int(i),
+                    _ => return Err(InterpError::Overflow),
+                }
+            }
+            Op::And => Value::Int(a & b),
+            Op::Or => Value::Int(a | b),
+            Op::Xor => Value::Int(a ^ b),
+            Op::Less => Value::Bool(a < b),
+            Op::LessEqual => Value::Bool(a <= b),
+            Op::Greater => Value::Bool(a > b),
+            Op::GreaterEqual => Value::Bool(a >= b),
+            _ => return Err(InterpError::TypeMismatch),
+        },
+        (_, Value::Float(a), Value::Float(b)) => match op {
+            Op::Add => Value::Float(a + b),
+            Op::Subtract => Value::Float(a - b),
+            Op::Multiply => Value::Float(a * b),
+            Op::Divide => Value::Float(a / b),
+            Op::Modulo => Value::Float(a % b),
+            Op::Less => Value::Bool(a < b),
+            Op::LessEqual => Value::Bool(a <= b),
+            Op::Greater => Value::Bool(a > b),
+            Op::GreaterEqual => Value::Bool(a >= b),
+            _ => return Err(InterpError::TypeMismatch),
+        },
+        (_, Value::String(a), Value::String(b)) => match op {
+            Op::Less => Value::Bool(a < b),
+            Op::LessEqual => Value::Bool(a <= b),
+            Op::Greater => Value::Bool(a > b),
+            Op::GreaterEqual => Value::Bool(a >= b),
+            _ => return Err(InterpError::TypeMismatch),
+        },
+        _ => return Err(InterpError::TypeMismatch),
+    };
+    Ok(normalize(typ, value))
+}
+
+#[inline]
+fn normalize(typ: Option<Numeric>, value: Value) -> Value {
+    match typ {
+        Some(typ) => typ.normalize(value),
+        None => value,
+    }
+}
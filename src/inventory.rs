@@ -0,0 +1,89 @@
+use std::collections::BTreeMap;
+
+use crate::bundle::ScriptBundle;
+use crate::definition::{Definition, DefinitionKind};
+use crate::symbols;
+
+// an api-level diff of classes, functions and enums between two bundles, e.g. two game versions
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct InventoryDiff {
+    pub added: Vec<InventoryEntry>,
+    pub removed: Vec<InventoryEntry>,
+    pub changed: Vec<SignatureChange>,
+}
+
+impl InventoryDiff {
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct InventoryEntry {
+    pub kind: DefinitionKind,
+    // fully-qualified name, e.g. `Class.Function`
+    pub name: String,
+    pub signature: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SignatureChange {
+    pub kind: DefinitionKind,
+    pub name: String,
+    pub old: String,
+    pub new: String,
+}
+
+// entries are sorted by kind and name
+pub fn compare_inventory(old: &ScriptBundle<'_>, new: &ScriptBundle<'_>) -> InventoryDiff {
+    let mut old = inventory(old);
+    let mut diff = InventoryDiff::default();
+    for ((kind, name), signature) in inventory(new) {
+        match old.remove(&(kind, name.clone())) {
+            None => diff.added.push(InventoryEntry {
+                kind,
+                name,
+                signature,
+            }),
+            Some(old) if old != signature => diff.changed.push(SignatureChange {
+                kind,
+                name,
+                old,
+                new: signature,
+            }),
+            Some(_) => {}
+        }
+    }
+    diff.removed = old
+        .into_iter()
+        .map(|((kind, name), signature)| InventoryEntry {
+            kind,
+            name,
+            signature,
+        })
+        .collect();
+    diff
+}
+
+fn inventory(bundle: &ScriptBundle<'_>) -> BTreeMap<(DefinitionKind, String), String> {
+    let mut entries = BTreeMap::new();
+    for index in 1..bundle.definition_count() {
+        let Some(def) = bundle.definition(index) else {
+            continue;
+        };
+        if !matches!(
+            def,
+            Definition::Class(_) | Definition::Function(_) | Definition::Enum(_)
+        ) {
+            continue;
+        }
+        if let Some(name) = symbols::qualified_name(bundle, index) {
+            entries.insert((def.kind(), name), symbols::signature(bundle, def));
+        }
+    }
+    entries
+}
@@ -0,0 +1,408 @@
+use std::fmt;
+
+use byte::{BytesExt, ToBytesExt, TryRead};
+
+use crate::definition::{Definition, DefinitionHeader};
+use crate::provenance::ChangeKind;
+use crate::util::{Owned, Relocate};
+use crate::ENDIANESS;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Edit<'i> {
+    Define {
+        index: u32,
+        definition: Definition<'i>,
+    },
+    Remove {
+        index: u32,
+        definition: Definition<'i>,
+    },
+    Modify {
+        index: u32,
+        old: Definition<'i>,
+        new: Definition<'i>,
+    },
+}
+
+impl<'i> Edit<'i> {
+    #[inline]
+    pub fn index(&self) -> u32 {
+        match self {
+            Edit::Define { index, .. }
+            | Edit::Remove { index, .. }
+            | Edit::Modify { index, .. } => *index,
+        }
+    }
+
+    // the change the edit makes to its definition when it's applied
+    pub(crate) fn change(&self) -> ChangeKind {
+        match self {
+            Edit::Define { .. } => ChangeKind::Defined,
+            Edit::Remove { .. } => ChangeKind::Removed,
+            Edit::Modify { .. } => ChangeKind::Modified,
+        }
+    }
+
+    // the change the edit makes to its definition when it's reverted
+    pub(crate) fn reverted_change(&self) -> ChangeKind {
+        match self {
+            Edit::Define { .. } => ChangeKind::Removed,
+            Edit::Remove { .. } => ChangeKind::Defined,
+            Edit::Modify { .. } => ChangeKind::Modified,
+        }
+    }
+
+    pub(crate) fn revert(&self, definitions: &mut Vec<Definition<'i>>) -> Result<(), EditMismatch> {
+        match self {
+            Edit::Define { index, .. } => {
+                // only the last definition can be taken back
+                if definitions.len() != *index as usize + 1 {
+                    return Err(EditMismatch::new(*index, definitions));
+                }
+                definitions.truncate(*index as usize);
+            }
+            Edit::Remove { index, definition }
+            | Edit::Modify {
+                index,
+                old: definition,
+                ..
+            } => *slot(definitions, *index)? = definition.clone(),
+        }
+        Ok(())
+    }
+
+    pub(crate) fn apply(&self, definitions: &mut Vec<Definition<'i>>) -> Result<(), EditMismatch> {
+        match self {
+            Edit::Define { index, definition } => {
+                if definitions.len() != *index as usize {
+                    return Err(EditMismatch::new(*index, definitions));
+                }
+                definitions.push(definition.clone());
+            }
+            Edit::Remove { index, .. } => *slot(definitions, *index)? = Definition::UNDEFINED,
+            Edit::Modify { index, new, .. } => *slot(definitions, *index)? = new.clone(),
+        }
+        Ok(())
+    }
+
+    fn tag(&self) -> u8 {
+        match self {
+            Edit::Define { .. } => 0,
+            Edit::Remove { .. } => 1,
+            Edit::Modify { .. } => 2,
+        }
+    }
+
+    fn write(&self, out: &mut Vec<u8>) -> byte::Result<()> {
+        out.push(self.tag());
+        out.extend(self.index().to_le_bytes());
+        match self {
+            Edit::Define { definition, .. } | Edit::Remove { definition, .. } => {
+                write_definition(definition, out)
+            }
+            Edit::Modify { old, new, .. } => {
+                write_definition(old, out)?;
+                write_definition(new, out)
+            }
+        }
+    }
+
+    fn read(bytes: &'i [u8], offset: &mut usize) -> byte::Result<Self> {
+        let tag: u8 = bytes.read(offset, ENDIANESS)?;
+        let index = bytes.read(offset, ENDIANESS)?;
+        let edit = match tag {
+            0 => Edit::Define {
+                index,
+                definition: read_definition(bytes, offset)?,
+            },
+            1 => Edit::Remove {
+                index,
+                definition: read_definition(bytes, offset)?,
+            },
+            2 => Edit::Modify {
+                index,
+                old: read_definition(bytes, offset)?,
+                new: read_definition(bytes, offset)?,
+            },
+            _ => {
+                return Err(byte::Error::BadInput {
+                    err: "invalid journal edit",
+                })
+            }
+        };
+        Ok(edit)
+    }
+
+    #[inline]
+    pub fn into_owned(self) -> Edit<'static> {
+        self.relocate(&Owned)
+    }
+
+    pub(crate) fn relocate<'b>(self, to: &impl Relocate<'b>) -> Edit<'b> {
+        match self {
+            Edit::Define { index, definition } => Edit::Define {
+                index,
+                definition: definition.relocate(to),
+            },
+            Edit::Remove { index, definition } => Edit::Remove {
+                index,
+                definition: definition.relocate(to),
+            },
+            Edit::Modify { index, old, new } => Edit::Modify {
+                index,
+                old: old.relocate(to),
+                new: new.relocate(to),
+            },
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Journal<'i> {
+    done: Vec<Edit<'i>>,
+    undone: Vec<Edit<'i>>,
+}
+
+impl<'i> Journal<'i> {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[inline]
+    pub fn edits(&self) -> &[Edit<'i>] {
+        &self.done
+    }
+
+    #[inline]
+    pub fn undone(&self) -> &[Edit<'i>] {
+        &self.undone
+    }
+
+    #[inline]
+    pub fn can_undo(&self) -> bool {
+        !self.done.is_empty()
+    }
+
+    #[inline]
+    pub fn can_redo(&self) -> bool {
+        !self.undone.is_empty()
+    }
+
+    pub fn into_edits(self) -> Vec<Edit<'i>> {
+        self.done
+    }
+
+    #[inline]
+    pub fn into_owned(self) -> Journal<'static> {
+        self.relocate(&Owned)
+    }
+
+    pub(crate) fn relocate<'b>(self, to: &impl Relocate<'b>) -> Journal<'b> {
+        let relocate = |edits: Vec<Edit<'_>>| edits.into_iter().map(|e| e.relocate(to)).collect();
+        Journal {
+            done: relocate(self.done),
+            undone: relocate(self.undone),
+        }
+    }
+
+    // the edits and the undone edits, each with the definitions involved encoded the way they are
+    // in bundles, their names refer to the string pools of the bundle the journal belongs to
+    pub fn to_bytes(&self) -> byte::Result<Vec<u8>> {
+        let mut out = vec![];
+        for edits in [&self.done, &self.undone] {
+            let count = u32::try_from(edits.len()).map_err(|_| byte::Error::BadInput {
+                err: "too many journal edits",
+            })?;
+            out.extend(count.to_le_bytes());
+            for edit in edits {
+                edit.write(&mut out)?;
+            }
+        }
+        Ok(out)
+    }
+
+    pub fn from_bytes(bytes: &'i [u8]) -> byte::Result<Self> {
+        let offset = &mut 0;
+        let mut read_edits = || {
+            let count: u32 = bytes.read(offset, ENDIANESS)?;
+            (0..count)
+                .map(|_| Edit::read(bytes, offset))
+                .collect::<byte::Result<Vec<_>>>()
+        };
+        Ok(Self {
+            done: read_edits()?,
+            undone: read_edits()?,
+        })
+    }
+
+    pub(crate) fn record(&mut self, edit: Edit<'i>) {
+        self.done.push(edit);
+        self.undone.clear();
+    }
+
+    // reverts the last edit, it's left in place if it doesn't fit the definitions
+    pub(crate) fn undo(
+        &mut self,
+        definitions: &mut Vec<Definition<'i>>,
+    ) -> Option<Result<&Edit<'i>, EditMismatch>> {
+        if let Err(err) = self.done.last()?.revert(definitions) {
+            return Some(Err(err));
+        }
+        let edit = self.done.pop()?;
+        self.undone.push(edit);
+        self.undone.last().map(Ok)
+    }
+
+    pub(crate) fn redo(
+        &mut self,
+        definitions: &mut Vec<Definition<'i>>,
+    ) -> Option<Result<&Edit<'i>, EditMismatch>> {
+        if let Err(err) = self.undone.last()?.apply(definitions) {
+            return Some(Err(err));
+        }
+        let edit = self.undone.pop()?;
+        self.done.push(edit);
+        self.done.last().map(Ok)
+    }
+}
+
+fn slot<'a, 'i>(
+    definitions: &'a mut [Definition<'i>],
+    index: u32,
+) -> Result<&'a mut Definition<'i>, EditMismatch> {
+    let len = definitions.len();
+    definitions
+        .get_mut(index as usize)
+        .ok_or(EditMismatch { index, len })
+}
+
+fn write_definition(definition: &Definition<'_>, out: &mut Vec<u8>) -> byte::Result<()> {
+    let body = definition.to_bytes(ENDIANESS)?;
+    let size = u32::try_from(body.len()).map_err(|_| byte::Error::BadInput {
+        err: "definition too large",
+    })?;
+    out.extend(DefinitionHeader::from_defintion(definition, size, 0).to_bytes(ENDIANESS)?);
+    out.extend(body);
+    Ok(())
+}
+
+fn read_definition<'i>(bytes: &'i [u8], offset: &mut usize) -> byte::Result<Definition<'i>> {
+    let header: DefinitionHeader = bytes.read(offset, ENDIANESS)?;
+    let rest = bytes.get(*offset..).ok_or(byte::Error::Incomplete)?;
+    let (mut definition, _) = Definition::try_read(rest, (ENDIANESS, header))?;
+    // edits are compared and applied with the code decoded
+    definition.decode_body()?;
+    *offset += header.size() as usize;
+    Ok(definition)
+}
+
+// an edit that doesn't fit the definitions it's applied to, e.g. a definition added back when
+// it's not the next one in order or a change to a slot that doesn't exist
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EditMismatch {
+    pub index: u32,
+    pub len: usize,
+}
+
+impl EditMismatch {
+    fn new(index: u32, definitions: &[Definition<'_>]) -> Self {
+        Self {
+            index,
+            len: definitions.len(),
+        }
+    }
+}
+
+impl fmt::Display for EditMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "edit of definition {} doesn't fit the {} definitions of the bundle",
+            self.index, self.len
+        )
+    }
+}
+
+impl std::error::Error for EditMismatch {}
+
+pub trait BundleObserver {
+    fn on_define(&mut self, _index: u32, _definition: &Definition<'_>) {}
+
+    fn on_remove(&mut self, _index: u32, _definition: &Definition<'_>) {}
+
+    fn on_modify(&mut self, _index: u32, _old: &Definition<'_>, _new: &Definition<'_>) {}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ObserverId(u32);
+
+#[derive(Default)]
+pub(crate) struct Observers {
+    entries: Vec<(ObserverId, Box<dyn BundleObserver + Send + Sync>)>,
+    next_id: u32,
+}
+
+impl Observers {
+    #[inline]
+    pub(crate) fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub(crate) fn add(&mut self, observer: Box<dyn BundleObserver + Send + Sync>) -> ObserverId {
+        let id = ObserverId(self.next_id);
+        self.next_id += 1;
+        self.entries.push((id, observer));
+        id
+    }
+
+    pub(crate) fn remove(
+        &mut self,
+        id: ObserverId,
+    ) -> Option<Box<dyn BundleObserver + Send + Sync>> {
+        let pos = self.entries.iter().position(|(i, _)| *i == id)?;
+        Some(self.entries.remove(pos).1)
+    }
+
+    pub(crate) fn on_define(&mut self, index: u32, definition: &Definition<'_>) {
+        for (_, observer) in &mut self.entries {
+            observer.on_define(index, definition);
+        }
+    }
+
+    pub(crate) fn on_remove(&mut self, index: u32, definition: &Definition<'_>) {
+        for (_, observer) in &mut self.entries {
+            observer.on_remove(index, definition);
+        }
+    }
+
+    pub(crate) fn on_modify(&mut self, index: u32, old: &Definition<'_>, new: &Definition<'_>) {
+        for (_, observer) in &mut self.entries {
+            observer.on_modify(index, old, new);
+        }
+    }
+
+    pub(crate) fn on_apply(&mut self, edit: &Edit<'_>) {
+        match edit {
+            Edit::Define { index, definition } => self.on_define(*index, definition),
+            Edit::Remove { index, definition } => self.on_remove(*index, definition),
+            Edit::Modify { index, old, new } => self.on_modify(*index, old, new),
+        }
+    }
+
+    pub(crate) fn on_revert(&mut self, edit: &Edit<'_>) {
+        match edit {
+            Edit::Define { index, definition } => self.on_remove(*index, definition),
+            Edit::Remove { index, definition } => self.on_define(*index, definition),
+            Edit::Modify { index, old, new } => self.on_modify(*index, new, old),
+        }
+    }
+}
+
+impl fmt::Debug for Observers {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Observers")
+            .field("count", &self.entries.len())
+            .finish()
+    }
+}
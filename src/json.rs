@@ -0,0 +1,60 @@
+use serde_json::{json, Map, Value};
+
+use crate::bundle::{PoolItemIndex, ScriptBundle};
+use crate::definition::Function;
+use crate::instr::{Instr, Offset, Operand};
+use crate::symbols;
+
+impl Instr<Offset> {
+    // a compact json form with named operands and resolved names, indices that can't be resolved
+    // are written as numbers, e.g. `{"op":"InvokeStatic","function":"Log;String","line":12,...}`
+    pub fn to_json(&self, bundle: &ScriptBundle<'_>) -> Value {
+        let mut obj = Map::new();
+        obj.insert("op".to_owned(), self.name().into());
+        for (key, operand) in self.operands() {
+            let value = match operand {
+                Operand::Int(v) => v.into(),
+                Operand::UInt(v) => v.into(),
+                Operand::Float(v) => v.into(),
+                Operand::CName(index) => pool(bundle, index),
+                Operand::String(index) => pool(bundle, index),
+                Operand::TweakDbId(index) => pool(bundle, index),
+                Operand::Resource(index) => pool(bundle, index),
+                Operand::Definition(index) => {
+                    symbols::qualified_name(bundle, index).map_or_else(|| index.into(), Value::from)
+                }
+                Operand::Type(index) => match bundle.get_item(index) {
+                    Some(typ) => typ.display(bundle).to_string().into(),
+                    None => u32::from(index).into(),
+                },
+                Operand::Offset(offset) => i16::from(offset).into(),
+            };
+            obj.insert(key.to_owned(), value);
+        }
+        Value::Object(obj)
+    }
+}
+
+impl Function<'_> {
+    // the json form of every instruction along with its byte offset
+    pub fn code_to_json(&self, bundle: &ScriptBundle<'_>) -> byte::Result<Value> {
+        self.body()
+            .code_iter()
+            .with_offsets()
+            .map(|(offset, instr)| {
+                let mut value = instr?.to_json(bundle);
+                value["offset"] = json!(u32::from(offset));
+                Ok(value)
+            })
+            .collect()
+    }
+}
+
+fn pool<'i, I>(bundle: &ScriptBundle<'i>, index: I) -> Value
+where
+    I: PoolItemIndex<'i, Output = str> + Copy + Into<u32>,
+{
+    bundle
+        .get_item(index)
+        .map_or_else(|| index.into().into(), Value::from)
+}
@@ -0,0 +1,150 @@
+use std::fmt::Write;
+
+use crate::bundle::Header;
+use crate::definition::DefinitionKind;
+use crate::instr::Opcode;
+
+// tables of the header in the order they're stored, with the types of their items
+const TABLES: [(&str, &str); 6] = [
+    ("string_data", "u1"),
+    ("cnames", "string_ref"),
+    ("tweakdb_ids", "string_ref"),
+    ("resources", "string_ref"),
+    ("definitions", "definition_header"),
+    ("strings", "string_ref"),
+];
+
+// generates a Kaitai Struct description of the bundle format for inspecting caches in hex
+// editors, it covers the header, the string pools and the definition headers, the bodies of
+// definitions are exposed as raw bytes
+pub fn kaitai_struct() -> String {
+    let mut out = String::new();
+    write_spec(&mut out).expect("writing to a string should not fail");
+    out
+}
+
+fn write_spec(out: &mut String) -> std::fmt::Result {
+    writeln!(out, "meta:")?;
+    writeln!(out, "  id: redscript_bundle")?;
+    writeln!(out, "  file-extension: redscripts")?;
+    writeln!(out, "  endian: le")?;
+    writeln!(out, "  bit-endian: le")?;
+    writeln!(out, "seq:")?;
+    writeln!(out, "  - id: header")?;
+    writeln!(out, "    type: header")?;
+    writeln!(out, "    size: {}", Header::SIZE)?;
+
+    writeln!(out, "instances:")?;
+    for (name, typ) in TABLES {
+        writeln!(out, "  {name}:")?;
+        writeln!(out, "    pos: header.{name}.offset")?;
+        if typ == "u1" {
+            writeln!(out, "    size: header.{name}.count")?;
+        } else {
+            writeln!(out, "    type: {typ}")?;
+            writeln!(out, "    repeat: expr")?;
+            writeln!(out, "    repeat-expr: header.{name}.count")?;
+        }
+    }
+
+    writeln!(out, "types:")?;
+    writeln!(out, "  header:")?;
+    writeln!(out, "    seq:")?;
+    write_field(out, "magic", &format!("contents: {:?}", Header::MAGIC))?;
+    write_field(out, "version", "type: u4")?;
+    writeln!(out, "        valid: {}", Header::SUPPORTED_VERSION)?;
+    write_field(out, "flags", "type: u4")?;
+    write_field(out, "timestamp", "type: timestamp")?;
+    write_field(out, "build", "type: u4")?;
+    write_field(out, "crc", "type: u4")?;
+    write_field(out, "segments", "type: u4")?;
+    for (name, _) in TABLES {
+        write_field(out, name, "type: table_header")?;
+    }
+
+    writeln!(out, "  timestamp:")?;
+    writeln!(out, "    seq:")?;
+    let bits = [
+        ("reserved1", 10),
+        ("day", 5),
+        ("month", 5),
+        ("year", 12),
+        ("millis", 10),
+        ("seconds", 6),
+        ("minutes", 6),
+        ("hours", 6),
+        ("reserved2", 4),
+    ];
+    for (name, size) in bits {
+        write_field(out, name, &format!("type: b{size}"))?;
+    }
+
+    writeln!(out, "  table_header:")?;
+    writeln!(out, "    seq:")?;
+    write_field(out, "offset", "type: u4")?;
+    write_field(out, "count", "type: u4")?;
+    write_field(out, "hash", "type: u4")?;
+
+    writeln!(out, "  string_ref:")?;
+    writeln!(out, "    seq:")?;
+    write_field(out, "offset", "type: u4")?;
+    writeln!(out, "    instances:")?;
+    writeln!(out, "      value:")?;
+    writeln!(out, "        io: _root._io")?;
+    writeln!(out, "        pos: _root.header.string_data.offset + offset")?;
+    writeln!(out, "        type: strz")?;
+    writeln!(out, "        encoding: UTF-8")?;
+
+    writeln!(out, "  definition_header:")?;
+    writeln!(out, "    seq:")?;
+    write_field(out, "name", "type: u4")?;
+    write_field(out, "parent", "type: u4")?;
+    write_field(out, "offset", "type: u4")?;
+    write_field(out, "size", "type: u4")?;
+    write_field(out, "kind", "type: u2")?;
+    writeln!(out, "        enum: definition_kind")?;
+    write_field(out, "padding", "size: 2")?;
+    writeln!(out, "    instances:")?;
+    writeln!(out, "      body:")?;
+    writeln!(out, "        io: _root._io")?;
+    writeln!(out, "        pos: offset")?;
+    writeln!(out, "        size: size")?;
+
+    writeln!(out, "enums:")?;
+    writeln!(out, "  definition_kind:")?;
+    for kind in DefinitionKind::ALL {
+        writeln!(out, "    {}: {}", kind.tag(), snake_case(kind.name()))?;
+    }
+    writeln!(out, "  opcode:")?;
+    for opcode in Opcode::ALL {
+        writeln!(
+            out,
+            "    {:#04x}: {}",
+            opcode.tag(),
+            snake_case(opcode.name())
+        )?;
+    }
+    Ok(())
+}
+
+#[inline]
+fn write_field(out: &mut String, name: &str, typ: &str) -> std::fmt::Result {
+    writeln!(out, "      - id: {name}")?;
+    writeln!(out, "        {typ}")
+}
+
+// Kaitai identifiers have to be lowercase
+fn snake_case(name: &str) -> String {
+    let mut out = String::with_capacity(name.len() + 4);
+    for (i, c) in name.chars().enumerate() {
+        if c.is_ascii_uppercase() {
+            if i > 0 {
+                out.push('_');
+            }
+            out.push(c.to_ascii_lowercase());
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
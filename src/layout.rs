@@ -0,0 +1,170 @@
+use std::fmt;
+
+use crate::bundle::ScriptBundle;
+use crate::definition::{Class, Type, TypeKind};
+use crate::index::FieldIndex;
+
+const POINTER_SIZE: u32 = 8;
+// guards against cyclic bases and structs embedding themselves
+const MAX_DEPTH: usize = 64;
+
+// an approximation of how an instance of a class is laid out in memory at runtime
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ClassLayout {
+    // in bytes, including inherited fields and padding
+    pub size: u32,
+    pub alignment: u32,
+    // in the order they're laid out, inherited fields come first
+    pub fields: Vec<FieldLayout>,
+    pub inherited_fields: usize,
+    // fields holding a strong, weak or script reference
+    pub ref_fields: usize,
+    pub value_fields: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FieldLayout {
+    pub field: FieldIndex,
+    pub offset: u32,
+    pub size: u32,
+}
+
+impl Class {
+    // sizes of native types are estimates and types that can't be resolved are assumed to be
+    // pointer-sized, so the result is only good for comparisons, fails when the size of the class
+    // doesn't fit in 32 bits
+    #[inline]
+    pub fn layout(&self, bundle: &ScriptBundle<'_>) -> Result<ClassLayout, LayoutOverflow> {
+        class_layout(bundle, self, 0)
+    }
+}
+
+fn class_layout(
+    bundle: &ScriptBundle<'_>,
+    class: &Class,
+    depth: usize,
+) -> Result<ClassLayout, LayoutOverflow> {
+    let base = class
+        .base()
+        .and_then(|base| bundle.get_item(base))
+        .filter(|_| depth < MAX_DEPTH);
+    let mut layout = match base {
+        Some(base) => {
+            let mut layout = class_layout(bundle, base, depth + 1)?;
+            layout.inherited_fields = layout.fields.len();
+            layout
+        }
+        None if class.flags().is_struct() => ClassLayout {
+            alignment: 1,
+            ..ClassLayout::default()
+        },
+        // room for the vtable
+        None => ClassLayout {
+            size: POINTER_SIZE,
+            alignment: POINTER_SIZE,
+            ..ClassLayout::default()
+        },
+    };
+
+    for &index in class.fields() {
+        let Some(field) = bundle.get_item(index) else {
+            continue;
+        };
+        let typ = bundle.get_item(field.typ());
+        let (size, alignment) = match typ {
+            Some(typ) => type_size(bundle, typ, depth)?,
+            None => (POINTER_SIZE, POINTER_SIZE),
+        };
+        if typ.is_some_and(Type::is_ref_like) {
+            layout.ref_fields += 1;
+        } else {
+            layout.value_fields += 1;
+        }
+        let offset = align_up(layout.size, alignment)?;
+        layout.fields.push(FieldLayout {
+            field: index,
+            offset,
+            size,
+        });
+        layout.size = offset.checked_add(size).ok_or(LayoutOverflow)?;
+        layout.alignment = layout.alignment.max(alignment);
+    }
+    layout.size = align_up(layout.size, layout.alignment)?;
+    Ok(layout)
+}
+
+// returns the size and the alignment of a value of the type
+fn type_size(
+    bundle: &ScriptBundle<'_>,
+    typ: &Type,
+    depth: usize,
+) -> Result<(u32, u32), LayoutOverflow> {
+    let res = match typ.kind() {
+        TypeKind::Primitive => {
+            let size = match bundle.get_item(typ.name()).unwrap_or_default() {
+                "Bool" | "Int8" | "Uint8" => 1,
+                "Int16" | "Uint16" => 2,
+                "Int32" | "Uint32" | "Float" => 4,
+                "String" => return Ok((32, POINTER_SIZE)),
+                "Variant" => return Ok((24, POINTER_SIZE)),
+                _ => 8,
+            };
+            (size, size)
+        }
+        TypeKind::Class => {
+            if let Some(enum_) = bundle.enum_by_name(typ.name()) {
+                let size = u32::from(enum_.size()).max(1);
+                return Ok((size, size));
+            }
+            match bundle
+                .classes()
+                .find(|(_, c)| c.name() == typ.name() && c.flags().is_struct())
+            {
+                Some((_, struct_)) if depth < MAX_DEPTH => {
+                    let layout = class_layout(bundle, struct_, depth + 1)?;
+                    (layout.size, layout.alignment)
+                }
+                _ => (POINTER_SIZE, POINTER_SIZE),
+            }
+        }
+        // a pointer to the object and one to its reference count
+        TypeKind::Box(_) | TypeKind::WeakRef(_) | TypeKind::ScriptRef(_) => {
+            (2 * POINTER_SIZE, POINTER_SIZE)
+        }
+        // a pointer to the elements followed by the capacity and the length
+        TypeKind::Array(_) => (2 * POINTER_SIZE, POINTER_SIZE),
+        // the elements are stored inline and followed by the length
+        &TypeKind::StaticArray { element_type, size } => {
+            let (element_size, alignment) =
+                match bundle.get_item(element_type).filter(|_| depth < MAX_DEPTH) {
+                    Some(element) => type_size(bundle, element, depth + 1)?,
+                    None => (POINTER_SIZE, POINTER_SIZE),
+                };
+            let alignment = alignment.max(4);
+            let elements = element_size.checked_mul(size).ok_or(LayoutOverflow)?;
+            let elements = align_up(elements, 4)?;
+            let total = elements.checked_add(4).ok_or(LayoutOverflow)?;
+            (align_up(total, alignment)?, alignment)
+        }
+    };
+    Ok(res)
+}
+
+#[inline]
+fn align_up(value: u32, alignment: u32) -> Result<u32, LayoutOverflow> {
+    value
+        .checked_next_multiple_of(alignment.max(1))
+        .ok_or(LayoutOverflow)
+}
+
+// the estimated size of a class exceeds 32 bits
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LayoutOverflow;
+
+impl fmt::Display for LayoutOverflow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("the size of the class exceeds the range of u32")
+    }
+}
+
+impl std::error::Error for LayoutOverflow {}
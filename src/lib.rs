@@ -1,26 +1,145 @@
 use byte::ctx::LittleEndian;
 
+mod annotate;
+mod assemble;
 mod bundle;
+mod cname;
+#[cfg(any(feature = "lz4", feature = "zstd"))]
+mod compression;
+mod constants;
+mod coverage;
 mod definition;
+#[cfg(feature = "differential")]
+mod differential;
+mod disasm;
+mod dump;
+mod emit;
+mod filters;
+mod fingerprint;
+mod flags;
+mod imports;
 mod index;
+mod indexed;
+mod install;
 mod instr;
+#[cfg(feature = "interp")]
+mod interp;
+mod inventory;
+mod journal;
+#[cfg(feature = "serde_json")]
+mod json;
+mod kaitai;
+mod layout;
+mod lookup;
+mod mangle;
+mod manifest;
+mod monitor;
+mod names;
+mod operator;
+mod order;
+mod origin;
+mod overlay;
+pub mod prelude;
+mod provenance;
+mod rename;
+mod retarget;
+#[cfg(feature = "rtti")]
+mod rtti;
+mod script;
+#[cfg(feature = "sqlite")]
+mod sqlite;
+mod structure;
+#[cfg(feature = "interp")]
+mod symbolic;
+mod symbols;
+mod syntax;
+mod usage;
 mod util;
+mod validate;
 
 const ENDIANESS: LittleEndian = byte::LE;
 
-pub use bundle::{BundleReader, PoolItemIndex, PoolItemIndexMut, ScriptBundle};
+pub use annotate::{Annotation, AnnotationTarget, Annotations};
+pub use assemble::{
+    AssembleError, BodyScope, BundleAssembler, ClassSpec, FieldSpec, FunctionSpec, ModuleSpec,
+};
+pub use bundle::{
+    encode_definition, BundleKind, BundleReader, DecodeTimings, EnumSizeRepair, EnumsByName,
+    HashMismatch, HashPolicy, LinkRepair, LookupMode, ParseLimits, PoolBytes, PoolError,
+    PoolItemIndex, PoolItemIndexMut, PreflightError, ReadOptions, ScriptBundle, Section,
+    StringPool, WriteReport,
+};
+#[cfg(feature = "mmap")]
+pub use bundle::{SaveError, SaveOptions};
 pub use byte::{Error, Result};
+pub use cname::CName;
+#[cfg(any(feature = "lz4", feature = "zstd"))]
+pub use compression::{
+    decompress, decompress_with_limit, Compression, CompressionError, DEFAULT_SIZE_LIMIT,
+};
+pub use constants::{ConstReplacement, ConstantEntry, ConstantTable};
+pub use coverage::{CoverageMap, CoverageReport, FunctionBlocks, FunctionCoverage};
 pub use definition::{
-    Class, ClassFlags, CodeIter, CowCodeIter, Definition, Enum, EnumMember, Field, FieldFlags,
-    Function, FunctionBody, FunctionFlags, Local, LocalFlags, Parameter, ParameterFlags, Property,
-    SourceFile, SourceReference, Type, TypeKind, Visibility,
+    Class, ClassFlags, CodeError, CodeIter, CowCodeIter, Definition, DefinitionHeader,
+    DefinitionKind, Enum, EnumMember, Field, FieldFlags, Function, FunctionBody, FunctionFlags,
+    Local, LocalFlags, NormalizedCode, Parameter, ParameterFlags, PoolKind, Property, SourceFile,
+    SourceReference, Type, TypeKind, Visibility,
 };
+#[cfg(feature = "differential")]
+pub use differential::{Divergence, DivergenceKind, ReferenceDecoder};
+pub use disasm::{DisasmOptions, Disassembly, Verbosity};
+pub use dump::DefinitionDump;
+pub use emit::{ExprType, TypeError, TypedEmitter, TypedExpr};
+pub use filters::{ClassFilters, FunctionFilters};
+pub use fingerprint::{
+    find_clones, match_functions, match_functions_with, CloneMember, CloneOptions, Fingerprint,
+    FunctionMatch, MatchOptions,
+};
+pub use flags::ParseFlagsError;
+pub use imports::{ImportKind, ImportTable, NativeImport};
 pub use index::{
-    CNameIndex, ClassIndex, EnumIndex, EnumValueIndex, FieldIndex, FunctionIndex, LocalIndex,
-    ParameterIndex, ResourceIndex, SourceFileIndex, StringIndex, TweakDbIndex, TypeIndex,
+    CNameIndex, ClassIndex, DisplayWith, EnumIndex, EnumValueIndex, FieldIndex, FunctionIndex,
+    IndexOverflow, IndexRange, LocalIndex, ParameterIndex, RawIndex, ResourceIndex,
+    SourceFileIndex, StringIndex, TweakDbIndex, TypeIndex,
+};
+pub use indexed::IndexedBundle;
+pub use install::{CacheKind, CacheLocation, GameCaches};
+pub use instr::{
+    resolve_targets, Breakpoint, ByteOffset, Conditional, Constant, EncodedOffset, Instr,
+    InstrIndex, Jump, Label, Offset, OffsetMap, Opcode, Profile, Switch, SwitchLabel,
 };
-pub use instr::{Breakpoint, Conditional, Instr, Jump, Offset, Profile, Switch, SwitchLabel};
+#[cfg(feature = "interp")]
+pub use interp::{InterpError, Interpreter, NativeFn, Value};
+pub use inventory::{compare_inventory, InventoryDiff, InventoryEntry, SignatureChange};
+pub use journal::{BundleObserver, Edit, EditMismatch, Journal, ObserverId};
+pub use kaitai::kaitai_struct;
+pub use layout::{ClassLayout, FieldLayout, LayoutOverflow};
+pub use lookup::{Hover, Reference, SymbolLookup};
+pub use mangle::{FunctionSignature, GameMangler, NameMangler};
+#[cfg(feature = "signing")]
+pub use manifest::VerifyError;
+pub use manifest::{DefinitionDigest, HeaderDigest, Manifest, PoolDigest};
+pub use monitor::{CancellationToken, Monitor, Phase, Progress, ProgressSink};
+pub use names::{NameCache, NameError};
+pub use operator::{find_operator, FunctionKind, OperatorKind, UnknownOperator};
+pub use order::SortError;
+pub use origin::{Evidence, Origin, OriginReport};
+pub use overlay::{OverlayBundle, OverlayItemIndex};
+pub use provenance::{ChangeKind, Provenance};
+pub use rename::RenameError;
+pub use retarget::{RetargetError, RetargetOptions};
+#[cfg(feature = "rtti")]
+pub use rtti::{RttiClass, RttiDump, RttiFunction, RttiReport};
+pub use structure::{CodeStructure, Region, SwitchCase};
+#[cfg(feature = "interp")]
+pub use symbolic::{Branch, Counterexample, PathProperty, SymbolicWalker, Verification};
+pub use symbols::{Symbol, SymbolTable};
+pub use syntax::{TypeDisplay, TypeParseError};
+pub use usage::PoolUsage;
+pub use util::{extended_path, fnv1a64};
+pub use validate::{FixSet, Issue};
 
+// with the `shared` feature strings are reference counted atomically and can cross threads
 #[cfg(not(feature = "shared"))]
 pub type Str<'a> = hipstr::LocalHipStr<'a>;
 
@@ -0,0 +1,133 @@
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+use crate::bundle::{PoolItemIndex, ScriptBundle};
+use crate::definition::{Definition, DefinitionKind};
+use crate::index::{CNameIndex, FunctionIndex, NzPoolIndex};
+use crate::instr::{ByteOffset, Instr};
+use crate::symbols;
+use crate::util::RandomState;
+
+// name and reference queries over a single bundle, the indices are built lazily on first use
+#[derive(Debug)]
+pub struct SymbolLookup<'a, 'i> {
+    bundle: &'a ScriptBundle<'i>,
+    by_name: OnceLock<HashMap<String, u32, RandomState>>,
+    references: OnceLock<References>,
+}
+
+#[derive(Debug, Default)]
+struct References {
+    by_index: HashMap<u32, Vec<Reference>, RandomState>,
+    // virtual calls are resolved at runtime, only their names are known
+    by_name: HashMap<CNameIndex, Vec<Reference>, RandomState>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Reference {
+    pub function: FunctionIndex,
+    pub offset: ByteOffset,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hover<'a> {
+    pub name: String,
+    pub kind: DefinitionKind,
+    pub signature: String,
+    pub flags: u32,
+    // path of the source file and the line number
+    pub source: Option<(&'a str, u32)>,
+}
+
+impl<'a, 'i> SymbolLookup<'a, 'i> {
+    pub fn new(bundle: &'a ScriptBundle<'i>) -> Self {
+        Self {
+            bundle,
+            by_name: OnceLock::new(),
+            references: OnceLock::new(),
+        }
+    }
+
+    // finds a definition by its fully-qualified name, e.g. `Class.Function`
+    pub fn definition(&self, name: &str) -> Option<u32> {
+        self.by_name
+            .get_or_init(|| {
+                (1..self.bundle.definition_count())
+                    .filter_map(|i| Some((symbols::qualified_name(self.bundle, i)?, i)))
+                    .collect()
+            })
+            .get(name)
+            .copied()
+    }
+
+    // like `definition`, but only succeeds if the definition is of the expected kind
+    pub fn find<A>(&self, name: &str) -> Option<NzPoolIndex<A>>
+    where
+        NzPoolIndex<A>: PoolItemIndex<'i>,
+    {
+        let index = NzPoolIndex::new(self.definition(name)?)?;
+        self.bundle.get_item(index)?;
+        Some(index)
+    }
+
+    // instructions referring to a definition, virtual calls are matched by the function name
+    pub fn references<A>(&self, index: NzPoolIndex<A>) -> impl Iterator<Item = Reference> + '_ {
+        let refs = self.references.get_or_init(|| self.collect_references());
+        let index = u32::from(index);
+        let by_index = refs.by_index.get(&index).into_iter().flatten();
+        let by_name = match self.bundle.definition(index) {
+            Some(Definition::Function(fun)) => refs.by_name.get(&fun.name()),
+            _ => None,
+        };
+        by_index.chain(by_name.into_iter().flatten()).copied()
+    }
+
+    pub fn hover<A>(&self, index: NzPoolIndex<A>) -> Option<Hover<'a>> {
+        let index = u32::from(index);
+        let def = self.bundle.definition(index)?;
+        let source = match def {
+            Definition::Function(fun) => fun.source().and_then(|source| {
+                let file = self.bundle.get_item(source.file())?;
+                Some((file.path(), source.line()))
+            }),
+            _ => None,
+        };
+        Some(Hover {
+            name: symbols::qualified_name(self.bundle, index)?,
+            kind: def.kind(),
+            signature: symbols::signature(self.bundle, def),
+            flags: symbols::flags(def),
+            source,
+        })
+    }
+
+    fn collect_references(&self) -> References {
+        let mut refs = References::default();
+        for (function, fun) in self.bundle.functions() {
+            let code = fun.body().code_iter().with_offsets();
+            for (offset, instr) in code.map_while(|(o, i)| Some((o, i.ok()?))) {
+                let reference = Reference { function, offset };
+                match instr {
+                    Instr::InvokeVirtual { function, .. } => {
+                        refs.by_name.entry(function).or_default().push(reference);
+                    }
+                    instr => {
+                        if let Some(target) = instr.referenced_definition() {
+                            refs.by_index.entry(target).or_default().push(reference);
+                        }
+                    }
+                }
+            }
+        }
+        refs
+    }
+}
+
+impl<'i> ScriptBundle<'i> {
+    // queries by qualified names that are built once per lookup, `IndexedBundle` keeps an index of
+    // the short names up to date through edits instead
+    #[inline]
+    pub fn symbol_lookup(&self) -> SymbolLookup<'_, 'i> {
+        SymbolLookup::new(self)
+    }
+}
@@ -0,0 +1,97 @@
+use crate::bundle::ScriptBundle;
+use crate::definition::Function;
+use crate::index::{FunctionIndex, TypeIndex};
+use crate::operator::FunctionKind;
+
+// the scheme used to encode signatures of functions in their names, it differs between compilers
+pub trait NameMangler {
+    fn mangle(&self, signature: &FunctionSignature<'_>) -> String;
+
+    // returns the name without the signature part
+    fn demangle<'a>(&self, name: &'a str) -> &'a str;
+}
+
+// `Name;ParamType1ParamType2`, with the return type appended as `;ReturnType` for operators and
+// casts since they can be overloaded on it
+#[derive(Debug, Default, Clone, Copy)]
+pub struct GameMangler;
+
+impl NameMangler for GameMangler {
+    fn mangle(&self, signature: &FunctionSignature<'_>) -> String {
+        let mut name = format!("{};{}", signature.name, signature.parameters.concat());
+        if let (FunctionKind::Operator(_) | FunctionKind::Cast { .. }, Some(ret)) =
+            (signature.kind, &signature.return_type)
+        {
+            name.push(';');
+            name.push_str(ret);
+        }
+        name
+    }
+
+    #[inline]
+    fn demangle<'a>(&self, name: &'a str) -> &'a str {
+        name.split_once(';').map_or(name, |(name, _)| name)
+    }
+}
+
+// the parts of a function that make up its mangled name, types are given by the names they
+// have in the bundle, e.g. `array:handle:GameObject`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FunctionSignature<'a> {
+    pub name: &'a str,
+    pub parameters: Vec<&'a str>,
+    pub return_type: Option<&'a str>,
+    pub kind: FunctionKind,
+}
+
+impl Function<'_> {
+    // the signature of the function with its name demangled by the given scheme
+    pub fn signature<'a>(
+        &self,
+        bundle: &'a ScriptBundle<'_>,
+        mangler: &dyn NameMangler,
+    ) -> Option<FunctionSignature<'a>> {
+        let type_name = |index: TypeIndex| bundle.get_item(bundle.get_item(index)?.name());
+        let parameters = self
+            .parameters()
+            .iter()
+            .map(|&param| type_name(bundle.get_item(param)?.typ()))
+            .collect::<Option<Vec<_>>>()?;
+        let return_type = match self.return_type(bundle) {
+            Some(typ) => Some(type_name(typ)?),
+            None => None,
+        };
+        Some(FunctionSignature {
+            name: mangler.demangle(bundle.get_item(self.name())?),
+            parameters,
+            return_type,
+            kind: self.kind(bundle),
+        })
+    }
+
+    // the name the function would be stored under by a compiler using the given scheme
+    #[inline]
+    pub fn mangled_name(
+        &self,
+        bundle: &ScriptBundle<'_>,
+        mangler: &dyn NameMangler,
+    ) -> Option<String> {
+        Some(mangler.mangle(&self.signature(bundle, mangler)?))
+    }
+}
+
+impl ScriptBundle<'_> {
+    // functions whose names demangle to the given name, e.g. every overload of a method
+    pub fn overloads<'a>(
+        &'a self,
+        name: &'a str,
+        mangler: &'a dyn NameMangler,
+    ) -> impl Iterator<Item = FunctionIndex> + 'a {
+        self.functions()
+            .filter(move |(_, fun)| {
+                self.get_item(fun.name())
+                    .is_some_and(|fun| mangler.demangle(fun) == name)
+            })
+            .map(|(index, _)| index)
+    }
+}
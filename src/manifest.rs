@@ -0,0 +1,170 @@
+use byte::ToBytesExt;
+use sha2::{Digest, Sha256};
+
+use crate::bundle::{Header, ScriptBundle};
+use crate::ENDIANESS;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Manifest {
+    pub header: HeaderDigest,
+    pub cnames: PoolDigest,
+    pub tdb_ids: PoolDigest,
+    pub resources: PoolDigest,
+    pub strings: PoolDigest,
+    pub definitions: Vec<DefinitionDigest>,
+}
+
+impl Manifest {
+    // returns the indices of definitions that differ from or are missing in the other manifest
+    pub fn mismatches<'a>(&'a self, other: &'a Manifest) -> impl Iterator<Item = u32> + 'a {
+        let longest = self.definitions.len().max(other.definitions.len());
+        (0..longest)
+            .filter(|&i| self.definitions.get(i) != other.definitions.get(i))
+            .map(|i| i as u32)
+    }
+}
+
+// the header the bundle is written with, the timestamp and the build are only known for bundles
+// read with their layout and are zero otherwise
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct HeaderDigest {
+    pub version: u32,
+    // packed the way the game stores it
+    pub timestamp: u64,
+    pub build: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PoolDigest {
+    pub count: u32,
+    // sha-256 of the strings, each followed by a zero byte
+    pub hash: [u8; 32],
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DefinitionDigest {
+    pub name: String,
+    pub tag: u16,
+    // sha-256 of the encoded definition, it has to be collision resistant for signatures to mean
+    // anything
+    pub hash: [u8; 32],
+}
+
+#[cfg(feature = "signing")]
+impl Manifest {
+    #[inline]
+    pub fn sign(&self, key: &ed25519_dalek::SigningKey) -> ed25519_dalek::Signature {
+        use ed25519_dalek::Signer;
+
+        key.sign(&self.signed_bytes())
+    }
+
+    #[inline]
+    pub fn verify_signature(
+        &self,
+        key: &ed25519_dalek::VerifyingKey,
+        signature: &ed25519_dalek::Signature,
+    ) -> Result<(), ed25519_dalek::SignatureError> {
+        key.verify_strict(&self.signed_bytes(), signature)
+    }
+
+    // a canonical encoding that doesn't depend on how the manifest is serialized
+    fn signed_bytes(&self) -> Vec<u8> {
+        let mut bytes = b"redscript-io manifest v1\0".to_vec();
+        bytes.extend(self.header.version.to_le_bytes());
+        bytes.extend(self.header.timestamp.to_le_bytes());
+        bytes.extend(self.header.build.to_le_bytes());
+        for pool in [&self.cnames, &self.tdb_ids, &self.resources, &self.strings] {
+            bytes.extend(pool.count.to_le_bytes());
+            bytes.extend(pool.hash);
+        }
+        bytes.extend((self.definitions.len() as u32).to_le_bytes());
+        for def in &self.definitions {
+            bytes.extend((def.name.len() as u32).to_le_bytes());
+            bytes.extend(def.name.as_bytes());
+            bytes.extend(def.tag.to_le_bytes());
+            bytes.extend(def.hash);
+        }
+        bytes
+    }
+}
+
+#[cfg(feature = "signing")]
+#[derive(Debug)]
+pub enum VerifyError {
+    Encoding(byte::Error),
+    Signature(ed25519_dalek::SignatureError),
+}
+
+#[cfg(feature = "signing")]
+impl std::fmt::Display for VerifyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            VerifyError::Encoding(err) => write!(f, "failed to encode the bundle: {err:?}"),
+            VerifyError::Signature(err) => write!(f, "signature mismatch: {err}"),
+        }
+    }
+}
+
+#[cfg(feature = "signing")]
+impl std::error::Error for VerifyError {}
+
+impl ScriptBundle<'_> {
+    // checks that the bundle matches a manifest signed with the key, e.g. by the author of a mod
+    #[cfg(feature = "signing")]
+    pub fn verify_signature(
+        &self,
+        key: &ed25519_dalek::VerifyingKey,
+        signature: &ed25519_dalek::Signature,
+    ) -> Result<(), VerifyError> {
+        self.manifest()
+            .map_err(VerifyError::Encoding)?
+            .verify_signature(key, signature)
+            .map_err(VerifyError::Signature)
+    }
+
+    pub fn manifest(&self) -> byte::Result<Manifest> {
+        let definitions = (0..self.definition_count())
+            .filter_map(|i| self.definition(i))
+            .map(|def| {
+                let name = self.get_item(def.name()).unwrap_or_default();
+                Ok(DefinitionDigest {
+                    name: name.to_owned(),
+                    tag: def.tag(),
+                    hash: Sha256::digest(def.to_bytes(ENDIANESS)?).into(),
+                })
+            })
+            .collect::<byte::Result<_>>()?;
+
+        Ok(Manifest {
+            header: HeaderDigest {
+                version: Header::SUPPORTED_VERSION,
+                timestamp: self.original_timestamp().unwrap_or_default(),
+                build: self.original_build().unwrap_or_default(),
+            },
+            cnames: pool_digest(self.cnames().iter()),
+            tdb_ids: pool_digest(self.tdb_ids().iter()),
+            resources: pool_digest(self.resources().iter()),
+            strings: pool_digest(self.strings().iter()),
+            definitions,
+        })
+    }
+}
+
+fn pool_digest<'a>(strings: impl Iterator<Item = &'a str>) -> PoolDigest {
+    let mut hasher = Sha256::new();
+    let mut count = 0;
+    for string in strings {
+        hasher.update(string.as_bytes());
+        hasher.update([0]);
+        count += 1;
+    }
+    PoolDigest {
+        count,
+        hash: hasher.finalize().into(),
+    }
+}
@@ -0,0 +1,120 @@
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    Parse,
+    // adding the classes and functions of the modules passed to BundleAssembler
+    Merge,
+    Validate,
+    Write,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Progress {
+    pub phase: Phase,
+    // number of definitions processed so far
+    pub done: u32,
+    pub total: u32,
+}
+
+impl Progress {
+    #[inline]
+    pub fn percent(&self) -> f32 {
+        if self.total == 0 {
+            100.
+        } else {
+            self.done as f32 * 100. / self.total as f32
+        }
+    }
+}
+
+pub trait ProgressSink {
+    fn report(&mut self, progress: Progress);
+}
+
+impl<F: FnMut(Progress)> ProgressSink for F {
+    #[inline]
+    fn report(&mut self, progress: Progress) {
+        self(progress);
+    }
+}
+
+// can be cloned and cancelled from another thread, the operation fails with an error
+// at the next definition boundary
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[inline]
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    #[inline]
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+// observes long running operations, passed to the `*_monitored` variants of read, write,
+// validation and assembly
+#[derive(Default)]
+pub struct Monitor<'a> {
+    progress: Option<&'a mut dyn ProgressSink>,
+    cancellation: Option<&'a CancellationToken>,
+}
+
+impl<'a> Monitor<'a> {
+    // how many definitions are processed between progress reports
+    const STEP: u32 = 1024;
+
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[inline]
+    pub fn with_progress(mut self, sink: &'a mut dyn ProgressSink) -> Self {
+        self.progress = Some(sink);
+        self
+    }
+
+    #[inline]
+    pub fn with_cancellation(mut self, token: &'a CancellationToken) -> Self {
+        self.cancellation = Some(token);
+        self
+    }
+
+    pub(crate) fn step(&mut self, phase: Phase, done: u32, total: u32) -> byte::Result<()> {
+        if self
+            .cancellation
+            .is_some_and(CancellationToken::is_cancelled)
+        {
+            return Err(byte::Error::BadInput {
+                err: "operation cancelled",
+            });
+        }
+        if done.is_multiple_of(Self::STEP) || done == total {
+            if let Some(sink) = &mut self.progress {
+                sink.report(Progress { phase, done, total });
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for Monitor<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Monitor")
+            .field("progress", &self.progress.is_some())
+            .field("cancellation", &self.cancellation)
+            .finish()
+    }
+}
@@ -0,0 +1,97 @@
+use std::fmt;
+use std::sync::OnceLock;
+
+use crate::bundle::ScriptBundle;
+use crate::index::NzPoolIndex;
+
+// lazily computed fully-qualified names (`Class.Function`) of definitions,
+// the cache does not borrow the bundle so it can be shared across threads
+#[derive(Debug, Default)]
+pub struct NameCache {
+    names: Vec<OnceLock<Box<str>>>,
+}
+
+impl NameCache {
+    pub fn new(bundle: &ScriptBundle<'_>) -> Self {
+        Self {
+            names: (0..bundle.definition_count())
+                .map(|_| OnceLock::new())
+                .collect(),
+        }
+    }
+
+    // fails if the definition can't be found or one of its parents is its own ancestor
+    #[inline]
+    pub fn get<'a, A>(
+        &'a self,
+        bundle: &ScriptBundle<'_>,
+        index: NzPoolIndex<A>,
+    ) -> Result<&'a str, NameError> {
+        self.resolve(bundle, index.into())
+    }
+
+    // drops all cached names, should be called after the bundle is modified
+    pub fn clear(&mut self, bundle: &ScriptBundle<'_>) {
+        *self = Self::new(bundle);
+    }
+
+    fn resolve<'a>(&'a self, bundle: &ScriptBundle<'_>, index: u32) -> Result<&'a str, NameError> {
+        // walk up to the first cached or top-level parent, a chain longer than the number of
+        // definitions has to go around in a cycle
+        let mut chain = vec![];
+        let mut prefix = None;
+        let mut current = index;
+        while let Some(slot) = self.names.get(current as usize) {
+            if let Some(name) = slot.get() {
+                prefix = Some(&**name);
+                break;
+            }
+            let Some(def) = bundle.definition(current) else {
+                break;
+            };
+            if chain.len() == self.names.len() {
+                return Err(NameError::Cycle(index));
+            }
+            chain.push((slot, def.name()));
+            current = def.parent();
+            if current == 0 {
+                break;
+            }
+        }
+
+        let last = chain.len();
+        for (i, (slot, name)) in chain.into_iter().rev().enumerate() {
+            // parents without a name are left out of the qualified name
+            let Some(name) = bundle.get_item(name) else {
+                if i + 1 == last {
+                    return Err(NameError::NotFound(index));
+                }
+                prefix = None;
+                continue;
+            };
+            prefix = Some(slot.get_or_init(|| match prefix {
+                Some(prefix) => format!("{prefix}.{name}").into(),
+                None => name.into(),
+            }));
+        }
+        prefix.ok_or(NameError::NotFound(index))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NameError {
+    NotFound(u32),
+    // the definition is nested in a chain of parents that loops back on itself
+    Cycle(u32),
+}
+
+impl fmt::Display for NameError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NameError::NotFound(index) => write!(f, "definition {index} not found"),
+            NameError::Cycle(index) => write!(f, "definition {index} has cyclic parents"),
+        }
+    }
+}
+
+impl std::error::Error for NameError {}
@@ -0,0 +1,186 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::bundle::ScriptBundle;
+use crate::definition::{Function, Parameter};
+use crate::index::{FunctionIndex, TypeIndex};
+use crate::mangle::{GameMangler, NameMangler};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OperatorKind {
+    AssignAdd,
+    AssignSubtract,
+    AssignMultiply,
+    AssignDivide,
+    AssignOr,
+    AssignAnd,
+    LogicOr,
+    LogicAnd,
+    Or,
+    Xor,
+    And,
+    Equal,
+    NotEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Modulo,
+    LogicNot,
+    BitNot,
+    Neg,
+}
+
+impl OperatorKind {
+    pub const ALL: [OperatorKind; 25] = [
+        OperatorKind::AssignAdd,
+        OperatorKind::AssignSubtract,
+        OperatorKind::AssignMultiply,
+        OperatorKind::AssignDivide,
+        OperatorKind::AssignOr,
+        OperatorKind::AssignAnd,
+        OperatorKind::LogicOr,
+        OperatorKind::LogicAnd,
+        OperatorKind::Or,
+        OperatorKind::Xor,
+        OperatorKind::And,
+        OperatorKind::Equal,
+        OperatorKind::NotEqual,
+        OperatorKind::Less,
+        OperatorKind::LessEqual,
+        OperatorKind::Greater,
+        OperatorKind::GreaterEqual,
+        OperatorKind::Add,
+        OperatorKind::Subtract,
+        OperatorKind::Multiply,
+        OperatorKind::Divide,
+        OperatorKind::Modulo,
+        OperatorKind::LogicNot,
+        OperatorKind::BitNot,
+        OperatorKind::Neg,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            OperatorKind::AssignAdd => "OperatorAssignAdd",
+            OperatorKind::AssignSubtract => "OperatorAssignSubtract",
+            OperatorKind::AssignMultiply => "OperatorAssignMultiply",
+            OperatorKind::AssignDivide => "OperatorAssignDivide",
+            OperatorKind::AssignOr => "OperatorAssignOr",
+            OperatorKind::AssignAnd => "OperatorAssignAnd",
+            OperatorKind::LogicOr => "OperatorLogicOr",
+            OperatorKind::LogicAnd => "OperatorLogicAnd",
+            OperatorKind::Or => "OperatorOr",
+            OperatorKind::Xor => "OperatorXor",
+            OperatorKind::And => "OperatorAnd",
+            OperatorKind::Equal => "OperatorEqual",
+            OperatorKind::NotEqual => "OperatorNotEqual",
+            OperatorKind::Less => "OperatorLess",
+            OperatorKind::LessEqual => "OperatorLessEqual",
+            OperatorKind::Greater => "OperatorGreater",
+            OperatorKind::GreaterEqual => "OperatorGreaterEqual",
+            OperatorKind::Add => "OperatorAdd",
+            OperatorKind::Subtract => "OperatorSubtract",
+            OperatorKind::Multiply => "OperatorMultiply",
+            OperatorKind::Divide => "OperatorDivide",
+            OperatorKind::Modulo => "OperatorModulo",
+            OperatorKind::LogicNot => "OperatorLogicNot",
+            OperatorKind::BitNot => "OperatorBitNot",
+            OperatorKind::Neg => "OperatorNeg",
+        }
+    }
+
+    #[inline]
+    pub fn is_unary(self) -> bool {
+        matches!(
+            self,
+            OperatorKind::LogicNot | OperatorKind::BitNot | OperatorKind::Neg
+        )
+    }
+
+    // accepts both plain and mangled names, e.g. `OperatorAdd;Int32Int32;Int32`
+    pub fn from_name(name: &str) -> Option<Self> {
+        let base = GameMangler.demangle(name);
+        Self::ALL.into_iter().find(|op| op.name() == base)
+    }
+}
+
+impl fmt::Display for OperatorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for OperatorKind {
+    type Err = UnknownOperator;
+
+    #[inline]
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_name(s).ok_or(UnknownOperator)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UnknownOperator;
+
+impl fmt::Display for UnknownOperator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("unknown operator name")
+    }
+}
+
+impl std::error::Error for UnknownOperator {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FunctionKind {
+    Regular,
+    Operator(OperatorKind),
+    Cast { implicit: bool },
+}
+
+impl Function<'_> {
+    pub fn operator_kind(&self, bundle: &ScriptBundle<'_>) -> Option<OperatorKind> {
+        if !self.is_operator() {
+            return None;
+        }
+        self.operator()
+            .and_then(|name| bundle.get_item(name))
+            .and_then(OperatorKind::from_name)
+            .or_else(|| OperatorKind::from_name(bundle.get_item(self.name())?))
+    }
+
+    pub fn kind(&self, bundle: &ScriptBundle<'_>) -> FunctionKind {
+        if let Some(op) = self.operator_kind(bundle) {
+            FunctionKind::Operator(op)
+        } else if self.is_cast() {
+            FunctionKind::Cast {
+                implicit: self.flags().is_implicit_cast(),
+            }
+        } else {
+            FunctionKind::Regular
+        }
+    }
+}
+
+pub fn find_operator(
+    bundle: &ScriptBundle<'_>,
+    kind: OperatorKind,
+    operands: &[TypeIndex],
+) -> Option<FunctionIndex> {
+    bundle
+        .functions()
+        .find(|(_, function)| {
+            function.operator_kind(bundle) == Some(kind)
+                && function.parameters().len() == operands.len()
+                && function
+                    .parameters()
+                    .iter()
+                    .zip(operands)
+                    .all(|(&param, &typ)| bundle.get_item(param).map(Parameter::typ) == Some(typ))
+        })
+        .map(|(index, _)| index)
+}
@@ -0,0 +1,97 @@
+use std::fmt;
+
+use crate::bundle::ScriptBundle;
+use crate::definition::Definition;
+
+impl ScriptBundle<'_> {
+    // reorders definitions so that parents precede their children and types precede
+    // the definitions that use them, which is the order the game writes them in, definitions
+    // already in order keep their positions, returns the new index of every definition
+    // by its old one, nothing is moved when it fails
+    #[inline]
+    pub fn sort_definitions_topologically(&mut self) -> Result<Vec<u32>, SortError> {
+        self.sort_definitions_from(1)
+    }
+
+    // sorts only the definitions from `start` onwards, the ones before it keep their positions
+    // and are not decoded, e.g. the bundle an assembler adds definitions to
+    pub(crate) fn sort_definitions_from(&mut self, start: u32) -> Result<Vec<u32>, SortError> {
+        let count = self.definition_count() as usize;
+        let start = (start as usize).clamp(1, count.max(1));
+        let mut visited = vec![false; count];
+        visited[..start.min(count)].fill(true);
+        let mut order = Vec::with_capacity(count);
+        order.extend(0..start.min(count) as u32);
+
+        let mut stack = vec![];
+        for root in start as u32..count as u32 {
+            stack.push((root, false));
+            while let Some((index, expanded)) = stack.pop() {
+                if expanded {
+                    order.push(index);
+                    continue;
+                }
+                // cycles are broken at the first definition reached
+                if visited[index as usize] {
+                    continue;
+                }
+                visited[index as usize] = true;
+                stack.push((index, true));
+                if let Some(def) = self.definition(index) {
+                    let deps = dependencies(def)
+                        .into_iter()
+                        .filter(|&dep| dep != 0 && (dep as usize) < count)
+                        .rev();
+                    stack.extend(deps.map(|dep| (dep, false)));
+                }
+            }
+        }
+        self.permute_definitions(&order, start)
+    }
+}
+
+fn dependencies(def: &Definition<'_>) -> Vec<u32> {
+    let mut deps = vec![def.parent()];
+    match def {
+        Definition::Type(typ) => deps.extend(typ.element_type().map(u32::from)),
+        Definition::Class(class) => deps.extend(class.base().map(u32::from)),
+        Definition::Function(fun) => deps.extend(fun.declared_return_type().map(u32::from)),
+        Definition::Parameter(param) => deps.push(param.typ().into()),
+        Definition::Local(local) => deps.push(local.typ().into()),
+        Definition::Field(field) => deps.push(field.typ().into()),
+        Definition::EnumMember(_)
+        | Definition::Enum(_)
+        | Definition::SourceFile(_)
+        | Definition::Bitfield => {}
+    }
+    deps
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SortError {
+    // the body of a function couldn't be decoded to rewrite its operands
+    Decoding { definition: u32, error: byte::Error },
+    // a definition refers to an index past the end of the bundle
+    Dangling { definition: u32, index: u32 },
+}
+
+impl fmt::Display for SortError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SortError::Decoding { definition, error } => {
+                write!(
+                    f,
+                    "failed to decode the body of definition {definition}: {error}"
+                )
+            }
+            SortError::Dangling { definition, index } => {
+                write!(
+                    f,
+                    "definition {definition} refers to {index} which does not exist"
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for SortError {}
@@ -0,0 +1,103 @@
+use std::fmt;
+
+use crate::bundle::ScriptBundle;
+use crate::definition::Definition;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Origin {
+    // written by the game toolchain
+    Vanilla,
+    // a game cache with scripts compiled in by redscript
+    Redscript,
+    // written by a community tool other than redscript
+    Other,
+    Unknown,
+}
+
+impl fmt::Display for Origin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Origin::Vanilla => f.write_str("vanilla"),
+            Origin::Redscript => f.write_str("redscript"),
+            Origin::Other => f.write_str("other tool"),
+            Origin::Unknown => f.write_str("unknown"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OriginReport {
+    pub origin: Origin,
+    // the observations the verdict is based on, in the order they were checked
+    pub evidence: Vec<Evidence>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Evidence {
+    // source files with the redscript extension
+    RedscriptSources(usize),
+    // definitions stored before their parents, the game always writes parents first
+    ParentsOutOfOrder(usize),
+    HeaderBuild(u32),
+    MissingHeaderBuild,
+    // the header is only available with ReadOptions::round_trip
+    HeaderUnavailable,
+}
+
+impl fmt::Display for Evidence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Evidence::RedscriptSources(count) => write!(f, "{count} redscript source files"),
+            Evidence::ParentsOutOfOrder(count) => {
+                write!(f, "{count} definitions stored before their parents")
+            }
+            Evidence::HeaderBuild(build) => write!(f, "header has build number {build}"),
+            Evidence::MissingHeaderBuild => f.write_str("header has no build number"),
+            Evidence::HeaderUnavailable => f.write_str("header fields were not preserved"),
+        }
+    }
+}
+
+impl ScriptBundle<'_> {
+    // guesses which toolchain produced the bundle, the checks are heuristics meant for triaging
+    // reports and the evidence should be shown along with the verdict
+    pub fn origin(&self) -> OriginReport {
+        let mut evidence = vec![];
+        let mut sources = 0;
+        let mut out_of_order = 0;
+        for (index, def) in self.definitions().enumerate().skip(1) {
+            if let Definition::SourceFile(file) = def {
+                if file.path().ends_with(".reds") {
+                    sources += 1;
+                }
+            }
+            if def.parent() as usize > index {
+                out_of_order += 1;
+            }
+        }
+        if sources > 0 {
+            evidence.push(Evidence::RedscriptSources(sources));
+        }
+        if out_of_order > 0 {
+            evidence.push(Evidence::ParentsOutOfOrder(out_of_order));
+        }
+        match self.original_build() {
+            Some(0) => evidence.push(Evidence::MissingHeaderBuild),
+            Some(build) => evidence.push(Evidence::HeaderBuild(build)),
+            None => evidence.push(Evidence::HeaderUnavailable),
+        }
+
+        let origin = if sources > 0 {
+            Origin::Redscript
+        } else if out_of_order > 0 {
+            Origin::Other
+        } else {
+            match self.original_build() {
+                Some(0) => Origin::Other,
+                Some(_) => Origin::Vanilla,
+                None => Origin::Unknown,
+            }
+        };
+        OriginReport { origin, evidence }
+    }
+}
@@ -0,0 +1,330 @@
+use std::collections::{BTreeMap, HashMap};
+use std::marker::PhantomData;
+use std::{fmt, ops};
+
+use crate::bundle::{self, PoolItemIndex, ScriptBundle, StringPool};
+use crate::definition::{Definition, DefinitionIndex, Function, FunctionFlags, Visibility};
+use crate::index::{
+    CNameIndex, ClassIndex, EnumIndex, EnumValueIndex, FieldIndex, FunctionIndex, IndexRange,
+    LocalIndex, NzPoolIndex, ParameterIndex, PoolIndex, ResourceIndex, SourceFileIndex,
+    StringIndex, TweakDbIndex, TypeIndex,
+};
+use crate::instr::Instr;
+use crate::lookup::SymbolLookup;
+use crate::util::RandomState;
+use crate::Str;
+
+// a merged view of a bundle and definitions and strings added or replaced on top of it, the base
+// is never copied nor changed, which makes speculative edits cheap
+#[derive(Debug)]
+pub struct OverlayBundle<'a, 'i> {
+    base: &'a ScriptBundle<'i>,
+    lookup: SymbolLookup<'a, 'i>,
+    definitions: BTreeMap<u32, Definition<'i>>,
+    // definitions past the end of the base, including the reserved ones
+    added: u32,
+    cnames: AddedStrings<'i, CNameIndex>,
+    tdb_ids: AddedStrings<'i, TweakDbIndex>,
+    resources: AddedStrings<'i, ResourceIndex>,
+    strings: AddedStrings<'i, StringIndex>,
+}
+
+impl<'a, 'i> OverlayBundle<'a, 'i> {
+    pub fn new(base: &'a ScriptBundle<'i>) -> Self {
+        Self {
+            base,
+            lookup: SymbolLookup::new(base),
+            definitions: BTreeMap::new(),
+            added: 0,
+            cnames: AddedStrings::new(base.cnames()),
+            tdb_ids: AddedStrings::new(base.tdb_ids()),
+            resources: AddedStrings::new(base.resources()),
+            strings: AddedStrings::new(base.strings()),
+        }
+    }
+
+    #[inline]
+    pub fn base(&self) -> &'a ScriptBundle<'i> {
+        self.base
+    }
+
+    #[inline]
+    pub fn get_item<I>(&self, index: I) -> Option<&I::Output>
+    where
+        I: OverlayItemIndex<'i>,
+    {
+        index.get_overlaid(self)
+    }
+
+    pub fn definition(&self, index: u32) -> Option<&Definition<'i>> {
+        self.definitions
+            .get(&index)
+            .or_else(|| self.base.definition(index))
+    }
+
+    #[inline]
+    pub fn definition_count(&self) -> u32 {
+        self.base.definition_count() + self.added
+    }
+
+    // definitions of the overlay, both the added and the replaced ones, by their index
+    #[inline]
+    pub fn overlaid(&self) -> impl Iterator<Item = (u32, &Definition<'i>)> {
+        self.definitions.iter().map(|(&index, def)| (index, def))
+    }
+
+    // the index the next definition will be given
+    #[inline]
+    pub fn next_index(&self) -> u32 {
+        self.definition_count()
+    }
+
+    pub fn define<A>(&mut self, def: A) -> NzPoolIndex<A::Index>
+    where
+        A: DefinitionIndex<'i>,
+    {
+        let index = self.next_index();
+        self.definitions.insert(index, def.into());
+        self.added += 1;
+        NzPoolIndex::new(index).expect("definition index should not be zero")
+    }
+
+    // sets aside indices for definitions that have to refer to each other before they're defined,
+    // they're filled with `define_reserved`
+    pub fn reserve<A>(&mut self, count: u32) -> IndexRange<A> {
+        let start = self.next_index();
+        self.added += count;
+        IndexRange::new(start, start + count)
+    }
+
+    // returns false if the index wasn't reserved or has already been filled
+    pub fn define_reserved<A>(&mut self, index: NzPoolIndex<A::Index>, def: A) -> bool
+    where
+        A: DefinitionIndex<'i>,
+    {
+        let index = u32::from(index);
+        if index < self.base.definition_count()
+            || index >= self.definition_count()
+            || self.definitions.contains_key(&index)
+        {
+            return false;
+        }
+        self.definitions.insert(index, def.into());
+        true
+    }
+
+    // defines a static function outside of any class, e.g. a snippet compiled against the base
+    pub fn emit_function(
+        &mut self,
+        name: impl Into<Str<'i>>,
+        return_type: Option<TypeIndex>,
+        code: Vec<Instr>,
+    ) -> FunctionIndex {
+        let name = self.get_or_add_cname(name);
+        let flags = FunctionFlags::new()
+            .with_is_static(true)
+            .with_is_final(true);
+        self.define(
+            Function::new(name, Visibility::Public, flags)
+                .with_return_type(return_type)
+                .with_code(code),
+        )
+    }
+
+    // the definition encoded on its own, see bundle::encode_definition
+    pub fn encode_definition(&self, index: u32) -> byte::Result<Vec<u8>> {
+        let def = self.definition(index).ok_or(byte::Error::BadInput {
+            err: "definition not found",
+        })?;
+        bundle::encode_definition(def)
+    }
+
+    // finds a definition by its fully-qualified name, e.g. `Class.Function`, the definitions of
+    // the overlay take precedence over the ones of the base
+    pub fn find<A>(&self, name: &str) -> Option<NzPoolIndex<A>>
+    where
+        NzPoolIndex<A>: OverlayItemIndex<'i>,
+    {
+        let overlaid = self
+            .definitions
+            .keys()
+            .rev()
+            .find(|&&index| self.qualified_name(index).as_deref() == Some(name));
+        let index = if let Some(&index) = overlaid {
+            NzPoolIndex::new(index)?
+        } else {
+            let index = self.lookup.definition(name)?;
+            // the base name is stale if the definition was replaced by one with another name
+            if self.definitions.contains_key(&index) {
+                return None;
+            }
+            NzPoolIndex::new(index)?
+        };
+        self.get_item(index)?;
+        Some(index)
+    }
+
+    fn qualified_name(&self, index: u32) -> Option<String> {
+        let def = self.definition(index)?;
+        let name = self.get_item(def.name())?;
+        match def.parent() {
+            0 => Some(name.to_owned()),
+            parent => {
+                let prefix = self.get_item(self.definition(parent)?.name())?;
+                Some(format!("{prefix}.{name}"))
+            }
+        }
+    }
+
+    // replaces a definition of the base or the overlay, returns false if there's none at the index
+    pub fn replace(&mut self, index: u32, def: impl Into<Definition<'i>>) -> bool {
+        if index == 0 || index >= self.definition_count() {
+            return false;
+        }
+        self.definitions.insert(index, def.into());
+        true
+    }
+
+    // copies the definition into the overlay before the first change
+    pub fn modify_definition(&mut self, index: u32, f: impl FnOnce(&mut Definition<'i>)) -> bool {
+        let Some(def) = self.definition(index).cloned() else {
+            return false;
+        };
+        f(self.definitions.entry(index).or_insert(def));
+        true
+    }
+
+    // drops the changes made to a definition of the base
+    pub fn revert(&mut self, index: u32) -> bool {
+        index < self.base.definition_count() && self.definitions.remove(&index).is_some()
+    }
+
+    #[inline]
+    pub fn get_or_add_cname(&mut self, string: impl Into<Str<'i>>) -> CNameIndex {
+        self.cnames.get_or_add(self.base.cnames(), string.into())
+    }
+
+    #[inline]
+    pub fn get_or_add_tdb_id(&mut self, string: impl Into<Str<'i>>) -> TweakDbIndex {
+        self.tdb_ids.get_or_add(self.base.tdb_ids(), string.into())
+    }
+
+    #[inline]
+    pub fn get_or_add_resource(&mut self, string: impl Into<Str<'i>>) -> ResourceIndex {
+        self.resources
+            .get_or_add(self.base.resources(), string.into())
+    }
+
+    #[inline]
+    pub fn get_or_add_string(&mut self, string: impl Into<Str<'i>>) -> StringIndex {
+        self.strings.get_or_add(self.base.strings(), string.into())
+    }
+}
+
+impl<'i, I> ops::Index<I> for OverlayBundle<'_, 'i>
+where
+    I: OverlayItemIndex<'i> + fmt::Display + Copy,
+{
+    type Output = I::Output;
+
+    fn index(&self, index: I) -> &Self::Output {
+        match index.get_overlaid(self) {
+            Some(val) => val,
+            None => panic!(
+                "unresolved {} index: {index}",
+                std::any::type_name::<I::Output>()
+            ),
+        }
+    }
+}
+
+// strings appended to one of the pools of the base
+#[derive(Debug)]
+struct AddedStrings<'i, I> {
+    offset: u32,
+    strings: Vec<Str<'i>>,
+    lookup: HashMap<Str<'i>, u32, RandomState>,
+    phantom: PhantomData<I>,
+}
+
+impl<'i, A> AddedStrings<'i, PoolIndex<A>> {
+    fn new(base: &StringPool<'i, A>) -> Self {
+        Self {
+            offset: base.len() as u32,
+            strings: vec![],
+            lookup: HashMap::default(),
+            phantom: PhantomData,
+        }
+    }
+
+    fn get(&self, index: PoolIndex<A>) -> Option<&str> {
+        let pos = u32::from(index).checked_sub(self.offset)?;
+        self.strings.get(pos as usize).map(Str::as_str)
+    }
+
+    fn get_or_add(&mut self, base: &StringPool<'i, A>, string: Str<'i>) -> PoolIndex<A> {
+        if let Some(index) = base.get_index(&string) {
+            return index;
+        }
+        if let Some(&index) = self.lookup.get(&string) {
+            return PoolIndex::new(index);
+        }
+        let index = self.offset + self.strings.len() as u32;
+        self.lookup.insert(string.clone(), index);
+        self.strings.push(string);
+        PoolIndex::new(index)
+    }
+}
+
+// items that can be resolved through an overlay, it's implemented by every index that can be
+// resolved through a bundle
+pub trait OverlayItemIndex<'i>: PoolItemIndex<'i> {
+    fn get_overlaid<'a>(self, overlay: &'a OverlayBundle<'_, 'i>) -> Option<&'a Self::Output>;
+}
+
+macro_rules! impl_overlay_string_item {
+    ($ty:ty, $name:ident) => {
+        impl<'i> OverlayItemIndex<'i> for $ty {
+            fn get_overlaid<'a>(
+                self,
+                overlay: &'a OverlayBundle<'_, 'i>,
+            ) -> Option<&'a Self::Output> {
+                overlay
+                    .base
+                    .get_item(self)
+                    .or_else(|| overlay.$name.get(self))
+            }
+        }
+    };
+}
+
+impl_overlay_string_item!(CNameIndex, cnames);
+impl_overlay_string_item!(TweakDbIndex, tdb_ids);
+impl_overlay_string_item!(ResourceIndex, resources);
+impl_overlay_string_item!(StringIndex, strings);
+
+macro_rules! impl_overlay_def_item {
+    ($idx:ty, $ty:ident) => {
+        impl<'i> OverlayItemIndex<'i> for $idx {
+            fn get_overlaid<'a>(
+                self,
+                overlay: &'a OverlayBundle<'_, 'i>,
+            ) -> Option<&'a Self::Output> {
+                match overlay.definition(u32::from(self)) {
+                    Some(Definition::$ty(val)) => Some(val),
+                    _ => None,
+                }
+            }
+        }
+    };
+}
+
+impl_overlay_def_item!(TypeIndex, Type);
+impl_overlay_def_item!(ClassIndex, Class);
+impl_overlay_def_item!(EnumValueIndex, EnumMember);
+impl_overlay_def_item!(EnumIndex, Enum);
+impl_overlay_def_item!(FunctionIndex, Function);
+impl_overlay_def_item!(ParameterIndex, Parameter);
+impl_overlay_def_item!(LocalIndex, Local);
+impl_overlay_def_item!(FieldIndex, Field);
+impl_overlay_def_item!(SourceFileIndex, SourceFile);
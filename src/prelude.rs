@@ -0,0 +1,18 @@
+// the types most consumers need, `use redscript_io::prelude::*;`
+pub use crate::bundle::{
+    BundleReader, PoolItemIndex, PoolItemIndexMut, ReadOptions, ScriptBundle, StringPool,
+};
+pub use crate::definition::{
+    Class, ClassFlags, Definition, DefinitionKind, Enum, EnumMember, Field, FieldFlags, Function,
+    FunctionBody, FunctionFlags, Local, LocalFlags, Parameter, ParameterFlags, SourceFile,
+    SourceReference, Type, TypeKind, Visibility,
+};
+pub use crate::emit::{ExprType, TypedEmitter, TypedExpr};
+pub use crate::filters::{ClassFilters, FunctionFilters};
+pub use crate::index::{
+    CNameIndex, ClassIndex, EnumIndex, EnumValueIndex, FieldIndex, FunctionIndex, LocalIndex,
+    ParameterIndex, ResourceIndex, SourceFileIndex, StringIndex, TweakDbIndex, TypeIndex,
+};
+pub use crate::instr;
+pub use crate::instr::{Instr, Offset};
+pub use crate::journal::BundleObserver;
@@ -0,0 +1,107 @@
+use std::collections::BTreeMap;
+
+use crate::bundle::ScriptBundle;
+
+// which sources, e.g. mods, defined or changed each definition of a bundle, meant to be stored
+// next to the cache, see ScriptBundle::set_source
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Provenance {
+    sources: Vec<String>,
+    // changes in the order they were made
+    changes: BTreeMap<u32, Vec<(u32, ChangeKind)>>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    current: Option<u32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "lowercase")
+)]
+pub enum ChangeKind {
+    Defined,
+    Modified,
+    Removed,
+}
+
+impl Provenance {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[inline]
+    pub fn sources(&self) -> impl Iterator<Item = &str> {
+        self.sources.iter().map(String::as_str)
+    }
+
+    // the source that defined the definition, None for the ones that were there to begin with
+    pub fn owner(&self, index: u32) -> Option<&str> {
+        self.changes(index)
+            .find(|&(_, change)| change == ChangeKind::Defined)
+            .map(|(source, _)| source)
+    }
+
+    pub fn changes(&self, index: u32) -> impl Iterator<Item = (&str, ChangeKind)> {
+        self.changes
+            .get(&index)
+            .into_iter()
+            .flatten()
+            .filter_map(|&(source, change)| {
+                Some((self.sources.get(source as usize)?.as_str(), change))
+            })
+    }
+
+    // definitions the source defined or changed
+    pub fn touched_by<'a>(&'a self, source: &'a str) -> impl Iterator<Item = u32> + 'a {
+        let id = self.sources.iter().position(|s| s == source);
+        self.changes
+            .iter()
+            .filter(move |(_, changes)| {
+                id.is_some_and(|id| changes.iter().any(|&(s, _)| s as usize == id))
+            })
+            .map(|(&index, _)| index)
+    }
+
+    pub(crate) fn set_source(&mut self, source: Option<String>) {
+        self.current = source.map(|source| {
+            let pos = self.sources.iter().position(|s| *s == source);
+            pos.unwrap_or_else(|| {
+                self.sources.push(source);
+                self.sources.len() - 1
+            }) as u32
+        });
+    }
+
+    pub(crate) fn record(&mut self, index: u32, change: ChangeKind) {
+        if let Some(source) = self.current {
+            let changes = self.changes.entry(index).or_default();
+            if changes.last() != Some(&(source, change)) {
+                changes.push((source, change));
+            }
+        }
+    }
+
+    // follows definitions moved to new positions, `positions` holds the new index of every
+    // definition by its old one
+    pub(crate) fn remap_definitions(&mut self, positions: &[u32]) {
+        self.changes = std::mem::take(&mut self.changes)
+            .into_iter()
+            .map(|(index, changes)| {
+                let index = positions.get(index as usize).copied().unwrap_or(index);
+                (index, changes)
+            })
+            .collect();
+    }
+}
+
+impl ScriptBundle<'_> {
+    // the source that defined a definition with the given fully-qualified name,
+    // e.g. the mod that added `Class.Function`
+    pub fn owner_of(&self, name: &str) -> Option<&str> {
+        let index = self.symbol_lookup().definition(name)?;
+        self.provenance()?.owner(index)
+    }
+}
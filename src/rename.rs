@@ -0,0 +1,144 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+
+use crate::bundle::{PoolError, ScriptBundle};
+use crate::definition::{Definition, TypeKind};
+use crate::index::CNameIndex;
+use crate::symbols;
+use crate::syntax::game_type_name_with;
+use crate::util::RandomState;
+
+impl ScriptBundle<'_> {
+    // renames classes, functions and fields given their fully-qualified names (e.g. `Class.Field`)
+    // and the new unqualified names, nothing is modified unless every rename can be applied,
+    // the types of renamed classes are renamed along with them,
+    // returns the indices of all modified definitions
+    pub fn apply_renames<'a>(
+        &mut self,
+        renames: impl IntoIterator<Item = (&'a str, &'a str)>,
+    ) -> Result<Vec<u32>, RenameError> {
+        let renamable = |def: &Definition<'_>| {
+            matches!(
+                def,
+                Definition::Class(_) | Definition::Function(_) | Definition::Field(_)
+            )
+        };
+        let by_name: HashMap<String, u32, RandomState> = (1..self.definition_count())
+            .filter(|&i| self.definition(i).is_some_and(renamable))
+            .filter_map(|i| Some((symbols::qualified_name(self, i)?, i)))
+            .collect();
+
+        let mut targets: HashMap<u32, &str, RandomState> = HashMap::default();
+        for (old, new) in renames {
+            let &index = by_name
+                .get(old)
+                .ok_or_else(|| RenameError::NotFound(old.to_owned()))?;
+            targets.insert(index, new);
+        }
+
+        // the final name of every renamable definition, keyed by the parent it's unique under
+        let mut taken: HashMap<_, u32, RandomState> = HashMap::default();
+        let mut order = by_name.values().copied().collect::<Vec<_>>();
+        // renamed definitions go last so that collisions are reported against them
+        order.sort_unstable_by_key(|i| (targets.contains_key(i), *i));
+        for index in order {
+            let def = self.definition(index).expect("indexed definition");
+            let name = match targets.get(&index) {
+                Some(&name) => name,
+                None => self.get_item(def.name()).unwrap_or_default(),
+            };
+            if let Some(existing) = taken.insert((def.kind(), def.parent(), name), index) {
+                if targets.contains_key(&index) {
+                    return Err(RenameError::Collision {
+                        name: name.to_owned(),
+                        existing,
+                    });
+                }
+            }
+        }
+
+        // the final names are worked out from the original ones, so that swapped names don't get
+        // renamed twice, and resolved before anything is changed
+        let mut names: BTreeMap<u32, String> = targets
+            .iter()
+            .map(|(&index, &new)| (index, new.to_owned()))
+            .collect();
+        let renamed_classes: HashMap<CNameIndex, &str, RandomState> = targets
+            .iter()
+            .filter_map(|(&index, &new)| match self.definition(index)? {
+                Definition::Class(class) => Some((class.name(), new)),
+                _ => None,
+            })
+            .collect();
+        for index in 1..self.definition_count() {
+            let Some(Definition::Type(typ)) = self.definition(index) else {
+                continue;
+            };
+            let name = if *typ.kind() == TypeKind::Class {
+                renamed_classes.get(&typ.name()).map(|&new| new.to_owned())
+            } else {
+                // composite type names embed the class name, e.g. `array:handle:Player`
+                game_type_name_with(self, typ, |innermost| {
+                    match renamed_classes.get(&innermost.name()) {
+                        Some(&new) if *innermost.kind() == TypeKind::Class => Some(new.to_owned()),
+                        _ => Some(self.get_item(innermost.name())?.to_owned()),
+                    }
+                })
+                .filter(|name| self.get_item(typ.name()) != Some(name.as_str()))
+            };
+            names.extend(name.map(|name| (index, name)));
+        }
+
+        // interning can't be undone, so the pool is checked to fit every new name up front
+        let cnames = self.cnames();
+        let missing = names
+            .values()
+            .filter(|name| cnames.get_index(name).is_none())
+            .collect::<HashSet<_, RandomState>>()
+            .len();
+        if missing > 0 && cnames.is_frozen() {
+            return Err(RenameError::Pool(PoolError::Frozen));
+        }
+        if cnames.len() + missing > u32::MAX as usize + 1 {
+            return Err(RenameError::Pool(PoolError::Overflow));
+        }
+
+        let mut resolved = Vec::with_capacity(names.len());
+        for (index, name) in names {
+            let name = self
+                .cnames_mut()
+                .get_or_add(name)
+                .map_err(RenameError::Pool)?;
+            resolved.push((index, name));
+        }
+
+        let mut modified = Vec::with_capacity(resolved.len());
+        for (index, name) in resolved {
+            self.modify_definition(index, |def| def.set_name(name));
+            modified.push(index);
+        }
+        Ok(modified)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RenameError {
+    NotFound(String),
+    // the new name is already used by another definition of the same kind and parent
+    Collision { name: String, existing: u32 },
+    Pool(PoolError),
+}
+
+impl fmt::Display for RenameError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RenameError::NotFound(name) => write!(f, "no class, function or field named {name}"),
+            RenameError::Collision { name, existing } => {
+                write!(f, "{name} is already used by definition {existing}")
+            }
+            RenameError::Pool(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl std::error::Error for RenameError {}
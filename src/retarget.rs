@@ -0,0 +1,142 @@
+use std::fmt;
+
+use crate::bundle::ScriptBundle;
+use crate::definition::{Definition, Function};
+use crate::index::FunctionIndex;
+use crate::instr::Instr;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RetargetOptions {
+    virtual_calls: bool,
+}
+
+impl RetargetOptions {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // also redirects virtual calls by the name of the function, these are matched by name alone,
+    // so they include the calls to overrides of the function and to unrelated methods with the
+    // same name
+    #[inline]
+    pub fn with_virtual_calls(mut self, virtual_calls: bool) -> Self {
+        self.virtual_calls = virtual_calls;
+        self
+    }
+}
+
+impl ScriptBundle<'_> {
+    // makes the calls to `from` call `to` instead, the functions have to take and return the same
+    // types, the calls made by `to` itself are left alone so that it can wrap `from`, bodies that
+    // can't be decoded are skipped, returns the functions that were modified
+    pub fn retarget_calls(
+        &mut self,
+        from: FunctionIndex,
+        to: FunctionIndex,
+        options: RetargetOptions,
+    ) -> Result<Vec<FunctionIndex>, RetargetError> {
+        let old = self.get_item(from).ok_or(RetargetError::NotFound(from))?;
+        let new = self.get_item(to).ok_or(RetargetError::NotFound(to))?;
+        self.check_compatible(old, new)?;
+        let names =
+            (options.virtual_calls && !old.flags().is_static()).then_some((old.name(), new.name()));
+
+        let mut changed = vec![];
+        for (index, fun) in self.functions() {
+            if index == to || fun.body().is_empty() {
+                continue;
+            }
+            let Ok(mut code) = fun.body().code_owned() else {
+                continue;
+            };
+            let mut modified = false;
+            for instr in &mut code {
+                match (instr, names) {
+                    (Instr::InvokeStatic { function, .. }, _) if *function == from => {
+                        *function = to;
+                        modified = true;
+                    }
+                    (Instr::InvokeVirtual { function, .. }, Some((old_name, new_name)))
+                        if *function == old_name =>
+                    {
+                        *function = new_name;
+                        modified = true;
+                    }
+                    _ => {}
+                }
+            }
+            if modified {
+                changed.push((index, code));
+            }
+        }
+
+        let mut modified = vec![];
+        for (index, code) in changed {
+            self.modify_definition(index.into(), |def| {
+                if let Definition::Function(fun) = def {
+                    fun.set_code(code);
+                }
+            });
+            modified.push(index);
+        }
+        Ok(modified)
+    }
+
+    fn check_compatible(
+        &self,
+        old: &Function<'_>,
+        new: &Function<'_>,
+    ) -> Result<(), RetargetError> {
+        if old.flags().is_static() != new.flags().is_static() {
+            return Err(RetargetError::StaticMismatch);
+        }
+        if old.parameters().len() != new.parameters().len() {
+            return Err(RetargetError::ParameterCount {
+                from: old.parameters().len(),
+                to: new.parameters().len(),
+            });
+        }
+        for (i, (&a, &b)) in old.parameters().iter().zip(new.parameters()).enumerate() {
+            let (Some(a), Some(b)) = (self.get_item(a), self.get_item(b)) else {
+                return Err(RetargetError::ParameterType(i));
+            };
+            if a.typ() != b.typ() || a.is_out() != b.is_out() {
+                return Err(RetargetError::ParameterType(i));
+            }
+        }
+        if old.return_type(self) != new.return_type(self) {
+            return Err(RetargetError::ReturnType);
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RetargetError {
+    NotFound(FunctionIndex),
+    // one of the functions is static and the other one isn't
+    StaticMismatch,
+    ParameterCount { from: usize, to: usize },
+    // the position of a parameter with a different type or passing mode
+    ParameterType(usize),
+    ReturnType,
+}
+
+impl fmt::Display for RetargetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RetargetError::NotFound(index) => write!(f, "function {index} not found"),
+            RetargetError::StaticMismatch => write!(f, "only one of the functions is static"),
+            RetargetError::ParameterCount { from, to } => {
+                write!(f, "the functions take {from} and {to} parameters")
+            }
+            RetargetError::ParameterType(i) => {
+                write!(f, "parameter {i} differs between the functions")
+            }
+            RetargetError::ReturnType => write!(f, "the functions return different types"),
+        }
+    }
+}
+
+impl std::error::Error for RetargetError {}
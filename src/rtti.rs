@@ -0,0 +1,102 @@
+use std::collections::{BTreeSet, HashMap};
+
+use crate::bundle::ScriptBundle;
+use crate::mangle::{GameMangler, NameMangler};
+
+// the subset of a RED4ext RTTI dump needed to cross-check native declarations
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize)]
+pub struct RttiDump {
+    pub classes: Vec<RttiClass>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize)]
+pub struct RttiClass {
+    pub name: String,
+    #[serde(default)]
+    pub functions: Vec<RttiFunction>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize)]
+pub struct RttiFunction {
+    pub name: String,
+}
+
+impl RttiDump {
+    #[inline]
+    pub fn from_json(json: &str) -> Result<Self, serde_json::Error> {
+        serde_json::from_str(json)
+    }
+}
+
+// names are reported as `Class` or `Class.function`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RttiReport {
+    // functions of classes known to the cache that the cache does not declare
+    pub missing_from_cache: Vec<String>,
+    // native classes and functions declared in the cache that the game does not have
+    pub missing_from_rtti: Vec<String>,
+}
+
+impl RttiReport {
+    #[inline]
+    pub fn is_consistent(&self) -> bool {
+        self.missing_from_cache.is_empty() && self.missing_from_rtti.is_empty()
+    }
+}
+
+impl ScriptBundle<'_> {
+    pub fn correlate_rtti(&self, dump: &RttiDump) -> RttiReport {
+        let rtti: HashMap<&str, BTreeSet<&str>> = dump
+            .classes
+            .iter()
+            .map(|c| {
+                let functions = c.functions.iter().map(|f| short_name(&f.name)).collect();
+                (c.name.as_str(), functions)
+            })
+            .collect();
+
+        let mut report = RttiReport::default();
+        for (_, class) in self.classes() {
+            let Some(class_name) = self.get_item(class.name()) else {
+                continue;
+            };
+            let methods = class
+                .methods()
+                .iter()
+                .filter_map(|&i| self.get_item(i))
+                .filter_map(|f| Some((self.get_item(f.name()).map(short_name)?, f)));
+
+            match rtti.get(class_name) {
+                Some(functions) => {
+                    let mut declared = BTreeSet::new();
+                    for (name, fun) in methods {
+                        declared.insert(name);
+                        if fun.flags().is_native() && !functions.contains(name) {
+                            report
+                                .missing_from_rtti
+                                .push(format!("{class_name}.{name}"));
+                        }
+                    }
+                    if class.flags().is_native() {
+                        report.missing_from_cache.extend(
+                            functions
+                                .difference(&declared)
+                                .map(|name| format!("{class_name}.{name}")),
+                        );
+                    }
+                }
+                None if class.flags().is_native() => {
+                    report.missing_from_rtti.push(class_name.to_owned());
+                }
+                None => {}
+            }
+        }
+        report
+    }
+}
+
+// strips the signature suffix from mangled names like `Method;Int32`
+#[inline]
+fn short_name(name: &str) -> &str {
+    GameMangler.demangle(name)
+}
@@ -0,0 +1,320 @@
+use std::collections::BTreeSet;
+use std::fmt::Write;
+
+use crate::bundle::ScriptBundle;
+use crate::definition::Function;
+use crate::index::{CNameIndex, TypeIndex};
+use crate::instr::{self, Instr};
+use crate::symbols;
+
+impl Function<'_> {
+    // generates rust code that recreates this function in another bundle using the builder api,
+    // it expects a `bundle: &mut ScriptBundle` in scope, referenced definitions are looked up
+    // by their qualified names and types are parsed from their redscript syntax,
+    // breakpoints and profiling instructions are replaced with nops
+    pub fn to_builder_script(&self, bundle: &ScriptBundle<'_>) -> byte::Result<String> {
+        let mut gen = ScriptGen {
+            bundle,
+            function: self,
+            defs: BTreeSet::new(),
+            types: BTreeSet::new(),
+        };
+        let original = self.body().code_owned()?;
+        let replaced = original
+            .iter()
+            .map(|instr| match instr {
+                Instr::Breakpoint(_) | Instr::Profile(_) | Instr::Target(_) => Instr::Nop,
+                instr => instr.clone(),
+            })
+            .collect();
+        // the nops are shorter than the instructions they replace
+        let code = instr::retarget_jumps(&original, replaced)
+            .ok_or(byte::Error::BadInput {
+                err: "jump offset out of range",
+            })?
+            .iter()
+            .map(|instr| gen.instr(instr))
+            .collect::<Vec<_>>();
+        let params = self
+            .parameters()
+            .iter()
+            .filter_map(|&index| bundle.get_item(index))
+            .map(|param| {
+                (
+                    param.name(),
+                    gen.typ(param.typ()),
+                    param.flags().to_string(),
+                )
+            })
+            .collect::<Vec<_>>();
+        let locals = self
+            .locals()
+            .iter()
+            .filter_map(|&index| bundle.get_item(index))
+            .map(|local| {
+                (
+                    local.name(),
+                    gen.typ(local.typ()),
+                    local.flags().to_string(),
+                )
+            })
+            .collect::<Vec<_>>();
+        let class = self.class().map(|class| gen.def(class.into()));
+        let return_type = self
+            .declared_return_type()
+            .filter(|_| !self.returns_void())
+            .map(|typ| gen.typ(typ));
+        let base_method = self.base_method().map(|base| gen.def(base.into()));
+
+        let mut out = String::new();
+        let w = &mut out;
+        if !gen.defs.is_empty() {
+            writeln!(w, "let lookup = bundle.symbol_lookup();").ok();
+            for &index in &gen.defs {
+                let name = symbols::qualified_name(bundle, index).unwrap_or_default();
+                writeln!(w, "let def_{index} = lookup.find({name:?}).unwrap();").ok();
+            }
+            writeln!(w, "drop(lookup);").ok();
+        }
+        for &index in &gen.types {
+            let syntax = bundle
+                .get_item(index)
+                .map(|typ| typ.display(bundle).to_string())
+                .unwrap_or_default();
+            writeln!(
+                w,
+                "let type_{} = bundle.parse_type({syntax:?}).unwrap();",
+                u32::from(index)
+            )
+            .ok();
+        }
+
+        writeln!(w, "let name = {};", gen.cname(self.name())).ok();
+        writeln!(
+            w,
+            "let function = bundle.define(Function::new(name, Visibility::{:?}, {:?}.parse().unwrap())",
+            self.visibility(),
+            self.flags().to_string()
+        )
+        .ok();
+        if let Some(class) = class {
+            writeln!(w, "    .with_class(Some({class}))").ok();
+        }
+        if let Some(typ) = return_type {
+            writeln!(w, "    .with_return_type(Some({typ}))").ok();
+        }
+        if let Some(base) = base_method {
+            writeln!(w, "    .with_base_method(Some({base}))").ok();
+        }
+        if let Some(source) = self.source() {
+            writeln!(
+                w,
+                "    .with_source(Some(redscript_io::SourceReference::new(redscript_io::SourceFileIndex::default(), {})))",
+                source.line()
+            )
+            .ok();
+        }
+        writeln!(w, ");").ok();
+
+        for (kind, items) in [("param", &params), ("local", &locals)] {
+            let ctor = if kind == "param" {
+                "Parameter"
+            } else {
+                "Local"
+            };
+            for (i, (name, typ, flags)) in items.iter().enumerate() {
+                writeln!(w, "let name = {};", gen.cname(*name)).ok();
+                writeln!(
+                    w,
+                    "let {kind}_{i} = bundle.define({ctor}::new(name, function, {typ}, {flags:?}.parse().unwrap()));"
+                )
+                .ok();
+            }
+        }
+
+        let vars = |kind: &str, len: usize| {
+            (0..len)
+                .map(|i| format!("{kind}_{i}"))
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        writeln!(w, "let code = vec![").ok();
+        for instr in &code {
+            writeln!(w, "    Instr::{instr},").ok();
+        }
+        writeln!(w, "];").ok();
+        writeln!(w, "bundle.modify(function, |fun| {{").ok();
+        writeln!(w, "    *fun = fun").ok();
+        writeln!(w, "        .clone()").ok();
+        if !params.is_empty() {
+            writeln!(
+                w,
+                "        .with_parameters([{}])",
+                vars("param", params.len())
+            )
+            .ok();
+        }
+        if !locals.is_empty() {
+            writeln!(w, "        .with_locals([{}])", vars("local", locals.len())).ok();
+        }
+        writeln!(w, "        .with_code(code);").ok();
+        writeln!(w, "}});").ok();
+
+        let imports = [
+            "Conditional",
+            "Function",
+            "Instr",
+            "Jump",
+            "Local",
+            "Offset",
+            "Parameter",
+            "Switch",
+            "SwitchLabel",
+            "Visibility",
+        ]
+        .into_iter()
+        .filter(|name| out.contains(&format!("{name}::")))
+        .collect::<Vec<_>>()
+        .join(", ");
+        Ok(format!("use redscript_io::{{{imports}}};\n{out}"))
+    }
+}
+
+struct ScriptGen<'a, 'i> {
+    bundle: &'a ScriptBundle<'i>,
+    function: &'a Function<'i>,
+    defs: BTreeSet<u32>,
+    types: BTreeSet<TypeIndex>,
+}
+
+impl ScriptGen<'_, '_> {
+    fn instr(&mut self, instr: &Instr) -> String {
+        let name = instr.name();
+        match instr {
+            Instr::I8Const(v) => format!("{name}({v})"),
+            Instr::I16Const(v) => format!("{name}({v})"),
+            Instr::I32Const(v) => format!("{name}({v})"),
+            Instr::I64Const(v) => format!("{name}({v})"),
+            Instr::U8Const(v) => format!("{name}({v})"),
+            Instr::U16Const(v) => format!("{name}({v})"),
+            Instr::U32Const(v) => format!("{name}({v})"),
+            Instr::U64Const(v) => format!("{name}({v})"),
+            Instr::F32Const(v) => format!("{name}(f32::from_bits({:#x}))", v.to_bits()),
+            Instr::F64Const(v) => format!("{name}(f64::from_bits({:#x}))", v.to_bits()),
+            Instr::CNameConst(index) => format!("{name}({})", self.cname(*index)),
+            Instr::StringConst(index) => {
+                let str = self.bundle.get_item(*index).unwrap_or_default();
+                format!("{name}(bundle.strings_mut().get_or_add({str:?}).unwrap())")
+            }
+            Instr::TweakDbIdConst(index) => {
+                let str = self.bundle.get_item(*index).unwrap_or_default();
+                format!("{name}(bundle.tdb_ids_mut().get_or_add({str:?}).unwrap())")
+            }
+            Instr::ResourceConst(index) => {
+                let str = self.bundle.get_item(*index).unwrap_or_default();
+                format!("{name}(bundle.resources_mut().get_or_add({str:?}).unwrap())")
+            }
+            Instr::EnumConst { enum_, value } => format!(
+                "{name} {{ enum_: {}, value: {} }}",
+                self.def((*enum_).into()),
+                self.def((*value).into())
+            ),
+            Instr::Local(index) => {
+                let pos = self.function.locals().iter().position(|i| i == index);
+                match pos {
+                    Some(pos) => format!("{name}(local_{pos})"),
+                    None => format!("{name}({})", self.def((*index).into())),
+                }
+            }
+            Instr::Param(index) => {
+                let pos = self.function.parameters().iter().position(|i| i == index);
+                match pos {
+                    Some(pos) => format!("{name}(param_{pos})"),
+                    None => format!("{name}({})", self.def((*index).into())),
+                }
+            }
+            Instr::ObjectField(index) | Instr::StructField(index) => {
+                format!("{name}({})", self.def((*index).into()))
+            }
+            Instr::Switch(switch) => format!(
+                "{name}(Switch::new({}, Offset::from({})))",
+                self.typ(switch.expr_type()),
+                i16::from(switch.first_case())
+            ),
+            Instr::SwitchLabel(label) => format!(
+                "{name}(SwitchLabel::new(Offset::from({}), Offset::from({})))",
+                i16::from(label.next_case()),
+                i16::from(label.body())
+            ),
+            Instr::Jump(jump)
+            | Instr::JumpIfFalse(jump)
+            | Instr::Skip(jump)
+            | Instr::Context(jump) => {
+                format!("{name}({})", jump_expr(jump.target().into()))
+            }
+            Instr::Conditional(cond) => format!(
+                "{name}(Conditional::new(Offset::from({}), Offset::from({})))",
+                i16::from(cond.false_label()),
+                i16::from(cond.exit())
+            ),
+            Instr::Construct { arg_count, class } => format!(
+                "{name} {{ arg_count: {arg_count}, class: {} }}",
+                self.def((*class).into())
+            ),
+            Instr::InvokeStatic {
+                exit,
+                line,
+                function,
+                flags,
+            } => format!(
+                "{name} {{ exit: {}, line: {line}, function: {}, flags: {flags} }}",
+                jump_expr(exit.target().into()),
+                self.def((*function).into())
+            ),
+            Instr::InvokeVirtual {
+                exit,
+                line,
+                function,
+                flags,
+            } => format!(
+                "{name} {{ exit: {}, line: {line}, function: {}, flags: {flags} }}",
+                jump_expr(exit.target().into()),
+                self.cname(*function)
+            ),
+            Instr::New(class) => format!("{name}({})", self.def((*class).into())),
+            Instr::DynamicCast { class, flags } => format!(
+                "{name} {{ class: {}, flags: {flags} }}",
+                self.def((*class).into())
+            ),
+            Instr::EnumToI32 { enum_type, size } | Instr::I32ToEnum { enum_type, size } => {
+                format!(
+                    "{name} {{ enum_type: {}, size: {size} }}",
+                    self.typ(*enum_type)
+                )
+            }
+            other => match other.type_operand() {
+                Some(typ) => format!("{name}({})", self.typ(typ)),
+                None => name.to_owned(),
+            },
+        }
+    }
+
+    fn def(&mut self, index: u32) -> String {
+        self.defs.insert(index);
+        format!("def_{index}")
+    }
+
+    fn typ(&mut self, index: TypeIndex) -> String {
+        self.types.insert(index);
+        format!("type_{}", u32::from(index))
+    }
+
+    fn cname(&self, index: CNameIndex) -> String {
+        let str = self.bundle.get_item(index).unwrap_or_default();
+        format!("bundle.cnames_mut().get_or_add({str:?}).unwrap()")
+    }
+}
+
+fn jump_expr(offset: i16) -> String {
+    format!("Jump::new(Offset::from({offset}))")
+}
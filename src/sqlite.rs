@@ -0,0 +1,139 @@
+// exports a bundle into a SQLite database with the following schema:
+//
+//   definitions(bundle, id, kind, name, qualified_name, parent, flags, signature)
+//     one row per definition, `id` is the definition index and `parent` is zero for top-level ones
+//   members(bundle, owner, member, role)
+//     methods and fields of classes, values of enums and parameters and locals of functions,
+//     `role` is one of `method`, `field`, `value`, `parameter` or `local`
+//   xrefs(bundle, function, offset, target, target_name)
+//     definitions referenced from function bodies, `offset` is the byte offset of the
+//     instruction, virtual calls are resolved by name only so they have a null `target`
+//
+// `bundle` is the name the bundle was exported under, so that several of them can share a
+// database, e.g. to compare game versions
+use rusqlite::{params, Connection};
+
+use crate::bundle::ScriptBundle;
+use crate::definition::Definition;
+use crate::instr::Instr;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS definitions (
+    bundle TEXT NOT NULL,
+    id INTEGER NOT NULL,
+    kind TEXT NOT NULL,
+    name TEXT NOT NULL,
+    qualified_name TEXT NOT NULL,
+    parent INTEGER NOT NULL,
+    flags INTEGER NOT NULL,
+    signature TEXT NOT NULL,
+    PRIMARY KEY (bundle, id)
+);
+CREATE TABLE IF NOT EXISTS members (
+    bundle TEXT NOT NULL,
+    owner INTEGER NOT NULL,
+    member INTEGER NOT NULL,
+    role TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS xrefs (
+    bundle TEXT NOT NULL,
+    function INTEGER NOT NULL,
+    offset INTEGER NOT NULL,
+    target INTEGER,
+    target_name TEXT
+);
+CREATE INDEX IF NOT EXISTS definitions_name ON definitions (bundle, qualified_name);
+CREATE INDEX IF NOT EXISTS members_owner ON members (bundle, owner);
+CREATE INDEX IF NOT EXISTS xrefs_target ON xrefs (bundle, target);
+";
+
+impl ScriptBundle<'_> {
+    // writes all definitions under the given name in a single transaction, the rows of other
+    // bundles are left intact and the ones of an earlier export under the same name are replaced
+    pub fn export_sqlite(&self, conn: &mut Connection, bundle: &str) -> rusqlite::Result<()> {
+        let tx = conn.transaction()?;
+        tx.execute_batch(SCHEMA)?;
+        for table in ["definitions", "members", "xrefs"] {
+            tx.execute(&format!("DELETE FROM {table} WHERE bundle = ?1"), [bundle])?;
+        }
+        {
+            let mut definitions = tx.prepare(
+                "INSERT INTO definitions
+                 (bundle, id, kind, name, qualified_name, parent, flags, signature)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            )?;
+            let mut members = tx.prepare(
+                "INSERT INTO members (bundle, owner, member, role) VALUES (?1, ?2, ?3, ?4)",
+            )?;
+            let mut xrefs = tx.prepare(
+                "INSERT INTO xrefs (bundle, function, offset, target, target_name)
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+            )?;
+
+            for sym in self.symbol_table().symbols {
+                let name = self
+                    .definition(sym.index)
+                    .and_then(|def| self.get_item(def.name()))
+                    .unwrap_or_default();
+                definitions.execute(params![
+                    bundle,
+                    sym.index,
+                    sym.kind.name(),
+                    name,
+                    sym.name,
+                    sym.parent,
+                    sym.flags,
+                    sym.signature
+                ])?;
+            }
+
+            for index in 1..self.definition_count() {
+                let Some(def) = self.definition(index) else {
+                    continue;
+                };
+                let mut add_members =
+                    |role: &str, items: &mut dyn Iterator<Item = u32>| -> rusqlite::Result<()> {
+                        for member in items {
+                            members.execute(params![bundle, index, member, role])?;
+                        }
+                        Ok(())
+                    };
+                match def {
+                    Definition::Class(class) => {
+                        add_members("method", &mut class.methods().iter().map(|&i| i.into()))?;
+                        add_members("field", &mut class.fields().iter().map(|&i| i.into()))?;
+                    }
+                    Definition::Enum(enum_) => {
+                        add_members("value", &mut enum_.values().iter().map(|&i| i.into()))?;
+                    }
+                    Definition::Function(fun) => {
+                        add_members("parameter", &mut fun.parameters().iter().map(|&i| i.into()))?;
+                        add_members("local", &mut fun.locals().iter().map(|&i| i.into()))?;
+
+                        let code = fun.body().code_iter().with_offsets();
+                        for (offset, instr) in code.map_while(|(o, i)| Some((o, i.ok()?))) {
+                            let (target, target_name) = match instr {
+                                Instr::InvokeVirtual { function, .. } => {
+                                    (None, self.get_item(function))
+                                }
+                                instr => match instr.referenced_definition() {
+                                    Some(target) => (Some(target), None),
+                                    None => continue,
+                                },
+                            };
+                            xrefs.execute(params![
+                                bundle,
+                                index,
+                                u32::from(offset),
+                                target,
+                                target_name
+                            ])?;
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+        tx.commit()
+    }
+}
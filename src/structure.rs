@@ -0,0 +1,143 @@
+use std::ops::Range;
+
+use crate::definition::FunctionBody;
+use crate::instr::{Instr, InstrIndex, Jump, Offset, OffsetMap};
+
+// high-level regions recognized in the code of a function, meant for printers that want to
+// indent and annotate the code without decompiling it, regions are ordered by where they start
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct CodeStructure {
+    pub regions: Vec<Region>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Region {
+    // the branches start after the JumpIfFalse, so the then branch includes the condition
+    If {
+        condition: InstrIndex,
+        then: Range<InstrIndex>,
+        else_: Option<Range<InstrIndex>>,
+    },
+    Switch {
+        switch: InstrIndex,
+        cases: Vec<SwitchCase>,
+        default: Option<InstrIndex>,
+    },
+    // `back_edge` is the last jump back to the header, `exit` is the target of the JumpIfFalse
+    // that leaves the loop if there's one
+    Loop {
+        header: InstrIndex,
+        back_edge: InstrIndex,
+        exit: Option<InstrIndex>,
+    },
+}
+
+impl Region {
+    pub fn start(&self) -> InstrIndex {
+        match self {
+            Region::If { condition, .. } => *condition,
+            Region::Switch { switch, .. } => *switch,
+            Region::Loop { header, .. } => *header,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SwitchCase {
+    pub label: InstrIndex,
+    pub body: InstrIndex,
+}
+
+impl FunctionBody<'_> {
+    // recognizes regions from the jumps of the code, the patterns are the ones emitted by the
+    // compilers, code that doesn't follow them is left out
+    pub fn structure(&self) -> byte::Result<CodeStructure> {
+        let code = self.code_owned()?;
+        let offsets = OffsetMap::new(&code);
+        let at = |i: usize| InstrIndex::new(i as u32);
+        let target = |i: usize, jump: &Jump<Offset>| offsets.resolve_jump(at(i), jump.target());
+
+        let mut loops: Vec<(InstrIndex, InstrIndex)> = vec![];
+        for (i, instr) in code.iter().enumerate() {
+            if let Instr::Jump(jump) = instr {
+                match target(i, jump) {
+                    Some(header) if header <= at(i) => {
+                        match loops.iter_mut().find(|(h, _)| *h == header) {
+                            Some((_, back_edge)) => *back_edge = at(i),
+                            None => loops.push((header, at(i))),
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        let mut regions = vec![];
+        let mut loop_exits = vec![];
+        for &(header, back_edge) in &loops {
+            let after = at(usize::from(back_edge) + 1);
+            let exit = (usize::from(header)..usize::from(back_edge)).find(
+                |&i| matches!(&code[i], Instr::JumpIfFalse(jump) if target(i, jump) == Some(after)),
+            );
+            loop_exits.extend(exit);
+            regions.push(Region::Loop {
+                header,
+                back_edge,
+                exit: exit.map(|_| after),
+            });
+        }
+
+        for (i, instr) in code.iter().enumerate() {
+            match instr {
+                Instr::JumpIfFalse(jump) if !loop_exits.contains(&i) => {
+                    let Some(false_label) = target(i, jump).filter(|&t| t > at(i)) else {
+                        continue;
+                    };
+                    let last = usize::from(false_label) - 1;
+                    let end = match code.get(last) {
+                        Some(Instr::Jump(jump)) if last > i => {
+                            target(last, jump).filter(|&t| t > false_label)
+                        }
+                        _ => None,
+                    };
+                    regions.push(Region::If {
+                        condition: at(i),
+                        then: at(i + 1)..if end.is_some() { at(last) } else { false_label },
+                        else_: end.map(|end| false_label..end),
+                    });
+                }
+                Instr::Switch(switch) => {
+                    let mut cases = vec![];
+                    let mut default = None;
+                    let mut next = offsets.resolve_jump(at(i), switch.first_case());
+                    while let Some(label) = next.filter(|&l| l > at(i)) {
+                        match code.get(usize::from(label)) {
+                            Some(Instr::SwitchLabel(l)) => {
+                                if let Some(body) = offsets.resolve_jump(label, l.body()) {
+                                    cases.push(SwitchCase { label, body });
+                                }
+                                next = offsets
+                                    .resolve_jump(label, l.next_case())
+                                    .filter(|&n| n > label);
+                            }
+                            Some(Instr::SwitchDefault) => {
+                                default = Some(label);
+                                break;
+                            }
+                            _ => break,
+                        }
+                    }
+                    regions.push(Region::Switch {
+                        switch: at(i),
+                        cases,
+                        default,
+                    });
+                }
+                _ => {}
+            }
+        }
+
+        regions.sort_by_key(Region::start);
+        Ok(CodeStructure { regions })
+    }
+}
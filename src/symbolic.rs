@@ -0,0 +1,295 @@
+use std::collections::HashSet;
+
+use crate::bundle::ScriptBundle;
+use crate::index::{FunctionIndex, TypeIndex};
+use crate::instr::{Instr, InstrIndex};
+use crate::interp::{Frame, InterpError, Interpreter, Value};
+
+// explores the paths of a function with unknown parameters to check simple properties, it's
+// bounded, paths are abandoned once they run out of steps and values it can't model are Unknown,
+// so it reports violations it can prove rather than proving their absence
+pub struct SymbolicWalker<'a, 'i> {
+    bundle: &'a ScriptBundle<'i>,
+    interp: Interpreter<'a, 'i>,
+    max_paths: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PathProperty {
+    // no path returns null
+    NonNullReturn,
+    // every switch over an enum has a case for each member or a default
+    ExhaustiveSwitch,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Verification {
+    pub counterexamples: Vec<Counterexample>,
+    // paths walked to the end
+    pub paths: usize,
+    // paths abandoned because of the bounds or code the walker can't follow
+    pub incomplete: usize,
+}
+
+impl Verification {
+    #[inline]
+    pub fn holds(&self) -> bool {
+        self.counterexamples.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Counterexample {
+    pub property: PathProperty,
+    // the instruction that violates the property
+    pub at: InstrIndex,
+    // the branches taken to get there
+    pub path: Vec<Branch>,
+    pub detail: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Branch {
+    pub at: InstrIndex,
+    pub target: InstrIndex,
+}
+
+struct State {
+    frame: Frame,
+    pc: usize,
+    path: Vec<Branch>,
+    steps: u64,
+}
+
+impl<'a, 'i> SymbolicWalker<'a, 'i> {
+    pub fn new(bundle: &'a ScriptBundle<'i>) -> Self {
+        let mut interp = Interpreter::new(bundle).with_step_limit(10_000);
+        interp.symbolic = true;
+        Self {
+            bundle,
+            interp,
+            max_paths: 256,
+        }
+    }
+
+    // bounds the number of instructions evaluated on a single path, it's what bounds loops
+    #[inline]
+    pub fn with_step_limit(mut self, step_limit: u64) -> Self {
+        self.interp = self.interp.with_step_limit(step_limit);
+        self
+    }
+
+    #[inline]
+    pub fn with_max_paths(mut self, max_paths: usize) -> Self {
+        self.max_paths = max_paths;
+        self
+    }
+
+    pub fn check(
+        &mut self,
+        function: FunctionIndex,
+        property: PathProperty,
+    ) -> Result<Verification, InterpError> {
+        let fun = self
+            .bundle
+            .get_item(function)
+            .ok_or(InterpError::MissingDefinition(function.into()))?;
+        let args = vec![Value::Unknown; fun.parameters().len()];
+        let mut pending = vec![State {
+            frame: self.interp.frame(fun, &args)?,
+            pc: 0,
+            path: vec![],
+            steps: 0,
+        }];
+
+        let mut result = Verification::default();
+        let mut reported = HashSet::new();
+        while let Some(state) = pending.pop() {
+            if result.paths + result.incomplete >= self.max_paths {
+                result.incomplete += pending.len() + 1;
+                break;
+            }
+            match self.walk(state, property, &mut pending) {
+                Ok(counterexamples) => {
+                    result.paths += 1;
+                    for ce in counterexamples {
+                        if reported.insert(ce.at) {
+                            result.counterexamples.push(ce);
+                        }
+                    }
+                }
+                Err(_) => result.incomplete += 1,
+            }
+        }
+        Ok(result)
+    }
+
+    // follows a path to its end, forking on branches that depend on unknown values
+    fn walk(
+        &mut self,
+        state: State,
+        property: PathProperty,
+        pending: &mut Vec<State>,
+    ) -> Result<Vec<Counterexample>, InterpError> {
+        let State {
+            mut frame,
+            mut pc,
+            mut path,
+            steps,
+        } = state;
+        self.interp.steps = steps;
+        let at = |i: usize| InstrIndex::new(i as u32);
+
+        let mut counterexamples = vec![];
+        while let Some(instr) = frame.code.get(pc) {
+            self.interp.step()?;
+            match instr {
+                Instr::Return => {
+                    let ret = at(pc);
+                    pc += 1;
+                    let value = match frame.code.get(pc) {
+                        None | Some(Instr::Nop) => Value::Void,
+                        Some(_) => self.interp.eval(&mut frame, &mut pc)?,
+                    };
+                    if property == PathProperty::NonNullReturn && value == Value::Null {
+                        counterexamples.push(Counterexample {
+                            property,
+                            at: ret,
+                            path,
+                            detail: "returns null".to_owned(),
+                        });
+                    }
+                    return Ok(counterexamples);
+                }
+                Instr::Jump(jump) => pc = frame.jump(pc, jump.target())?,
+                Instr::JumpIfFalse(jump) => {
+                    let (branch, target) = (pc, frame.jump(pc, jump.target())?);
+                    pc += 1;
+                    match self.interp.eval(&mut frame, &mut pc)? {
+                        Value::Unknown => {
+                            self.fork(&frame, &path, branch, target, pending);
+                            path.push(Branch {
+                                at: at(branch),
+                                target: at(pc),
+                            });
+                        }
+                        val if !val.as_bool()? => pc = target,
+                        _ => {}
+                    }
+                }
+                Instr::Switch(switch) => {
+                    let (branch, typ) = (pc, switch.expr_type());
+                    let mut label = frame.jump(pc, switch.first_case())?;
+                    pc += 1;
+                    let value = self.interp.eval(&mut frame, &mut pc)?;
+
+                    let mut cases = vec![];
+                    let (exit, has_default) = loop {
+                        self.interp.step()?;
+                        match frame.code.get(label) {
+                            Some(Instr::SwitchLabel(case)) => {
+                                let (body, next) = (case.body(), case.next_case());
+                                let mut case_pc = label + 1;
+                                let case = self.interp.eval(&mut frame, &mut case_pc)?;
+                                cases.push((case, frame.jump(label, body)?));
+                                label = frame.jump(label, next)?;
+                            }
+                            Some(Instr::SwitchDefault) => break (label + 1, true),
+                            _ => break (label, false),
+                        }
+                    };
+
+                    if property == PathProperty::ExhaustiveSwitch && !has_default {
+                        let missing = self.missing_members(typ, &cases);
+                        if !missing.is_empty() {
+                            counterexamples.push(Counterexample {
+                                property,
+                                at: at(branch),
+                                path: path.clone(),
+                                detail: format!("missing cases: {}", missing.join(", ")),
+                            });
+                        }
+                    }
+
+                    let matched = cases.iter().find(|(case, _)| *case == value);
+                    pc = match matched {
+                        Some(&(_, body)) if value != Value::Unknown => body,
+                        _ if value != Value::Unknown => exit,
+                        _ => {
+                            for &(_, body) in &cases {
+                                self.fork(&frame, &path, branch, body, pending);
+                            }
+                            path.push(Branch {
+                                at: at(branch),
+                                target: at(exit),
+                            });
+                            exit
+                        }
+                    };
+                }
+                Instr::SwitchLabel(case) => pc = frame.jump(pc, case.body())?,
+                Instr::Assign => {
+                    pc += 1;
+                    let place = self.interp.place(&mut frame, &mut pc)?;
+                    let value = self.interp.eval(&mut frame, &mut pc)?;
+                    *frame.resolve(&place)? = value;
+                }
+                Instr::Nop | Instr::Breakpoint(_) | Instr::Profile(_) | Instr::SwitchDefault => {
+                    pc += 1;
+                }
+                _ => {
+                    self.interp.eval(&mut frame, &mut pc)?;
+                }
+            }
+        }
+        Ok(counterexamples)
+    }
+
+    fn fork(
+        &self,
+        frame: &Frame,
+        path: &[Branch],
+        branch: usize,
+        target: usize,
+        pending: &mut Vec<State>,
+    ) {
+        let mut path = path.to_vec();
+        path.push(Branch {
+            at: InstrIndex::new(branch as u32),
+            target: InstrIndex::new(target as u32),
+        });
+        pending.push(State {
+            frame: frame.clone(),
+            pc: target,
+            path,
+            steps: self.interp.steps,
+        });
+    }
+
+    // names of the members of the enum switched over that no case handles, enums are matched to
+    // their types by name
+    fn missing_members(&self, typ: TypeIndex, cases: &[(Value, usize)]) -> Vec<String> {
+        let Some(typ) = self.bundle.get_item(typ) else {
+            return vec![];
+        };
+        let Some((_, enum_)) = self.bundle.enums().find(|(_, e)| e.name() == typ.name()) else {
+            return vec![];
+        };
+        enum_
+            .values()
+            .iter()
+            .filter_map(|&member| self.bundle.get_item(member))
+            .filter(|member| {
+                !cases
+                    .iter()
+                    .any(|(case, _)| *case == Value::Int(member.value()))
+            })
+            .map(|member| {
+                self.bundle
+                    .get_item(member.name())
+                    .unwrap_or_default()
+                    .to_owned()
+            })
+            .collect()
+    }
+}
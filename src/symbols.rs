@@ -0,0 +1,158 @@
+use std::fmt::Write as _;
+use std::io;
+
+use crate::bundle::ScriptBundle;
+use crate::definition::{Definition, DefinitionKind};
+use crate::index::TypeIndex;
+
+// a flat listing of every definition in a bundle, meant for spreadsheets and other tools
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SymbolTable {
+    pub symbols: Vec<Symbol>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Symbol {
+    pub index: u32,
+    pub kind: DefinitionKind,
+    // fully-qualified name, e.g. `Class.Function`
+    pub name: String,
+    // zero for top-level definitions
+    pub parent: u32,
+    // raw bits of the flag set of the definition, zero for kinds without flags
+    pub flags: u32,
+    pub signature: String,
+}
+
+impl SymbolTable {
+    pub fn write_csv(&self, mut writer: impl io::Write) -> io::Result<()> {
+        writeln!(writer, "index,kind,name,parent,flags,signature")?;
+        for sym in &self.symbols {
+            writeln!(
+                writer,
+                "{},{},{},{},{},{}",
+                sym.index,
+                sym.kind.name(),
+                csv_field(&sym.name),
+                sym.parent,
+                sym.flags,
+                csv_field(&sym.signature)
+            )?;
+        }
+        Ok(())
+    }
+
+    #[cfg(all(feature = "serde", feature = "serde_json"))]
+    pub fn write_json(&self, writer: impl io::Write) -> serde_json::Result<()> {
+        serde_json::to_writer(writer, self)
+    }
+}
+
+impl ScriptBundle<'_> {
+    pub fn symbol_table(&self) -> SymbolTable {
+        let symbols = (1..self.definition_count())
+            .filter_map(|index| {
+                let def = self.definition(index)?;
+                Some(Symbol {
+                    index,
+                    kind: def.kind(),
+                    name: qualified_name(self, index).unwrap_or_default(),
+                    parent: def.parent(),
+                    flags: flags(def),
+                    signature: signature(self, def),
+                })
+            })
+            .collect();
+        SymbolTable { symbols }
+    }
+}
+
+pub(crate) fn flags(def: &Definition<'_>) -> u32 {
+    match def {
+        Definition::Class(class) => u16::from(class.flags()).into(),
+        Definition::Function(fun) => u32::from(fun.flags()),
+        Definition::Parameter(param) => u8::from(param.flags()).into(),
+        Definition::Local(local) => u8::from(local.flags()).into(),
+        Definition::Field(field) => u16::from(field.flags()).into(),
+        _ => 0,
+    }
+}
+
+pub(crate) fn qualified_name(bundle: &ScriptBundle<'_>, index: u32) -> Option<String> {
+    let def = bundle.definition(index)?;
+    let name = bundle.get_item(def.name())?;
+    match def.parent() {
+        0 => Some(name.to_owned()),
+        parent => {
+            let parent = bundle.definition(parent)?;
+            let prefix = bundle.get_item(parent.name())?;
+            Some(format!("{prefix}.{name}"))
+        }
+    }
+}
+
+// a textual summary of the parts of a definition that other scripts depend on
+pub(crate) fn signature(bundle: &ScriptBundle<'_>, def: &Definition<'_>) -> String {
+    let type_name = |index: TypeIndex| {
+        bundle.get_item(index).map_or_else(
+            || "<unknown>".to_owned(),
+            |typ| typ.display(bundle).to_string(),
+        )
+    };
+    match def {
+        Definition::Type(typ) => typ.display(bundle).to_string(),
+        Definition::Class(class) => {
+            let mut sig = String::new();
+            if let Some(base) = class.base().and_then(|base| bundle.get_item(base)) {
+                let base = bundle.get_item(base.name()).unwrap_or_default();
+                write!(sig, "extends {base} ").ok();
+            }
+            sig.push('{');
+            for field in class.fields().iter().filter_map(|&f| bundle.get_item(f)) {
+                let name = bundle.get_item(field.name()).unwrap_or_default();
+                write!(sig, " {name}: {};", type_name(field.typ())).ok();
+            }
+            sig.push_str(" }");
+            sig
+        }
+        Definition::Function(fun) => {
+            let params = fun
+                .parameters()
+                .iter()
+                .filter_map(|&p| bundle.get_item(p))
+                .map(|param| type_name(param.typ()))
+                .collect::<Vec<_>>()
+                .join(", ");
+            match fun.return_type(bundle) {
+                Some(ret) => format!("({params}) -> {}", type_name(ret)),
+                None => format!("({params})"),
+            }
+        }
+        Definition::Enum(enum_) => enum_
+            .values()
+            .iter()
+            .filter_map(|&v| bundle.get_item(v))
+            .map(|member| {
+                let name = bundle.get_item(member.name()).unwrap_or_default();
+                format!("{name} = {}", member.value())
+            })
+            .collect::<Vec<_>>()
+            .join(", "),
+        Definition::EnumMember(member) => member.value().to_string(),
+        Definition::Parameter(param) => type_name(param.typ()),
+        Definition::Local(local) => type_name(local.typ()),
+        Definition::Field(field) => type_name(field.typ()),
+        Definition::SourceFile(file) => file.path().to_owned(),
+        Definition::Bitfield => String::new(),
+    }
+}
+
+pub(crate) fn csv_field(value: &str) -> std::borrow::Cow<'_, str> {
+    if value.contains([',', '"', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\"")).into()
+    } else {
+        value.into()
+    }
+}
@@ -0,0 +1,225 @@
+use std::fmt;
+
+use crate::bundle::{PoolError, ScriptBundle};
+use crate::definition::{Definition, Type, TypeKind};
+use crate::index::{CNameIndex, TypeIndex};
+
+impl Type {
+    // formats the type the way it's written in redscript, e.g. `array<ref<GameObject>>`
+    #[inline]
+    pub fn display<'a, 'i>(&'a self, bundle: &'a ScriptBundle<'i>) -> TypeDisplay<'a, 'i> {
+        TypeDisplay { typ: self, bundle }
+    }
+}
+
+#[derive(Debug)]
+pub struct TypeDisplay<'a, 'i> {
+    typ: &'a Type,
+    bundle: &'a ScriptBundle<'i>,
+}
+
+impl fmt::Display for TypeDisplay<'_, '_> {
+    // the nesting chain is bounded, so types that are dangling or nested in themselves are written
+    // as `<unknown>` rather than failing
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut suffixes = vec![];
+        let mut innermost = None;
+        for typ in self.typ.nesting(self.bundle) {
+            match typ.kind() {
+                TypeKind::Primitive | TypeKind::Class => {
+                    innermost = Some(self.bundle.get_item(typ.name()).unwrap_or("<unknown>"));
+                    break;
+                }
+                TypeKind::Box(_) => f.write_str("ref<")?,
+                TypeKind::WeakRef(_) => f.write_str("wref<")?,
+                TypeKind::Array(_) => f.write_str("array<")?,
+                TypeKind::StaticArray { size, .. } => {
+                    f.write_str("[")?;
+                    suffixes.push(format!("; {size}]"));
+                    continue;
+                }
+                TypeKind::ScriptRef(_) => f.write_str("script_ref<")?,
+            }
+            suffixes.push(">".to_owned());
+        }
+        f.write_str(innermost.unwrap_or("<unknown>"))?;
+        suffixes
+            .iter()
+            .rev()
+            .try_for_each(|suffix| f.write_str(suffix))
+    }
+}
+
+impl ScriptBundle<'_> {
+    // parses a type written in redscript syntax and returns an index of a matching type,
+    // composite types that don't exist in the bundle yet are defined
+    pub fn parse_type(&mut self, src: &str) -> Result<TypeIndex, TypeParseError> {
+        let mut parser = Parser { src, pos: 0 };
+        let index = parser.parse(self)?;
+        parser.skip_whitespace();
+        if parser.pos != src.len() {
+            return Err(parser.error("unexpected trailing input"));
+        }
+        Ok(index)
+    }
+
+    fn find_type(&self, pred: impl Fn(&Type) -> bool) -> Option<TypeIndex> {
+        self.definitions()
+            .enumerate()
+            .skip(1)
+            .find_map(|(i, def)| match def {
+                Definition::Type(typ) if pred(typ) => TypeIndex::from_usize(i),
+                _ => None,
+            })
+    }
+
+    fn intern_type(&mut self, kind: TypeKind) -> Result<TypeIndex, TypeParseError> {
+        if let Some(index) = self.find_type(|typ| *typ.kind() == kind) {
+            return Ok(index);
+        }
+        let typ = Type::new(CNameIndex::UNDEFINED, kind);
+        let name = game_type_name(self, &typ)
+            .ok_or_else(|| TypeParseError::UnknownType(format!("{:?}", typ.kind())))?;
+        let name = self
+            .cnames_mut()
+            .get_or_add(name)
+            .map_err(TypeParseError::Pool)?;
+        Ok(self.define(Type::new(name, typ.kind().clone())))
+    }
+}
+
+// the name the game gives a type, names of composite types are derived from the innermost one,
+// e.g. `array:handle:GameObject`
+pub(crate) fn game_type_name(bundle: &ScriptBundle<'_>, typ: &Type) -> Option<String> {
+    game_type_name_with(bundle, typ, |innermost| {
+        Some(bundle.get_item(innermost.name())?.to_owned())
+    })
+}
+
+// like `game_type_name`, but with the name of the innermost type given by `innermost`
+pub(crate) fn game_type_name_with(
+    bundle: &ScriptBundle<'_>,
+    typ: &Type,
+    innermost: impl FnOnce(&Type) -> Option<String>,
+) -> Option<String> {
+    let chain = typ.nesting(bundle).collect::<Vec<_>>();
+    let (last, wrappers) = chain.split_last()?;
+    if last.element_type().is_some() {
+        return None;
+    }
+    let mut name = innermost(last)?;
+    for wrapper in wrappers.iter().rev() {
+        name = match wrapper.kind() {
+            TypeKind::Box(_) => format!("handle:{name}"),
+            TypeKind::WeakRef(_) => format!("whandle:{name}"),
+            TypeKind::Array(_) => format!("array:{name}"),
+            TypeKind::StaticArray { size, .. } => format!("[{size}]{name}"),
+            TypeKind::ScriptRef(_) => format!("script_ref:{name}"),
+            TypeKind::Primitive | TypeKind::Class => return None,
+        };
+    }
+    Some(name)
+}
+
+struct Parser<'a> {
+    src: &'a str,
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn parse(&mut self, bundle: &mut ScriptBundle<'_>) -> Result<TypeIndex, TypeParseError> {
+        self.skip_whitespace();
+        if self.eat('[') {
+            let element_type = self.parse(bundle)?;
+            self.expect(';')?;
+            self.skip_whitespace();
+            let digits = self.take_while(|c| c.is_ascii_digit());
+            let size = digits
+                .parse()
+                .map_err(|_| self.error("expected an array size"))?;
+            self.expect(']')?;
+            return bundle.intern_type(TypeKind::StaticArray { element_type, size });
+        }
+
+        let ident = self.take_while(|c| c.is_alphanumeric() || c == '_' || c == '.');
+        if ident.is_empty() {
+            return Err(self.error("expected a type name"));
+        }
+        let wrap: Option<fn(TypeIndex) -> TypeKind> = match ident {
+            "ref" => Some(TypeKind::Box),
+            "wref" => Some(TypeKind::WeakRef),
+            "array" => Some(TypeKind::Array),
+            "script_ref" => Some(TypeKind::ScriptRef),
+            _ => None,
+        };
+        match wrap {
+            Some(wrap) => {
+                self.expect('<')?;
+                let inner = self.parse(bundle)?;
+                self.expect('>')?;
+                bundle.intern_type(wrap(inner))
+            }
+            None => bundle
+                .find_type(|typ| {
+                    matches!(typ.kind(), TypeKind::Primitive | TypeKind::Class)
+                        && bundle.get_item(typ.name()) == Some(ident)
+                })
+                .ok_or_else(|| TypeParseError::UnknownType(ident.to_owned())),
+        }
+    }
+
+    fn take_while(&mut self, pred: impl Fn(char) -> bool) -> &str {
+        let start = self.pos;
+        let rest = &self.src[start..];
+        self.pos += rest.find(|c| !pred(c)).unwrap_or(rest.len());
+        &self.src[start..self.pos]
+    }
+
+    fn skip_whitespace(&mut self) {
+        self.take_while(char::is_whitespace);
+    }
+
+    fn eat(&mut self, expected: char) -> bool {
+        self.skip_whitespace();
+        if self.src[self.pos..].starts_with(expected) {
+            self.pos += expected.len_utf8();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), TypeParseError> {
+        if self.eat(expected) {
+            Ok(())
+        } else {
+            Err(self.error("unexpected character"))
+        }
+    }
+
+    fn error(&self, message: &'static str) -> TypeParseError {
+        TypeParseError::Syntax {
+            message,
+            pos: self.pos,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TypeParseError {
+    Syntax { message: &'static str, pos: usize },
+    UnknownType(String),
+    Pool(PoolError),
+}
+
+impl fmt::Display for TypeParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TypeParseError::Syntax { message, pos } => write!(f, "{message} at {pos}"),
+            TypeParseError::UnknownType(name) => write!(f, "unknown type {name}"),
+            TypeParseError::Pool(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl std::error::Error for TypeParseError {}
@@ -0,0 +1,83 @@
+use crate::bundle::ScriptBundle;
+use crate::definition::PoolKind;
+
+// the number of references to every entry of the string pools and to every definition, entries
+// with none can be dropped by compaction
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PoolUsage {
+    pub cnames: Vec<u32>,
+    pub tdb_ids: Vec<u32>,
+    pub resources: Vec<u32>,
+    pub strings: Vec<u32>,
+    // indexed by definition index, references of definitions to themselves are not counted
+    pub definitions: Vec<u32>,
+}
+
+impl PoolUsage {
+    #[inline]
+    pub fn pool(&self, kind: PoolKind) -> &[u32] {
+        match kind {
+            PoolKind::CName => &self.cnames,
+            PoolKind::TweakDbId => &self.tdb_ids,
+            PoolKind::Resource => &self.resources,
+            PoolKind::String => &self.strings,
+        }
+    }
+
+    pub fn unused_strings(&self, kind: PoolKind) -> impl Iterator<Item = u32> + '_ {
+        unused(self.pool(kind))
+    }
+
+    // the placeholder definition at index 0 is never reported
+    pub fn unused_definitions(&self) -> impl Iterator<Item = u32> + '_ {
+        unused(&self.definitions).filter(|&index| index != 0)
+    }
+
+    fn pool_mut(&mut self, kind: PoolKind) -> &mut Vec<u32> {
+        match kind {
+            PoolKind::CName => &mut self.cnames,
+            PoolKind::TweakDbId => &mut self.tdb_ids,
+            PoolKind::Resource => &mut self.resources,
+            PoolKind::String => &mut self.strings,
+        }
+    }
+}
+
+fn unused(counts: &[u32]) -> impl Iterator<Item = u32> + '_ {
+    counts
+        .iter()
+        .enumerate()
+        .filter(|&(_, &count)| count == 0)
+        .map(|(index, _)| index as u32)
+}
+
+impl ScriptBundle<'_> {
+    // counts references from every definition, references that point outside of the pools are
+    // ignored and function bodies that can't be decoded only contribute their definition part
+    pub fn pool_usage(&self) -> PoolUsage {
+        let mut usage = PoolUsage {
+            cnames: vec![0; self.cnames().len()],
+            tdb_ids: vec![0; self.tdb_ids().len()],
+            resources: vec![0; self.resources().len()],
+            strings: vec![0; self.strings().len()],
+            definitions: vec![0; self.definition_count() as usize],
+        };
+        for (index, def) in self.definitions().enumerate().skip(1) {
+            for (kind, string) in def.referenced_strings() {
+                if let Some(count) = usage.pool_mut(kind).get_mut(string as usize) {
+                    *count += 1;
+                }
+            }
+            for target in def.referenced_definitions() {
+                if target as usize == index {
+                    continue;
+                }
+                if let Some(count) = usage.definitions.get_mut(target as usize) {
+                    *count += 1;
+                }
+            }
+        }
+        usage
+    }
+}
@@ -1,11 +1,23 @@
+use std::borrow::Cow;
 use std::mem;
+use std::path::Path;
 
 use byte::ctx::{Endianess, Len};
 use byte::{BytesExt, Measure, TryRead, TryWrite};
+use smallvec::SmallVec;
 
 use crate::index::NzPoolIndex;
 use crate::Str;
 
+// the hasher used by the maps of the crate, without the `ahash` feature the one from std is used
+#[cfg(feature = "ahash")]
+pub(crate) type RandomState = ahash::RandomState;
+#[cfg(not(feature = "ahash"))]
+pub(crate) type RandomState = std::collections::hash_map::RandomState;
+
+// the lists of children of definitions, most of them are short enough to be stored inline
+pub(crate) type ChildList<A> = SmallVec<[A; 4]>;
+
 pub struct Prefixed<Ctx>(pub Ctx);
 
 impl<'a, A, Ctx> TryRead<'a, Prefixed<Ctx>> for Vec<A>
@@ -31,7 +43,10 @@ where
 {
     fn try_write(&self, bytes: &mut [u8], Prefixed(ctx): Prefixed<Ctx>) -> byte::Result<usize> {
         let offset = &mut 0;
-        bytes.write(offset, &(self.len() as u32), ctx)?;
+        let len = u32::try_from(self.len()).map_err(|_| byte::Error::BadInput {
+            err: "too many items for a length prefix",
+        })?;
+        bytes.write(offset, &len, ctx)?;
         for item in self {
             bytes.write(offset, item, ctx)?;
         }
@@ -51,6 +66,55 @@ where
     }
 }
 
+impl<'a, A, Ctx> TryRead<'a, Prefixed<Ctx>> for SmallVec<A>
+where
+    A: smallvec::Array,
+    A::Item: TryRead<'a, Ctx>,
+    Ctx: Endianess,
+{
+    fn try_read(bytes: &'a [u8], Prefixed(ctx): Prefixed<Ctx>) -> byte::Result<(Self, usize)> {
+        let offset = &mut 0;
+        let len: u32 = bytes.read(offset, ctx)?;
+        let result = bytes
+            .read_iter(offset, ctx)
+            .take(len as _)
+            .collect::<byte::Result<SmallVec<_>>>()?;
+        Ok((result, *offset))
+    }
+}
+
+impl<A, Ctx> TryWrite<Prefixed<Ctx>> for SmallVec<A>
+where
+    A: smallvec::Array,
+    A::Item: TryWrite<Ctx>,
+    Ctx: Endianess,
+{
+    fn try_write(&self, bytes: &mut [u8], Prefixed(ctx): Prefixed<Ctx>) -> byte::Result<usize> {
+        let offset = &mut 0;
+        let len = u32::try_from(self.len()).map_err(|_| byte::Error::BadInput {
+            err: "too many items for a length prefix",
+        })?;
+        bytes.write(offset, &len, ctx)?;
+        for item in self {
+            bytes.write(offset, item, ctx)?;
+        }
+        Ok(*offset)
+    }
+}
+
+impl<A, Ctx> Measure<Prefixed<Ctx>> for SmallVec<A>
+where
+    A: smallvec::Array,
+    A::Item: Measure<Ctx>,
+    Ctx: Copy,
+{
+    fn measure(&self, Prefixed(ctx): Prefixed<Ctx>) -> usize {
+        let len = mem::size_of::<u32>();
+        let items: usize = self.iter().map(|item| item.measure(ctx)).sum();
+        len + items
+    }
+}
+
 impl<'a, Ctx> TryRead<'a, Prefixed<Ctx>> for Str<'a>
 where
     Ctx: Endianess,
@@ -69,7 +133,10 @@ where
 {
     fn try_write(&self, bytes: &mut [u8], Prefixed(ctx): Prefixed<Ctx>) -> byte::Result<usize> {
         let offset = &mut 0;
-        bytes.write(offset, &(self.len() as u16), ctx)?;
+        let len: u16 = self.len().try_into().map_err(|_| byte::Error::BadInput {
+            err: "string is too long for its length prefix",
+        })?;
+        bytes.write(offset, &len, ctx)?;
         bytes.write(offset, self.as_str(), Len(self.len()))?;
         Ok(*offset)
     }
@@ -189,3 +256,85 @@ macro_rules! impl_bitfield_read_write {
 }
 
 pub(crate) use impl_bitfield_read_write;
+
+// the FNV-1a 64-bit hash the game uses to identify names
+pub fn fnv1a64(str: &str) -> u64 {
+    const OFFSET_BASIS: u64 = 0xCBF2_9CE4_8422_2325;
+    const PRIME: u64 = 0x0000_0100_0000_01B3;
+
+    str.bytes().fold(OFFSET_BASIS, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(PRIME)
+    })
+}
+
+// prefixes absolute paths with `\\?\` on windows to lift the MAX_PATH limit,
+// caches often live deep inside game library directories
+#[cfg(windows)]
+pub fn extended_path(path: &Path) -> Cow<'_, Path> {
+    use std::ffi::OsString;
+    use std::path::{Component, Prefix};
+
+    let Ok(absolute) = std::path::absolute(path) else {
+        return Cow::Borrowed(path);
+    };
+    let mut components = absolute.components();
+    let Some(Component::Prefix(prefix)) = components.next() else {
+        return Cow::Borrowed(path);
+    };
+    let mut extended = OsString::new();
+    match prefix.kind() {
+        Prefix::Disk(_) => {
+            extended.push(r"\\?\");
+            extended.push(absolute.as_os_str());
+        }
+        Prefix::UNC(server, share) => {
+            extended.push(r"\\?\UNC\");
+            extended.push(server);
+            extended.push(r"\");
+            extended.push(share);
+            extended.push(components.as_path().as_os_str());
+        }
+        // already verbatim or a device path
+        _ => return Cow::Borrowed(path),
+    }
+    Cow::Owned(extended.into())
+}
+
+#[cfg(not(windows))]
+#[inline]
+pub fn extended_path(path: &Path) -> Cow<'_, Path> {
+    Cow::Borrowed(path)
+}
+
+// moves borrowed strings and bytes to storage that lives for 'b
+pub(crate) trait Relocate<'b> {
+    fn relocate_str(&self, str: Str<'_>) -> Str<'b>;
+    fn relocate_bytes(&self, bytes: Cow<'_, [u8]>) -> Cow<'b, [u8]>;
+}
+
+pub(crate) struct Owned;
+
+impl Relocate<'static> for Owned {
+    #[inline]
+    fn relocate_str(&self, str: Str<'_>) -> Str<'static> {
+        str.into_owned()
+    }
+
+    #[inline]
+    fn relocate_bytes(&self, bytes: Cow<'_, [u8]>) -> Cow<'static, [u8]> {
+        Cow::Owned(bytes.into_owned())
+    }
+}
+
+#[cfg(feature = "bumpalo")]
+impl<'b> Relocate<'b> for &'b bumpalo::Bump {
+    #[inline]
+    fn relocate_str(&self, str: Str<'_>) -> Str<'b> {
+        Str::borrowed(self.alloc_str(&str))
+    }
+
+    #[inline]
+    fn relocate_bytes(&self, bytes: Cow<'_, [u8]>) -> Cow<'b, [u8]> {
+        Cow::Borrowed(self.alloc_slice_copy(&bytes))
+    }
+}
@@ -0,0 +1,360 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use bitfield_struct::bitfield;
+
+use crate::bundle::{EnumSizeRepair, LinkRepair, PoolItemIndex, ScriptBundle};
+use crate::definition::Definition;
+use crate::index::{ClassIndex, EnumIndex, EnumValueIndex, FunctionIndex, NzPoolIndex};
+use crate::instr::ByteOffset;
+use crate::monitor::{Monitor, Phase};
+
+// selects the fixes applied by ScriptBundle::fix
+#[bitfield(u16)]
+#[derive(PartialEq, Eq)]
+pub struct FixSet {
+    // parent references that disagree with the containers listing the definitions
+    pub links: bool,
+    // size operands of enum conversions that disagree with the declared size of the enum
+    pub enum_sizes: bool,
+    // entries of member lists pointing at missing definitions or definitions of the wrong kind,
+    // these are removed from the lists
+    pub dangling_refs: bool,
+    // methods of a class with the same mangled name as a later one, the earlier ones are removed
+    // along with their parameters and locals and whatever referred to them refers to the last one
+    pub duplicate_functions: bool,
+    #[bits(12)]
+    __: u16,
+}
+
+impl FixSet {
+    // fixes that only make the bundle agree with itself and don't drop anything
+    pub const SAFE: Self = Self::new().with_links(true).with_enum_sizes(true);
+    pub const ALL: Self = Self::SAFE
+        .with_dangling_refs(true)
+        .with_duplicate_functions(true);
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Issue {
+    BrokenLink(LinkRepair),
+    EnumSizeMismatch(EnumSizeRepair),
+    DanglingReference {
+        container: u32,
+        target: u32,
+    },
+    EnumValueOutOfRange {
+        enum_: EnumIndex,
+        member: EnumValueIndex,
+    },
+    DuplicateEnumValue {
+        enum_: EnumIndex,
+        value: i64,
+    },
+    InvalidStaticArrayType {
+        function: u32,
+        offset: ByteOffset,
+    },
+    DuplicateFunction {
+        class: ClassIndex,
+        duplicate: FunctionIndex,
+        kept: FunctionIndex,
+    },
+}
+
+impl Issue {
+    // whether one of the fixes addresses the issue
+    pub fn is_fixable(&self) -> bool {
+        matches!(
+            self,
+            Issue::BrokenLink(_)
+                | Issue::EnumSizeMismatch(_)
+                | Issue::DanglingReference { .. }
+                | Issue::DuplicateFunction { .. }
+        )
+    }
+
+    // whether the game accepts bundles with the issue, e.g. enum members that alias each other
+    #[inline]
+    pub fn is_warning(&self) -> bool {
+        matches!(self, Issue::DuplicateEnumValue { .. })
+    }
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Issue::BrokenLink(link) => write!(
+                f,
+                "definition {} has parent {} but is listed by {}",
+                link.index, link.old_parent, link.new_parent
+            ),
+            Issue::EnumSizeMismatch(repair) => write!(
+                f,
+                "enum conversion at {} in function {} has size {}, expected {}",
+                u32::from(repair.offset),
+                repair.function,
+                repair.old_size,
+                repair.new_size
+            ),
+            Issue::DanglingReference { container, target } => write!(
+                f,
+                "definition {container} refers to a missing or mismatched definition {target}"
+            ),
+            Issue::EnumValueOutOfRange { enum_, member } => {
+                write!(f, "value of {member} doesn't fit the size of {enum_}")
+            }
+            Issue::DuplicateEnumValue { enum_, value } => {
+                write!(f, "{enum_} has multiple members with value {value}")
+            }
+            Issue::InvalidStaticArrayType { function, offset } => write!(
+                f,
+                "static array instruction at {} in function {function} has a non-static array type",
+                u32::from(*offset)
+            ),
+            Issue::DuplicateFunction {
+                class,
+                duplicate,
+                kept,
+            } => write!(f, "{duplicate} of {class} has the same name as {kept}"),
+        }
+    }
+}
+
+impl<'i> ScriptBundle<'i> {
+    // runs every check over the bundle, see Issue::is_fixable for the ones `fix` can address
+    #[inline]
+    pub fn validate(&self) -> Vec<Issue> {
+        // validation can only fail when it's cancelled
+        self.validate_monitored(Monitor::new()).unwrap_or_default()
+    }
+
+    #[inline]
+    pub fn validate_monitored(&self, mut monitor: Monitor<'_>) -> byte::Result<Vec<Issue>> {
+        self.validate_from(1, &mut monitor)
+    }
+
+    // runs the checks over the definitions from `start` onwards, e.g. the ones added to a bundle
+    pub(crate) fn validate_from(
+        &self,
+        start: u32,
+        monitor: &mut Monitor<'_>,
+    ) -> byte::Result<Vec<Issue>> {
+        let mut issues = self
+            .dangling_refs(start)
+            .into_iter()
+            .map(|(container, target)| Issue::DanglingReference { container, target })
+            .collect::<Vec<_>>();
+        issues.extend(
+            self.broken_links()
+                .into_iter()
+                .filter(|link| link.index >= start)
+                .map(Issue::BrokenLink),
+        );
+
+        let enums = self.enums_by_name();
+        let first = start.max(1);
+        let total = self.definition_count().saturating_sub(first);
+        for index in first..self.definition_count() {
+            monitor.step(Phase::Validate, index - first + 1, total)?;
+            match self.definition(index) {
+                Some(Definition::Class(c)) => {
+                    let Some(class) = NzPoolIndex::new(index) else {
+                        continue;
+                    };
+                    issues.extend(c.duplicate_methods(self).into_iter().map(
+                        |(duplicate, kept)| Issue::DuplicateFunction {
+                            class,
+                            duplicate,
+                            kept,
+                        },
+                    ));
+                }
+                Some(Definition::Function(fun)) => {
+                    if let Some((_, repairs)) = self.enum_size_repairs(index, &enums) {
+                        issues.extend(repairs.into_iter().map(Issue::EnumSizeMismatch));
+                    }
+                    issues.extend(fun.invalid_static_array_instrs(self).map(|offset| {
+                        Issue::InvalidStaticArrayType {
+                            function: index,
+                            offset,
+                        }
+                    }));
+                }
+                Some(Definition::Enum(e)) => {
+                    let Some(enum_) = NzPoolIndex::new(index) else {
+                        continue;
+                    };
+                    issues.extend(
+                        e.values_out_of_range(self)
+                            .map(|member| Issue::EnumValueOutOfRange { enum_, member }),
+                    );
+                    issues.extend(
+                        e.duplicate_values(self)
+                            .into_iter()
+                            .map(|(value, _)| Issue::DuplicateEnumValue { enum_, value }),
+                    );
+                }
+                _ => {}
+            }
+        }
+        Ok(issues)
+    }
+
+    // applies the selected fixes and returns the issues that were fixed, dangling references are
+    // stripped first so that links are rebuilt from the cleaned up lists
+    pub fn fix(&mut self, fixes: FixSet) -> Vec<Issue> {
+        let mut fixed = vec![];
+        if fixes.dangling_refs() {
+            fixed.extend(
+                self.strip_dangling_refs()
+                    .into_iter()
+                    .map(|(container, target)| Issue::DanglingReference { container, target }),
+            );
+        }
+        if fixes.duplicate_functions() {
+            fixed.extend(self.drop_duplicate_functions().into_iter().map(
+                |(class, duplicate, kept)| Issue::DuplicateFunction {
+                    class,
+                    duplicate,
+                    kept,
+                },
+            ));
+        }
+        if fixes.links() {
+            fixed.extend(self.repair_links().into_iter().map(Issue::BrokenLink));
+        }
+        if fixes.enum_sizes() {
+            fixed.extend(
+                self.repair_enum_sizes()
+                    .into_iter()
+                    .map(Issue::EnumSizeMismatch),
+            );
+        }
+        fixed
+    }
+
+    fn dangling_refs(&self, start: u32) -> Vec<(u32, u32)> {
+        let mut refs = vec![];
+        for index in start.max(1)..self.definition_count() {
+            let mut check = |targets: &[u32]| {
+                refs.extend(targets.iter().map(|&target| (index, target)));
+            };
+            match self.definition(index) {
+                Some(Definition::Class(c)) => {
+                    check(&self.missing(c.methods()));
+                    check(&self.missing(c.fields()));
+                    check(&self.missing(c.overrides()));
+                }
+                Some(Definition::Enum(e)) => check(&self.missing(e.values())),
+                Some(Definition::Function(f)) => {
+                    check(&self.missing(f.parameters()));
+                    check(&self.missing(f.locals()));
+                }
+                _ => {}
+            }
+        }
+        refs
+    }
+
+    fn strip_dangling_refs(&mut self) -> Vec<(u32, u32)> {
+        let refs = self.dangling_refs(1);
+        for &(container, target) in &refs {
+            self.modify_definition(container, |def| {
+                let keep = |index: u32| index != target;
+                match def {
+                    Definition::Class(c) => {
+                        **c = c
+                            .as_ref()
+                            .clone()
+                            .with_methods(retain(c.methods(), keep))
+                            .with_fields(retain(c.fields(), keep))
+                            .with_overrides(retain(c.overrides(), keep));
+                    }
+                    Definition::Enum(e) => {
+                        **e = e.as_ref().clone().with_values(retain(e.values(), keep));
+                    }
+                    Definition::Function(f) => {
+                        let parameters = retain(f.parameters(), keep);
+                        let locals = retain(f.locals(), keep);
+                        **f = f
+                            .as_ref()
+                            .clone()
+                            .with_parameters(parameters)
+                            .with_locals(locals);
+                    }
+                    _ => {}
+                }
+            });
+        }
+        refs
+    }
+
+    // bodies that can't be decoded keep referring to the removed functions
+    fn drop_duplicate_functions(&mut self) -> Vec<(ClassIndex, FunctionIndex, FunctionIndex)> {
+        let mut duplicates = vec![];
+        for (class, c) in self.classes() {
+            duplicates.extend(
+                c.duplicate_methods(self)
+                    .into_iter()
+                    .map(|(duplicate, kept)| (class, duplicate, kept)),
+            );
+        }
+        let remap = duplicates
+            .iter()
+            .map(|&(_, duplicate, kept)| (u32::from(duplicate), u32::from(kept)))
+            .collect::<HashMap<_, _>>();
+
+        for &(class, duplicate, _) in &duplicates {
+            self.modify_definition(class.into(), |def| {
+                if let Definition::Class(c) = def {
+                    let methods = retain(c.methods(), |index| index != u32::from(duplicate));
+                    **c = c.as_ref().clone().with_methods(methods);
+                }
+            });
+            if let Some(Definition::Function(fun)) = self.remove(duplicate) {
+                for &param in fun.parameters() {
+                    self.remove(param);
+                }
+                for &local in fun.locals() {
+                    self.remove(local);
+                }
+            }
+        }
+
+        for index in 1..self.definition_count() {
+            let refers = self.definition(index).is_some_and(|def| {
+                def.referenced_definitions()
+                    .iter()
+                    .any(|index| remap.contains_key(index))
+            });
+            if refers {
+                self.modify_definition(index, |def| {
+                    if def.decode_body().is_ok() {
+                        def.map_definitions(|index| remap.get(&index).copied().unwrap_or(index));
+                    }
+                });
+            }
+        }
+        duplicates
+    }
+
+    fn missing<A>(&self, indices: &[NzPoolIndex<A>]) -> Vec<u32>
+    where
+        NzPoolIndex<A>: PoolItemIndex<'i>,
+    {
+        indices
+            .iter()
+            .filter(|&&index| self.get_item(index).is_none())
+            .map(|&index| index.into())
+            .collect()
+    }
+}
+
+fn retain<A>(indices: &[NzPoolIndex<A>], keep: impl Fn(u32) -> bool) -> Vec<NzPoolIndex<A>> {
+    indices
+        .iter()
+        .copied()
+        .filter(|&index| keep(index.into()))
+        .collect()
+}